@@ -0,0 +1,59 @@
+/*
+    A benchmark for a world where creatures are born & die all the time, as spawning & despawning
+    through commands costs differently to going through queries. The scenario runs for a while
+    before timing anything, so the world being timed is already full of replaced creatures.
+    tests/churn.rs checks the same scenario gets through without problems.
+
+    Run with: cargo bench --bench churn
+*/
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use predator_prey::{read_settings, Settings, SimApi};
+
+// How long the scenario is run for before timing, long enough for thousands of births & deaths
+const STRESS_TICKS: u32 = 600;
+// How many ticks are timed at once
+const TIMED_TICKS: u32 = 10;
+
+// Creatures with little energy that breed as soon as they can, and a fifth of the prey culled
+// every 10 ticks, so the populations stay about the same size while being replaced constantly
+fn churn_settings() -> Settings {
+    let mut settings = read_settings();
+    settings.seed = 1;
+    settings.prey_population = 300;
+    settings.predator_population = 100;
+    settings.prey_life = 400;
+    settings.predator_life = 400;
+    settings.prey_reproduction_energy = 200;
+    settings.predator_reproduction_energy = 200;
+    settings.predator_energy_loss = 4;
+    settings.predation_efficiency = 1.0;
+    settings.max_entities = 800;
+    settings.prevent_extinction = true;
+    settings.min_prey = 100;
+    settings.min_predator = 30;
+    // Enough culls to keep going well past the ticks that are timed
+    settings.perturbations = (1..=STRESS_TICKS)
+        .map(|step| format!("{} cull prey 0.2", step * 10))
+        .collect::<Vec<String>>()
+        .join(";");
+    settings.metrics_output = String::new();
+    settings
+}
+
+fn churn(criterion: &mut Criterion) {
+    let mut api = SimApi::new(churn_settings());
+    api.step(STRESS_TICKS);
+
+    let mut group = criterion.benchmark_group("churn");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(TIMED_TICKS as u64));
+    group.bench_function("step", |bencher| {
+        bencher.iter(|| api.step(TIMED_TICKS));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, churn);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+/*
+    Benchmarks for saving & loading snapshots in both formats, to see how often a big run can
+    afford to be checkpointed.
+
+    Run with: cargo bench --bench snapshots
+*/
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::path::PathBuf;
+
+use predator_prey::{load_snapshot, read_settings, save_snapshot, SimApi, SnapshotFormat};
+
+const POPULATION: i32 = 5000;
+
+fn snapshots(criterion: &mut Criterion) {
+    let mut settings = read_settings();
+    settings.seed = 1;
+    settings.predator_population = POPULATION / 5;
+    settings.prey_population = POPULATION - settings.predator_population;
+    settings.metrics_output = String::new();
+
+    let mut api = SimApi::new(settings);
+    api.step(1);
+    let snapshot = api.snapshot();
+
+    let mut save_group = criterion.benchmark_group("save_snapshot");
+    let mut paths: Vec<(SnapshotFormat, PathBuf)> = Vec::new();
+    for format in [SnapshotFormat::Json, SnapshotFormat::Binary] {
+        let path = std::env::temp_dir().join(format!("bench_snapshot.{}", format.extension()));
+
+        save_group.throughput(Throughput::Elements(POPULATION as u64));
+        save_group.bench_with_input(
+            BenchmarkId::new(format!("{:?}", format), POPULATION),
+            &path,
+            |bencher, path| {
+                bencher.iter(|| save_snapshot(path, &snapshot).unwrap());
+            },
+        );
+
+        paths.push((format, path));
+    }
+    save_group.finish();
+
+    let mut load_group = criterion.benchmark_group("load_snapshot");
+    for (format, path) in paths.iter() {
+        load_group.throughput(Throughput::Elements(POPULATION as u64));
+        load_group.bench_with_input(
+            BenchmarkId::new(format!("{:?}", format), POPULATION),
+            path,
+            |bencher, path| {
+                bencher.iter(|| load_snapshot(path).unwrap());
+            },
+        );
+    }
+    load_group.finish();
+
+    for (_, path) in paths {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+criterion_group!(benches, snapshots);
+criterion_main!(benches);
@@ -0,0 +1,112 @@
+/*
+    Benchmarks for the systems that compare every creature against its neighbors, as they're
+    the ones that get slow first as the populations grow.
+
+    Run with: cargo bench
+*/
+
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use predator_prey::spatial_index::SpatialIndexKind;
+use predator_prey::{
+    build_headless_app, handle_hostile_collisions, read_settings, update_predators, update_preys,
+};
+
+const POPULATIONS: [i32; 3] = [100, 500, 2000];
+
+// Big enough for spreading the detection across the cpu cores to pay off
+const PARALLEL_POPULATION: i32 = 5000;
+
+// Builds a world with the given number of creatures, a fifth of them predators,
+// and runs one frame so everything is spawned and the spatial indexes are filled in
+fn build_world(population: i32, spatial_index: SpatialIndexKind, parallel_detection: bool) -> App {
+    let mut settings = read_settings();
+    settings.seed = 1;
+    settings.predator_population = population / 5;
+    settings.prey_population = population - settings.predator_population;
+    settings.spatial_index = spatial_index;
+    settings.parallel_detection = parallel_detection;
+    settings.metrics_output = String::new();
+
+    let mut app = build_headless_app(settings);
+    app.update();
+    app
+}
+
+fn bench_system<M>(
+    criterion: &mut Criterion,
+    name: &str,
+    system: impl IntoSystem<(), (), M> + Copy + 'static,
+) {
+    let mut group = criterion.benchmark_group(name);
+
+    for spatial_index in [SpatialIndexKind::Grid, SpatialIndexKind::QuadTree] {
+        for population in POPULATIONS {
+            let mut app = build_world(population, spatial_index, false);
+            let system_id = app.world_mut().register_system(system);
+
+            group.throughput(Throughput::Elements(population as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}", spatial_index), population),
+                &population,
+                |bencher, _| {
+                    bencher.iter(|| app.world_mut().run_system(system_id).unwrap());
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+// The same system looking for neighbors one creature at a time and all at once
+fn bench_parallel_detection<M>(
+    criterion: &mut Criterion,
+    name: &str,
+    system: impl IntoSystem<(), (), M> + Copy + 'static,
+) {
+    let mut group = criterion.benchmark_group(format!("{}_parallel_detection", name));
+
+    for parallel_detection in [false, true] {
+        let mut app = build_world(
+            PARALLEL_POPULATION,
+            SpatialIndexKind::Grid,
+            parallel_detection,
+        );
+        let system_id = app.world_mut().register_system(system);
+
+        group.throughput(Throughput::Elements(PARALLEL_POPULATION as u64));
+        group.bench_with_input(
+            BenchmarkId::new(
+                if parallel_detection {
+                    "Parallel"
+                } else {
+                    "SingleThreaded"
+                },
+                PARALLEL_POPULATION,
+            ),
+            &PARALLEL_POPULATION,
+            |bencher, _| {
+                bencher.iter(|| app.world_mut().run_system(system_id).unwrap());
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn systems(criterion: &mut Criterion) {
+    bench_system(criterion, "update_predators", update_predators);
+    bench_system(criterion, "update_preys", update_preys);
+    bench_system(
+        criterion,
+        "handle_hostile_collisions",
+        handle_hostile_collisions,
+    );
+    bench_parallel_detection(criterion, "update_predators", update_predators);
+    bench_parallel_detection(criterion, "update_preys", update_preys);
+}
+
+criterion_group!(benches, systems);
+criterion_main!(benches);
@@ -0,0 +1,122 @@
+/*
+    This file includes all systems & child functions used for
+    remembering when important things happened, so they can be marked on the population plot.
+
+    Examples:
+    - The PlotAnnotations resource that anything can add an event to
+    - Functions that notice the first time the predators boom
+    - Functions that draw the events as lines on a plot, with a tooltip when hovered
+*/
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use egui_plot::{PlotUi, VLine};
+
+use crate::{Predator, SimClock};
+
+// How close the pointer has to be to a line, in pixels, to show what it marks
+const HOVER_DISTANCE: f32 = 4.0;
+
+// The predators have boomed once there are this many times as many as there were at the start
+const BOOM_FACTOR: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnnotationKind {
+    Extinction,
+    Perturbation,
+    PredatorBoom,
+}
+
+impl AnnotationKind {
+    fn color(&self) -> egui::Color32 {
+        match self {
+            AnnotationKind::Extinction => egui::Color32::LIGHT_RED,
+            AnnotationKind::Perturbation => egui::Color32::GRAY,
+            AnnotationKind::PredatorBoom => egui::Color32::LIGHT_YELLOW,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlotAnnotation {
+    pub seconds: f64,
+    pub kind: AnnotationKind,
+    pub description: String,
+}
+
+// Every event worth marking on the plot so far, in the order they happened
+#[derive(Resource, Default)]
+pub struct PlotAnnotations {
+    pub events: Vec<PlotAnnotation>,
+}
+
+impl PlotAnnotations {
+    pub fn add(&mut self, seconds: f64, kind: AnnotationKind, description: String) {
+        self.events.push(PlotAnnotation {
+            seconds,
+            kind,
+            description,
+        });
+    }
+
+    // Draws a line at the time of every event, and returns the descriptions of the ones under the
+    // pointer so they can be shown once the plot is done drawing
+    pub fn draw(&self, plot_ui: &mut PlotUi) -> Vec<String> {
+        let pointer = plot_ui.pointer_coordinate();
+        let pixels_per_second = plot_ui.transform().dpos_dvalue_x().abs() as f32;
+        let mut hovered = Vec::new();
+
+        for event in self.events.iter() {
+            plot_ui.vline(VLine::new(event.seconds).color(event.kind.color()));
+
+            if let Some(pointer) = pointer {
+                if ((pointer.x - event.seconds).abs() as f32) * pixels_per_second <= HOVER_DISTANCE
+                {
+                    hovered.push(format!(
+                        "{}:{:04.1}s {}",
+                        (event.seconds / 60.0).floor(),
+                        event.seconds % 60.0,
+                        event.description
+                    ));
+                }
+            }
+        }
+
+        hovered
+    }
+}
+
+// Only the first boom is marked, the populations go up and down too often to mark every one
+pub fn annotate_predator_boom(
+    predator_query: Query<(), With<Predator>>,
+    clock: Res<SimClock>,
+    mut annotations: ResMut<PlotAnnotations>,
+    mut starting_count: Local<Option<usize>>,
+    mut boomed: Local<bool>,
+) {
+    if *boomed {
+        return;
+    }
+
+    let count = predator_query.iter().count();
+    // Nothing has spawned yet on the very first tick
+    if starting_count.is_none() && count > 0 {
+        *starting_count = Some(count);
+    }
+    let Some(starting_count) = *starting_count else {
+        return;
+    };
+    if count < starting_count * BOOM_FACTOR {
+        return;
+    }
+
+    *boomed = true;
+    annotations.add(
+        clock.seconds,
+        AnnotationKind::PredatorBoom,
+        format!(
+            "First predator boom, {} predators from {}",
+            count, starting_count
+        ),
+    );
+}
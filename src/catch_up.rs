@@ -0,0 +1,116 @@
+/*
+    This file includes all systems & child functions used for
+    jumping the simulation ahead to a later time, instead of watching it get there.
+
+    Examples:
+    - The CatchUp resource holding the time being simulated to
+    - Functions that tick the simulation as fast as they can until it gets there
+    - Functions that show how far along it is, and let a new time be picked from the ui
+*/
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::time::{Duration, Instant};
+
+use crate::ensemble::TICK_DURATION;
+use crate::{tick_simulation, Settings, SimClock};
+
+// How long each frame spends catching up before drawing, so the window keeps responding
+// and the progress can be shown
+const FRAME_BUDGET: Duration = Duration::from_millis(50);
+
+#[derive(Resource, Default)]
+pub struct CatchUp {
+    // The simulated time in seconds being caught up to, if catching up
+    target: Option<f64>,
+    // The time catching up started from, for showing how far along it is
+    start: f64,
+    // The time typed into the ui
+    requested: f64,
+}
+
+impl CatchUp {
+    // A target of 0 doesn't catch up at all
+    pub fn new(target: f32) -> Self {
+        CatchUp {
+            target: (target > 0.0).then_some(target as f64),
+            start: 0.0,
+            requested: target as f64,
+        }
+    }
+}
+
+pub fn not_catching_up(catch_up: Res<CatchUp>) -> bool {
+    catch_up.target.is_none()
+}
+
+// Ticks the simulation until it reaches the target or the frame's budget runs out. Every tick is
+// given the same length, so catching up from the same point with the same seed always ends the
+// same way, however fast the computer is
+pub fn catch_up(world: &mut World) {
+    let Some(target) = world.resource::<CatchUp>().target else {
+        return;
+    };
+
+    let fixed_timestep_hz = world.resource::<Settings>().fixed_timestep_hz;
+    let tick_length = if fixed_timestep_hz > 0.0 {
+        Duration::from_secs_f64(1.0 / fixed_timestep_hz as f64)
+    } else {
+        Duration::from_secs_f64(TICK_DURATION)
+    };
+
+    let frame_time = *world.resource::<Time>();
+    let mut tick_time = Time::<()>::default();
+    let started = Instant::now();
+
+    while world.resource::<SimClock>().seconds < target && started.elapsed() < FRAME_BUDGET {
+        tick_time.advance_by(tick_length);
+        world.insert_resource(tick_time);
+        tick_simulation(world);
+    }
+
+    world.insert_resource(frame_time);
+
+    let clock = world.resource::<SimClock>();
+    if clock.seconds >= target {
+        info!("Caught up to {:.1}s at tick {}", clock.seconds, clock.ticks);
+        world.resource_mut::<CatchUp>().target = None;
+    }
+}
+
+pub fn catch_up_ui(
+    mut contexts: EguiContexts,
+    mut catch_up: ResMut<CatchUp>,
+    clock: Res<SimClock>,
+) {
+    if let Some(target) = catch_up.target {
+        let progress =
+            (clock.seconds - catch_up.start) / (target - catch_up.start).max(f64::EPSILON);
+
+        egui::Window::new("Catching Up").show(contexts.ctx_mut(), |ui| {
+            ui.add(
+                egui::ProgressBar::new(progress.clamp(0.0, 1.0) as f32)
+                    .text(format!("{:.1}s / {:.1}s", clock.seconds, target)),
+            );
+        });
+        return;
+    }
+
+    egui::Window::new("Simulate To")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut catch_up.requested)
+                        .range(0.0..=f64::MAX)
+                        .suffix("s"),
+                );
+
+                // The simulation can only go forwards
+                if ui.button("Go").clicked() && catch_up.requested > clock.seconds {
+                    catch_up.target = Some(catch_up.requested);
+                    catch_up.start = clock.seconds;
+                }
+            });
+        });
+}
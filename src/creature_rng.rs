@@ -0,0 +1,111 @@
+/*
+    This file includes everything used for
+    giving every creature its own stream of random numbers, instead of them all sharing SimRng.
+
+    Examples:
+    - The CreatureRng component, seeded from the run's seed & the creature's StableId
+    - Functions that pick between a creature's own random numbers and the shared ones
+*/
+
+use bevy::ecs::{component::ComponentId, world::DeferredWorld};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{Settings, SimRng, StableId};
+
+// With per_entity_rng on, a creature's wiggling, fleeing, metabolism & mutations all come from
+// here. Adding or removing one creature then doesn't change the random numbers any other gets
+#[derive(Component)]
+#[component(on_add = seed_creature_rng)]
+pub struct CreatureRng(pub StdRng);
+
+// Seeded properly as soon as it's added to a creature, see seed_creature_rng
+impl Default for CreatureRng {
+    fn default() -> Self {
+        CreatureRng(StdRng::seed_from_u64(0))
+    }
+}
+
+// Seeded from the run's seed & the creature's StableId, which is numbered first as it comes before
+// CreatureRng when a creature is spawned. The same creature gets the same stream in every run with
+// the same seed, however many others were spawned or died before it
+fn seed_creature_rng(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) {
+    let seed = world.resource::<Settings>().seed;
+    let id = world
+        .get::<StableId>(entity)
+        .map_or(0, |stable_id| stable_id.0);
+
+    // Spread the ids out so neighbouring creatures don't get similar seeds
+    let creature_seed = seed ^ id.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+
+    if let Some(mut rng) = world.get_mut::<CreatureRng>(entity) {
+        rng.0 = StdRng::seed_from_u64(creature_seed);
+    }
+}
+
+// The random numbers to use for something one creature does
+pub fn creature_rng<'a>(
+    shared: &'a mut SimRng,
+    own: &'a mut CreatureRng,
+    settings: &Settings,
+) -> &'a mut StdRng {
+    if settings.per_entity_rng {
+        &mut own.0
+    } else {
+        &mut shared.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_settings;
+    use crate::stable_id::NextStableId;
+    use rand::Rng;
+
+    fn world() -> World {
+        let mut settings = read_settings();
+        settings.seed = 42;
+
+        let mut world = World::new();
+        world.insert_resource(settings);
+        world.init_resource::<NextStableId>();
+        world
+    }
+
+    fn draws(world: &mut World, entity: Entity) -> Vec<u64> {
+        let mut rng = world.get_mut::<CreatureRng>(entity).unwrap();
+        (0..4).map(|_| rng.0.gen()).collect()
+    }
+
+    #[test]
+    fn streams_follow_the_stable_id_not_the_spawn_order() {
+        let mut first = world();
+        let a = first.spawn((StableId(5), CreatureRng::default())).id();
+        let b = first.spawn((StableId(9), CreatureRng::default())).id();
+
+        let mut second = world();
+        let extra = second.spawn((StableId(2), CreatureRng::default())).id();
+        let later_b = second.spawn((StableId(9), CreatureRng::default())).id();
+        let later_a = second.spawn((StableId(5), CreatureRng::default())).id();
+
+        assert_eq!(draws(&mut first, a), draws(&mut second, later_a));
+        assert_eq!(draws(&mut first, b), draws(&mut second, later_b));
+        assert_ne!(draws(&mut first, a), draws(&mut second, extra));
+    }
+
+    #[test]
+    fn new_creatures_are_seeded_from_their_assigned_id() {
+        let mut spawned = world();
+        spawned.spawn((StableId::default(), CreatureRng::default()));
+        let creature = spawned
+            .spawn((StableId::default(), CreatureRng::default()))
+            .id();
+        assert_eq!(spawned.get::<StableId>(creature), Some(&StableId(2)));
+
+        let mut restored = world();
+        let same = restored.spawn((StableId(2), CreatureRng::default())).id();
+
+        assert_eq!(draws(&mut spawned, creature), draws(&mut restored, same));
+    }
+}
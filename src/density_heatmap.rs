@@ -0,0 +1,148 @@
+/*
+    This file includes all systems & child functions used for
+    shading the world by how crowded each part of it is, to spot crowding & dead zones.
+
+    Examples:
+    - Functions that count the prey & predators in each cell of a grid over the world
+    - Functions that shade each cell by its count, with a separate layer for each species
+*/
+
+use bevy::prelude::*;
+
+use crate::position_systems::{world_size, PositionSize};
+use crate::{Predator, Prey, Settings, Species};
+
+// A square drawn over one cell of the density grid, one for each species
+#[derive(Component)]
+pub struct DensityCell {
+    species: Species,
+    column: usize,
+    row: usize,
+}
+
+fn grid_size(settings: &Settings) -> (usize, usize) {
+    (
+        settings.density_columns.max(1) as usize,
+        settings.density_rows.max(1) as usize,
+    )
+}
+
+// The cells always cover the configured world size, creatures past the edges of an open world
+// are counted in the nearest cell
+fn cell_size(settings: &Settings) -> Vec2 {
+    let (columns, rows) = grid_size(settings);
+    world_size(settings) / Vec2::new(columns as f32, rows as f32)
+}
+
+fn cell_of(position: Vec2, settings: &Settings) -> (usize, usize) {
+    let (columns, rows) = grid_size(settings);
+    let size = cell_size(settings);
+    let origin = world_size(settings) / -2.0;
+
+    let column = ((position.x - origin.x) / size.x).floor() as i64;
+    let row = ((position.y - origin.y) / size.y).floor() as i64;
+    (
+        column.clamp(0, columns as i64 - 1) as usize,
+        row.clamp(0, rows as i64 - 1) as usize,
+    )
+}
+
+pub fn setup_density_heatmap(mut commands: Commands, settings: Res<Settings>) {
+    let (columns, rows) = grid_size(&settings);
+    let size = cell_size(&settings);
+    let origin = world_size(&settings) / -2.0;
+
+    for species in [Species::Prey, Species::Predator] {
+        for row in 0..rows {
+            for column in 0..columns {
+                let center = origin + size * Vec2::new(column as f32 + 0.5, row as f32 + 0.5);
+
+                commands.spawn((
+                    DensityCell {
+                        species,
+                        column,
+                        row,
+                    },
+                    Sprite {
+                        color: Color::NONE,
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    // In front of the grass but behind cover, burrows & creatures
+                    Transform::from_xyz(center.x, center.y, -1.5),
+                    Visibility::Hidden,
+                ));
+            }
+        }
+    }
+}
+
+// Counting everyone is only done every density_update_interval seconds, as the picture barely
+// changes from one frame to the next. Each layer is shaded relative to its most crowded cell
+pub fn update_density_heatmap(
+    mut cells: Query<(&DensityCell, &mut Sprite, &mut Visibility)>,
+    prey_query: Query<&PositionSize, With<Prey>>,
+    predator_query: Query<&PositionSize, With<Predator>>,
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut since_update: Local<Option<f32>>,
+) {
+    if !settings.show_prey_density && !settings.show_predator_density {
+        for (_, _, mut visibility) in cells.iter_mut() {
+            *visibility = Visibility::Hidden;
+        }
+        *since_update = None;
+        return;
+    }
+
+    if let Some(elapsed) = since_update.as_mut() {
+        *elapsed += time.delta_secs();
+        if *elapsed < settings.density_update_interval {
+            return;
+        }
+    }
+    *since_update = Some(0.0);
+
+    let (columns, rows) = grid_size(&settings);
+    let mut prey_counts = vec![0u32; columns * rows];
+    let mut predator_counts = vec![0u32; columns * rows];
+
+    for position_size in prey_query.iter() {
+        let (column, row) = cell_of(Vec2::new(position_size.x, position_size.y), &settings);
+        prey_counts[row * columns + column] += 1;
+    }
+    for position_size in predator_query.iter() {
+        let (column, row) = cell_of(Vec2::new(position_size.x, position_size.y), &settings);
+        predator_counts[row * columns + column] += 1;
+    }
+
+    let most_prey = prey_counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let most_predators = predator_counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+
+    for (cell, mut sprite, mut visibility) in cells.iter_mut() {
+        let (shown, counts, most, color) = match cell.species {
+            Species::Prey => (
+                settings.show_prey_density,
+                &prey_counts,
+                most_prey,
+                settings.prey_color,
+            ),
+            Species::Predator => (
+                settings.show_predator_density,
+                &predator_counts,
+                most_predators,
+                settings.predator_color,
+            ),
+        };
+
+        // Cells from before the grid size was changed have nothing to show
+        if !shown || cell.column >= columns || cell.row >= rows {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Visible;
+
+        let intensity = counts[cell.row * columns + cell.column] as f32 / most;
+        sprite.color = color.with_alpha(intensity * settings.density_opacity);
+    }
+}
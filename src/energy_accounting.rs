@@ -0,0 +1,76 @@
+/*
+    This file includes all systems & child functions used for
+    keeping track of how much energy there is in the whole simulation, to catch energy that's
+    made out of nothing or disappears.
+
+    Examples:
+    - Functions that add up the energy of every creature & the environment each tick
+    - Functions that work out how much the total changed since the last tick
+*/
+
+use bevy::prelude::*;
+
+use crate::grass::GrassGrid;
+use crate::{Environment, Life, Predator, Prey, Settings};
+
+// Grass and the shared environment pool are both counted in the same units as Life,
+// as eating one unit of either gives one unit of Life
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct EnergyLedger {
+    pub prey: i64,
+    pub predators: i64,
+    // The shared pool, or the grass when grass_enabled is on, as prey only eat one or the other
+    pub food: f64,
+    pub total: f64,
+    // How much the total changed since the last tick, None until there's been a last tick
+    pub delta: Option<f64>,
+    measured: bool,
+}
+
+// Only reads, so it can't change what it's measuring. With conserve_reproduction_energy on, the
+// total should only go up from the food growing and go down from metabolism & the energy lost when
+// predators eat (all but predation_efficiency of the prey's energy)
+pub fn update_energy_ledger(
+    prey_query: Query<&Life, With<Prey>>,
+    predator_query: Query<&Life, With<Predator>>,
+    environment_query: Query<&Environment>,
+    grass: Res<GrassGrid>,
+    settings: Res<Settings>,
+    mut ledger: ResMut<EnergyLedger>,
+) {
+    let prey: i64 = prey_query.iter().map(|life| life.value as i64).sum();
+    let predators: i64 = predator_query.iter().map(|life| life.value as i64).sum();
+    let food = if settings.grass_enabled {
+        grass.total_biomass() as f64
+    } else {
+        environment_query
+            .iter()
+            .map(|environment| environment.energy_pool as f64)
+            .sum()
+    };
+    let total = (prey + predators) as f64 + food;
+
+    // The very first tick has nothing to compare against
+    ledger.delta = if ledger.measured {
+        Some(total - ledger.total)
+    } else {
+        None
+    };
+    ledger.prey = prey;
+    ledger.predators = predators;
+    ledger.food = food;
+    ledger.total = total;
+    ledger.measured = true;
+
+    if settings.log_energy_accounting {
+        info!(
+            "Energy: prey {} + predators {} + food {:.0} = {:.0} (change {:+.0})",
+            prey,
+            predators,
+            food,
+            total,
+            ledger.delta.unwrap_or(0.0)
+        );
+    }
+}
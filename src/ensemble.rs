@@ -0,0 +1,158 @@
+/*
+    This file includes everything used for running ensembles, which are many copies of
+    the same simulation running side by side without a window, each with their own seed.
+
+    Examples:
+    - Functions that build & step the headless copies of the simulation
+    - Functions that combine every copy's populations into averages written to a CSV file
+*/
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::run_report::{write_run_report, RunReportFormat};
+use crate::{PopulationHistory, Settings, SimulationPlugin};
+
+// Every headless frame moves the clock forward by the same amount so the runs line up tick for tick
+pub(crate) const TICK_DURATION: f64 = 1.0 / 60.0;
+
+pub fn build_headless_app(settings: Settings) -> App {
+    let mut app = App::new();
+
+    app.add_plugins(MinimalPlugins);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        TICK_DURATION,
+    )));
+    app.insert_resource(settings);
+    app.add_plugins(SimulationPlugin);
+
+    app
+}
+
+pub fn run_ensemble(base_settings: &Settings) {
+    let output_folder = Path::new(&base_settings.ensemble_output);
+    if let Err(error) = fs::create_dir_all(output_folder) {
+        error!(
+            "Couldn't create the ensemble output folder {}: {}",
+            base_settings.ensemble_output, error
+        );
+        return;
+    }
+
+    // Each copy gets the next seed along and writes its own metrics file
+    let mut apps: Vec<App> = (0..base_settings.ensemble_runs)
+        .map(|run| {
+            let mut settings = base_settings.clone();
+            settings.seed = base_settings.seed.wrapping_add(run as u64);
+            settings.metrics_output = output_folder
+                .join(format!("seed_{}.csv", settings.seed))
+                .to_string_lossy()
+                .into_owned();
+
+            build_headless_app(settings)
+        })
+        .collect();
+
+    // Step every copy one tick at a time so they all progress together
+    for tick in 1..=base_settings.ensemble_ticks {
+        for app in apps.iter_mut() {
+            app.update();
+        }
+
+        if tick % 1000 == 0 || tick == base_settings.ensemble_ticks {
+            info!("Ensemble: {}/{} ticks", tick, base_settings.ensemble_ticks);
+        }
+    }
+
+    if base_settings.run_report != RunReportFormat::Off {
+        for app in apps.iter_mut() {
+            let seed = app.world().resource::<Settings>().seed;
+            let report_path = output_folder.join(format!(
+                "seed_{}_report.{}",
+                seed,
+                base_settings.run_report.extension()
+            ));
+            if let Err(error) =
+                write_run_report(app.world_mut(), &report_path, base_settings.run_report)
+            {
+                error!(
+                    "Couldn't write the run report to {}: {}",
+                    report_path.display(),
+                    error
+                );
+            }
+        }
+    }
+
+    let histories: Vec<&PopulationHistory> = apps
+        .iter()
+        .map(|app| app.world().resource::<PopulationHistory>())
+        .collect();
+
+    let aggregate_path = output_folder.join("aggregate.csv");
+    if let Err(error) = write_aggregate(&aggregate_path, &histories) {
+        error!(
+            "Couldn't write the ensemble aggregate to {}: {}",
+            aggregate_path.display(),
+            error
+        );
+    }
+}
+
+fn mean_and_standard_deviation(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powf(2.0))
+        .sum::<f64>()
+        / count;
+
+    (mean, variance.sqrt())
+}
+
+fn write_aggregate(path: &Path, histories: &[&PopulationHistory]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "time,prey_mean,prey_std,predator_mean,predator_std")?;
+
+    // All the copies are stepped together so they should have the same number of samples,
+    // but only go as far as the shortest one just in case
+    let samples = histories
+        .iter()
+        .map(|history| history.prey_population.len())
+        .min()
+        .unwrap_or(0);
+
+    for sample in 0..samples {
+        let prey: Vec<f64> = histories
+            .iter()
+            .map(|history| history.prey_population[sample][1])
+            .collect();
+        let predators: Vec<f64> = histories
+            .iter()
+            .map(|history| history.predator_population[sample][1])
+            .collect();
+
+        let (prey_mean, prey_std) = mean_and_standard_deviation(&prey);
+        let (predator_mean, predator_std) = mean_and_standard_deviation(&predators);
+
+        writeln!(
+            file,
+            "{:.3},{:.3},{:.3},{:.3},{:.3}",
+            histories[0].prey_population[sample][0],
+            prey_mean,
+            prey_std,
+            predator_mean,
+            predator_std
+        )?;
+    }
+
+    file.flush()
+}
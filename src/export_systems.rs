@@ -0,0 +1,449 @@
+/*
+    This file includes all systems & child functions used for
+    writing data about the simulation out to files so it can be analyzed outside of the program.
+
+    Examples:
+    - Functions that sample population, energy & age metrics into a CSV file
+    - Functions that record snapshots of every creature to numbered files for making animations
+    - Functions that write out everything still waiting to be written when the program closes
+*/
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::sim_api::take_snapshot;
+use crate::snapshot_file::save_snapshot;
+use crate::{Age, Environment, Life, Predator, Prey, Settings, SimClock, SimStats};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MetricsColumn {
+    Time,
+    PreyCount,
+    PredatorCount,
+    PreyEnergyMean,
+    PreyEnergyVariance,
+    PredatorEnergyMean,
+    PredatorEnergyVariance,
+    PreyMeanAge,
+    PredatorMeanAge,
+    Births,
+    Deaths,
+    EnvironmentEnergy,
+}
+
+impl MetricsColumn {
+    // Every column in the order it's written to the file. This never depends on the order
+    // the columns are listed in the settings so scripts reading the file don't break
+    const ALL: [MetricsColumn; 12] = [
+        MetricsColumn::Time,
+        MetricsColumn::PreyCount,
+        MetricsColumn::PredatorCount,
+        MetricsColumn::PreyEnergyMean,
+        MetricsColumn::PreyEnergyVariance,
+        MetricsColumn::PredatorEnergyMean,
+        MetricsColumn::PredatorEnergyVariance,
+        MetricsColumn::PreyMeanAge,
+        MetricsColumn::PredatorMeanAge,
+        MetricsColumn::Births,
+        MetricsColumn::Deaths,
+        MetricsColumn::EnvironmentEnergy,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            MetricsColumn::Time => "time",
+            MetricsColumn::PreyCount => "prey_count",
+            MetricsColumn::PredatorCount => "predator_count",
+            MetricsColumn::PreyEnergyMean => "prey_energy_mean",
+            MetricsColumn::PreyEnergyVariance => "prey_energy_variance",
+            MetricsColumn::PredatorEnergyMean => "predator_energy_mean",
+            MetricsColumn::PredatorEnergyVariance => "predator_energy_variance",
+            MetricsColumn::PreyMeanAge => "prey_mean_age",
+            MetricsColumn::PredatorMeanAge => "predator_mean_age",
+            MetricsColumn::Births => "births",
+            MetricsColumn::Deaths => "deaths",
+            MetricsColumn::EnvironmentEnergy => "environment_energy",
+        }
+    }
+}
+
+impl FromStr for MetricsColumn {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        MetricsColumn::ALL
+            .iter()
+            .find(|column| column.header() == value)
+            .copied()
+            .ok_or(format!("Unknown metrics column \"{}\"", value))
+    }
+}
+
+pub fn parse_metrics_columns(value: &str) -> Result<Vec<MetricsColumn>, String> {
+    let requested = value
+        .split(',')
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.parse::<MetricsColumn>())
+        .collect::<Result<Vec<MetricsColumn>, String>>()?;
+
+    Ok(MetricsColumn::ALL
+        .iter()
+        .copied()
+        .filter(|column| requested.contains(column))
+        .collect())
+}
+
+#[derive(Resource)]
+pub struct MetricsWriter {
+    file: Option<BufWriter<File>>,
+    columns: Vec<MetricsColumn>,
+    last_sample_time: Option<f64>,
+    // Births and deaths are written per interval, so remember the totals from the last sample
+    last_births: u64,
+    last_deaths: u64,
+}
+
+pub fn setup_metrics_writer(mut commands: Commands, settings: Res<Settings>) {
+    // The settings are checked when they're read, so this never fails
+    let columns = parse_metrics_columns(&settings.metrics_columns).unwrap_or_default();
+    let mut file = None;
+
+    if !settings.metrics_output.is_empty() {
+        match File::create(&settings.metrics_output) {
+            Ok(created_file) => {
+                let mut writer = BufWriter::new(created_file);
+                let header: Vec<&str> = columns.iter().map(|column| column.header()).collect();
+
+                match writeln!(writer, "{}", header.join(",")) {
+                    Ok(()) => file = Some(writer),
+                    Err(error) => error!(
+                        "Couldn't write the metrics header to {}: {}",
+                        settings.metrics_output, error
+                    ),
+                }
+            }
+            Err(error) => error!(
+                "Couldn't create the metrics file {}: {}",
+                settings.metrics_output, error
+            ),
+        }
+    }
+
+    commands.insert_resource(MetricsWriter {
+        file,
+        columns,
+        last_sample_time: None,
+        last_births: 0,
+        last_deaths: 0,
+    });
+}
+
+// Returns the mean energy, the variance of the energy and the mean age of a group of creatures
+fn summarize<'a>(creatures: impl Iterator<Item = (&'a Life, &'a Age)>) -> (f64, f64, f64) {
+    let creatures: Vec<(f64, f64)> = creatures
+        .map(|(life, age)| (life.value as f64, age.ticks as f64))
+        .collect();
+
+    if creatures.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+
+    let count = creatures.len() as f64;
+    let energy_mean = creatures.iter().map(|(energy, _)| energy).sum::<f64>() / count;
+    let energy_variance = creatures
+        .iter()
+        .map(|(energy, _)| (energy - energy_mean).powf(2.0))
+        .sum::<f64>()
+        / count;
+    let age_mean = creatures.iter().map(|(_, age)| age).sum::<f64>() / count;
+
+    (energy_mean, energy_variance, age_mean)
+}
+
+pub fn write_metrics(
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+    stats: Res<SimStats>,
+    mut metrics_writer: ResMut<MetricsWriter>,
+    prey_query: Query<(&Life, &Age), With<Prey>>,
+    predator_query: Query<(&Life, &Age), With<Predator>>,
+    environment_query: Query<&Environment>,
+) {
+    if metrics_writer.file.is_none() {
+        return;
+    }
+
+    let time_elapsed = clock.seconds;
+    if let Some(last_sample_time) = metrics_writer.last_sample_time {
+        if time_elapsed - last_sample_time < settings.metrics_interval as f64 {
+            return;
+        }
+    }
+
+    let (prey_energy_mean, prey_energy_variance, prey_mean_age) = summarize(prey_query.iter());
+    let (predator_energy_mean, predator_energy_variance, predator_mean_age) =
+        summarize(predator_query.iter());
+    let environment_energy: i64 = environment_query
+        .iter()
+        .map(|environment| environment.energy_pool as i64)
+        .sum();
+
+    let values: Vec<String> = metrics_writer
+        .columns
+        .iter()
+        .map(|column| match column {
+            MetricsColumn::Time => format!("{:.3}", time_elapsed),
+            MetricsColumn::PreyCount => prey_query.iter().count().to_string(),
+            MetricsColumn::PredatorCount => predator_query.iter().count().to_string(),
+            MetricsColumn::PreyEnergyMean => format!("{:.3}", prey_energy_mean),
+            MetricsColumn::PreyEnergyVariance => format!("{:.3}", prey_energy_variance),
+            MetricsColumn::PredatorEnergyMean => format!("{:.3}", predator_energy_mean),
+            MetricsColumn::PredatorEnergyVariance => format!("{:.3}", predator_energy_variance),
+            MetricsColumn::PreyMeanAge => format!("{:.3}", prey_mean_age),
+            MetricsColumn::PredatorMeanAge => format!("{:.3}", predator_mean_age),
+            MetricsColumn::Births => (stats.births - metrics_writer.last_births).to_string(),
+            MetricsColumn::Deaths => (stats.deaths - metrics_writer.last_deaths).to_string(),
+            MetricsColumn::EnvironmentEnergy => environment_energy.to_string(),
+        })
+        .collect();
+
+    metrics_writer.last_sample_time = Some(time_elapsed);
+    metrics_writer.last_births = stats.births;
+    metrics_writer.last_deaths = stats.deaths;
+
+    if let Some(file) = metrics_writer.file.as_mut() {
+        if let Err(error) = writeln!(file, "{}", values.join(",")) {
+            error!(
+                "Couldn't write metrics to {}: {}",
+                settings.metrics_output, error
+            );
+        }
+    }
+}
+
+// Each frame is a file named frame_<tick>.json (or .bin in the binary format), with the tick padded
+// to 8 digits so the files sort in order. Both formats are described in src/snapshot_file.rs
+// Runs with the whole world so it can share the snapshot code with SimApi
+pub fn record_snapshots(world: &mut World) {
+    let settings = world.resource::<Settings>();
+    if !settings.record_snapshots {
+        return;
+    }
+
+    let interval = settings.record_interval_ticks.max(1);
+    let folder = settings.record_output.clone();
+    let extension = settings.record_format.extension();
+    if !world.resource::<SimClock>().ticks.is_multiple_of(interval) {
+        return;
+    }
+
+    save_recording_frame(world, &folder, extension);
+}
+
+fn save_recording_frame(world: &mut World, folder: &str, extension: &str) {
+    if let Err(error) = std::fs::create_dir_all(folder) {
+        error!("Couldn't create the recording folder {}: {}", folder, error);
+        return;
+    }
+
+    let snapshot = take_snapshot(world);
+    let path = Path::new(folder).join(format!("frame_{:08}.{}", snapshot.tick, extension));
+
+    if let Err(error) = save_snapshot(&path, &snapshot) {
+        error!("Couldn't write the snapshot {}: {}", path.display(), error);
+    }
+}
+
+// Makes sure the output files are complete, by writing a last metrics row, a last snapshot and a
+// summary of the run, then flushing everything to disk. Buffered rows would otherwise be lost if
+// the program ends without dropping the MetricsWriter. Safe to call more than once
+pub fn flush_exports(world: &mut World) {
+    let ticks = world.resource::<SimClock>().ticks;
+    let seconds = world.resource::<SimClock>().seconds;
+    let settings = world.resource::<Settings>().clone();
+
+    // The final state gets a metrics row even if the interval since the last one hasn't passed
+    let already_sampled = world
+        .get_resource::<MetricsWriter>()
+        .map(|metrics_writer| metrics_writer.last_sample_time == Some(seconds))
+        .unwrap_or(true);
+    if !already_sampled {
+        world.resource_mut::<MetricsWriter>().last_sample_time = None;
+        if let Err(error) = world.run_system_cached(write_metrics) {
+            error!("Couldn't write the last metrics row: {}", error);
+        }
+    }
+    if let Some(mut metrics_writer) = world.get_resource_mut::<MetricsWriter>() {
+        if let Some(file) = metrics_writer.file.as_mut() {
+            if let Err(error) = file.flush() {
+                error!(
+                    "Couldn't write metrics to {}: {}",
+                    settings.metrics_output, error
+                );
+            }
+        }
+    }
+
+    if settings.record_snapshots && !ticks.is_multiple_of(settings.record_interval_ticks.max(1)) {
+        save_recording_frame(
+            world,
+            &settings.record_output,
+            settings.record_format.extension(),
+        );
+    }
+
+    if !settings.summary_output.is_empty() {
+        if let Err(error) = write_summary(world, &settings.summary_output) {
+            error!(
+                "Couldn't write the summary to {}: {}",
+                settings.summary_output, error
+            );
+        }
+    }
+}
+
+// A "name = value" line for each total, so it can be read back with the same config crate
+fn write_summary(world: &mut World, path: &str) -> std::io::Result<()> {
+    let snapshot = take_snapshot(world);
+    let stats = world.resource::<SimStats>();
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(writer, "ticks = {}", snapshot.tick)?;
+    writeln!(writer, "seconds = {:.3}", snapshot.seconds)?;
+    writeln!(writer, "prey_count = {}", snapshot.prey_count)?;
+    writeln!(writer, "predator_count = {}", snapshot.predator_count)?;
+    writeln!(
+        writer,
+        "environment_energy = {}",
+        snapshot.environment_energy
+    )?;
+    writeln!(writer, "births = {}", stats.births)?;
+    writeln!(writer, "deaths = {}", stats.deaths)?;
+    writeln!(writer, "emigrations = {}", stats.emigrations)?;
+    writeln!(writer, "immigrations = {}", stats.immigrations)?;
+
+    writer.flush()
+}
+
+// Closing the window sends AppExit during the frame, so by Last the exports can still be finished
+// off before the app stops
+pub fn flush_on_exit(world: &mut World, mut flushed: Local<bool>) {
+    if *flushed || world.resource::<Events<AppExit>>().is_empty() {
+        return;
+    }
+
+    flush_exports(world);
+    *flushed = true;
+    info!("Flushed the exports before exiting");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{load_snapshot, read_settings, SimApi};
+    use bevy::app::AppExit;
+    use std::fs;
+
+    use super::{parse_metrics_columns, MetricsColumn};
+
+    // Columns are always written in the same order, whatever order they're asked for in
+    #[test]
+    fn metrics_columns_are_read_in_file_order() {
+        assert_eq!(
+            parse_metrics_columns("deaths, time,,prey_count").unwrap(),
+            vec![
+                MetricsColumn::Time,
+                MetricsColumn::PreyCount,
+                MetricsColumn::Deaths
+            ]
+        );
+        assert_eq!(
+            parse_metrics_columns("time,prey_cuont").unwrap_err(),
+            "Unknown metrics column \"prey_cuont\""
+        );
+    }
+
+    // With a fixed timestep of 240Hz every 60th of a second frame runs four ticks,
+    // and every one of them is exported instead of only the last
+    #[test]
+    fn every_tick_of_a_frame_is_exported() {
+        let folder = std::env::temp_dir().join("predator_prey_export_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let metrics = folder.join("metrics.csv");
+        let recording = folder.join("recording");
+
+        let mut settings = read_settings();
+        settings.seed = 1;
+        settings.fixed_timestep_hz = 240.0;
+        settings.metrics_output = metrics.to_string_lossy().to_string();
+        settings.metrics_interval = 0.0;
+        settings.record_snapshots = true;
+        settings.record_interval_ticks = 1;
+        settings.record_output = recording.to_string_lossy().to_string();
+
+        let mut api = SimApi::new(settings);
+        api.step(10);
+        api.flush();
+        let ticks = api.snapshot().tick as usize;
+
+        assert!(ticks >= 30, "only ran {} ticks", ticks);
+        let rows = fs::read_to_string(&metrics).unwrap().lines().count() - 1;
+        assert_eq!(rows, ticks);
+        assert_eq!(fs::read_dir(&recording).unwrap().count(), ticks);
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+
+    // Nothing is written on the last tick itself with these intervals, so everything about the
+    // end of the run has to come from the flush when the app exits
+    #[test]
+    fn exiting_finishes_off_the_files() {
+        let folder = std::env::temp_dir().join("predator_prey_exit_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let metrics = folder.join("metrics.csv");
+        let recording = folder.join("recording");
+        let summary = folder.join("summary.toml");
+
+        let mut settings = read_settings();
+        settings.seed = 1;
+        settings.metrics_output = metrics.to_string_lossy().to_string();
+        settings.metrics_interval = 1000.0;
+        settings.record_snapshots = true;
+        settings.record_interval_ticks = 1000;
+        settings.record_output = recording.to_string_lossy().to_string();
+        settings.summary_output = summary.to_string_lossy().to_string();
+
+        let mut api = SimApi::new(settings);
+        api.step(20);
+        api.app_mut().world_mut().send_event(AppExit::Success);
+        api.app_mut().update();
+        let snapshot = api.snapshot();
+
+        // The first row & the one written on exit, every line finished
+        let written = fs::read_to_string(&metrics).unwrap();
+        assert!(written.ends_with('\n'));
+        assert_eq!(written.lines().count(), 3);
+        let last_row = written.lines().last().unwrap();
+        assert!(
+            last_row.starts_with(&format!("{:.3}", snapshot.seconds)),
+            "{} at {}",
+            last_row,
+            snapshot.seconds
+        );
+
+        let last_frame = recording.join(format!("frame_{:08}.json", snapshot.tick));
+        assert_eq!(load_snapshot(&last_frame).unwrap().tick, snapshot.tick);
+
+        let summary = fs::read_to_string(&summary).unwrap();
+        assert!(summary.contains(&format!("ticks = {}\n", snapshot.tick)));
+        assert!(summary.ends_with("immigrations = 0\n"));
+
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
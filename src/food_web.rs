@@ -0,0 +1,246 @@
+/*
+    This file includes everything used for
+    deciding who eats whom from the settings, instead of it being hardcoded to predators eating prey.
+
+    Examples:
+    - The SpeciesId component every creature is tagged with
+    - The interaction matrix listing which species eat which, and how much energy they get
+    - The Layers component picking which species each creature can see & touch
+*/
+
+use bevy::prelude::*;
+
+use crate::Settings;
+
+// Which species a creature belongs to in the food web. Prey are always 0 and predators are
+// always 1, other numbers are free for species added later
+#[derive(Reflect, Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[reflect(Component)]
+pub struct SpeciesId(pub u16);
+
+pub const PREY_SPECIES: SpeciesId = SpeciesId(0);
+pub const PREDATOR_SPECIES: SpeciesId = SpeciesId(1);
+
+impl SpeciesId {
+    // The bit standing for this species in a layer mask. Species past 31 don't fit in a mask
+    pub fn bit(&self) -> u32 {
+        1u32.checked_shl(self.0 as u32).unwrap_or(0)
+    }
+}
+
+// Which species a creature notices (detection_mask) and can touch (collision_mask), with bit n
+// standing for SpeciesId(n). Taken from the settings when the creature is spawned
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Layers {
+    pub detection_mask: u32,
+    pub collision_mask: u32,
+}
+
+impl Layers {
+    pub fn prey(settings: &Settings) -> Self {
+        Layers {
+            detection_mask: settings.prey_detection_mask,
+            collision_mask: settings.prey_collision_mask,
+        }
+    }
+
+    pub fn predator(settings: &Settings) -> Self {
+        Layers {
+            detection_mask: settings.predator_detection_mask,
+            collision_mask: settings.predator_collision_mask,
+        }
+    }
+
+    pub fn detects(&self, species: SpeciesId) -> bool {
+        self.detection_mask & species.bit() != 0
+    }
+
+    // Touching goes both ways, so both creatures have to be able to touch the other's species
+    pub fn collide(first: (&Layers, SpeciesId), second: (&Layers, SpeciesId)) -> bool {
+        first.0.collision_mask & second.1.bit() != 0 && second.0.collision_mask & first.1.bit() != 0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Interaction {
+    pub eater: SpeciesId,
+    pub eaten: SpeciesId,
+    // The fraction (0 to 1) of the eaten creature's energy the eater gets
+    pub efficiency: f32,
+}
+
+// Everything that happens when two species touch. Pairs that aren't listed just pass by each other
+#[derive(Resource)]
+pub struct InteractionMatrix {
+    pub interactions: Vec<Interaction>,
+    // What the matrix was made from, so it can be remade if the settings change
+    source: String,
+    predation_efficiency: f32,
+}
+
+impl InteractionMatrix {
+    // The settings are checked when they're read, so this never fails
+    pub fn new(settings: &Settings) -> Self {
+        InteractionMatrix {
+            interactions: parse_interaction_matrix(
+                &settings.interaction_matrix,
+                settings.predation_efficiency,
+            )
+            .unwrap_or_default(),
+            source: settings.interaction_matrix.clone(),
+            predation_efficiency: settings.predation_efficiency,
+        }
+    }
+
+    // A matrix edited into something that can't be read (like with the inspector) is reported
+    // once and the last one that worked is kept, instead of stopping the simulation
+    pub fn refresh(&mut self, settings: &Settings) {
+        if self.source == settings.interaction_matrix
+            && self.predation_efficiency == settings.predation_efficiency
+        {
+            return;
+        }
+
+        match parse_interaction_matrix(&settings.interaction_matrix, settings.predation_efficiency)
+        {
+            Ok(interactions) => self.interactions = interactions,
+            Err(error) => error!(
+                "Keeping the last interaction matrix, as the new one can't be used: {}",
+                error
+            ),
+        }
+        self.source = settings.interaction_matrix.clone();
+        self.predation_efficiency = settings.predation_efficiency;
+    }
+
+    // How much of its energy eaten gives to eater, or None if eater doesn't eat it
+    pub fn efficiency(&self, eater: SpeciesId, eaten: SpeciesId) -> Option<f32> {
+        self.interactions
+            .iter()
+            .find(|interaction| interaction.eater == eater && interaction.eaten == eaten)
+            .map(|interaction| interaction.efficiency)
+    }
+}
+
+// Interactions are written as "eater>eaten:efficiency" with a semicolon between each one, e.g.
+// "1>0:0.1;2>1:0.05". Leaving out the efficiency uses predation_efficiency, and leaving out the
+// whole matrix gives the usual two species model of predators eating prey
+pub fn parse_interaction_matrix(
+    value: &str,
+    predation_efficiency: f32,
+) -> Result<Vec<Interaction>, String> {
+    if value.trim().is_empty() {
+        return Ok(vec![Interaction {
+            eater: PREDATOR_SPECIES,
+            eaten: PREY_SPECIES,
+            efficiency: predation_efficiency,
+        }]);
+    }
+
+    value
+        .split(';')
+        .map(|interaction| interaction.trim())
+        .filter(|interaction| !interaction.is_empty())
+        .map(|interaction| parse_interaction(interaction, predation_efficiency))
+        .collect()
+}
+
+fn parse_interaction(interaction: &str, predation_efficiency: f32) -> Result<Interaction, String> {
+    let (pair, efficiency) = match interaction.split_once(':') {
+        Some((pair, efficiency)) => {
+            let efficiency = efficiency.trim().parse::<f32>().map_err(|_| {
+                format!(
+                    "\"{}\" has an efficiency that isn't a number in \"{}\"",
+                    efficiency.trim(),
+                    interaction
+                )
+            })?;
+            if !(0.0..=1.0).contains(&efficiency) {
+                return Err(format!(
+                    "The efficiency has to be between 0 and 1 in \"{}\"",
+                    interaction
+                ));
+            }
+            (pair, efficiency)
+        }
+        None => (interaction, predation_efficiency),
+    };
+
+    let (eater, eaten) = pair.split_once('>').ok_or(format!(
+        "\"{}\" should be written as eater>eaten:efficiency",
+        interaction
+    ))?;
+    let species = |number: &str| {
+        number.trim().parse::<u16>().map(SpeciesId).map_err(|_| {
+            format!(
+                "\"{}\" isn't a species number in \"{}\"",
+                number.trim(),
+                interaction
+            )
+        })
+    };
+
+    Ok(Interaction {
+        eater: species(eater)?,
+        eaten: species(eaten)?,
+        efficiency,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrices_are_read_with_and_without_efficiencies() {
+        let interactions = parse_interaction_matrix(" 1>0:0.2; 2>1 ;", 0.1).unwrap();
+
+        assert_eq!(
+            interactions,
+            vec![
+                Interaction {
+                    eater: PREDATOR_SPECIES,
+                    eaten: PREY_SPECIES,
+                    efficiency: 0.2,
+                },
+                Interaction {
+                    eater: SpeciesId(2),
+                    eaten: PREDATOR_SPECIES,
+                    efficiency: 0.1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn a_bad_live_edit_keeps_the_last_matrix() {
+        let mut settings = crate::read_settings();
+        settings.interaction_matrix = "1>0:0.3".to_string();
+        let mut matrix = InteractionMatrix::new(&settings);
+
+        settings.interaction_matrix = "1>0:0.3;2-1".to_string();
+        matrix.refresh(&settings);
+
+        assert_eq!(matrix.efficiency(PREDATOR_SPECIES, PREY_SPECIES), Some(0.3));
+        assert_eq!(matrix.interactions.len(), 1);
+    }
+
+    #[test]
+    fn typos_in_the_matrix_are_errors() {
+        for value in [
+            "1>0:0.1;2-1",
+            "1>0:lots",
+            "a>0",
+            "1>",
+            "1>0:1.5",
+            "1>0:-0.1",
+        ] {
+            assert!(
+                parse_interaction_matrix(value, 0.1).is_err(),
+                "{} was read",
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,269 @@
+/*
+    This file includes all systems & child functions used for
+    showing how the creatures' genes are spread out, to see selection shaping the population.
+
+    Examples:
+    - Functions that sort one gene of the living creatures into a histogram every so often
+    - Functions that keep the mean & standard deviation of every gene over time
+    - Functions that draw the histogram & the spread over time, with a dropdown to pick the gene
+*/
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use egui_plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints};
+
+use crate::render_systems::to_color32;
+use crate::sim_api::Species;
+use crate::{Genes, Predator, Prey, Settings, SimClock};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GeneTrait {
+    Speed,
+    DetectionRange,
+    Aggression,
+}
+
+impl GeneTrait {
+    const ALL: [GeneTrait; 3] = [
+        GeneTrait::Speed,
+        GeneTrait::DetectionRange,
+        GeneTrait::Aggression,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            GeneTrait::Speed => "Speed",
+            GeneTrait::DetectionRange => "Detection Range",
+            GeneTrait::Aggression => "Aggression",
+        }
+    }
+
+    fn value(&self, genes: &Genes) -> f64 {
+        match self {
+            GeneTrait::Speed => genes.speed as f64,
+            GeneTrait::DetectionRange => genes.detection_range as f64,
+            GeneTrait::Aggression => genes.aggression as f64,
+        }
+    }
+}
+
+// The mean & standard deviation of every gene, in the order of GeneTrait::ALL
+type Spread = [(f64, f64); 3];
+
+// The latest histogram of the gene picked in the ui, and the spread of every gene over time so
+// picking another one doesn't start its history over
+#[derive(Resource)]
+pub struct GeneHistogram {
+    species: Species,
+    gene: GeneTrait,
+    // The middle of each bin and how many creatures are in it
+    bars: Vec<(f64, f64)>,
+    bar_width: f64,
+    prey_spread: Vec<(f64, Spread)>,
+    predator_spread: Vec<(f64, Spread)>,
+    // The simulated time the next sample is taken at
+    next_sample: f64,
+}
+
+impl Default for GeneHistogram {
+    fn default() -> Self {
+        GeneHistogram {
+            species: Species::Prey,
+            gene: GeneTrait::Speed,
+            bars: Vec::new(),
+            bar_width: 1.0,
+            prey_spread: Vec::new(),
+            predator_spread: Vec::new(),
+            next_sample: 0.0,
+        }
+    }
+}
+
+fn mean_and_deviation(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / count;
+    (mean, variance.sqrt())
+}
+
+fn spread(genes: &[&Genes]) -> Spread {
+    GeneTrait::ALL.map(|gene| {
+        let values: Vec<f64> = genes.iter().map(|genes| gene.value(genes)).collect();
+        mean_and_deviation(&values)
+    })
+}
+
+// Splits the values evenly between their lowest & highest into the given number of bins. When
+// they're all the same there's only one bin, as there's nothing to split
+fn sort_into_bins(values: &[f64], bins: usize) -> (Vec<(f64, f64)>, f64) {
+    let (Some(min), Some(max)) = (
+        values.iter().copied().reduce(f64::min),
+        values.iter().copied().reduce(f64::max),
+    ) else {
+        return (Vec::new(), 1.0);
+    };
+    if max <= min {
+        return (vec![(min, values.len() as f64)], 1.0);
+    }
+
+    let width = (max - min) / bins as f64;
+    let mut counts = vec![0.0; bins];
+    for value in values {
+        let bin = (((value - min) / width) as usize).min(bins - 1);
+        counts[bin] += 1.0;
+    }
+
+    let bars = counts
+        .into_iter()
+        .enumerate()
+        .map(|(bin, count)| (min + width * (bin as f64 + 0.5), count))
+        .collect();
+    (bars, width)
+}
+
+// Only every gene_histogram_interval simulated seconds, as sorting everyone every frame adds up
+pub fn update_gene_histogram(
+    mut histogram: ResMut<GeneHistogram>,
+    prey_query: Query<&Genes, With<Prey>>,
+    predator_query: Query<&Genes, With<Predator>>,
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+) {
+    if clock.seconds < histogram.next_sample {
+        return;
+    }
+    histogram.next_sample = clock.seconds + settings.gene_histogram_interval as f64;
+
+    let prey_genes: Vec<&Genes> = prey_query.iter().collect();
+    let predator_genes: Vec<&Genes> = predator_query.iter().collect();
+
+    let shown = match histogram.species {
+        Species::Prey => &prey_genes,
+        Species::Predator => &predator_genes,
+    };
+    let gene = histogram.gene;
+    let values: Vec<f64> = shown.iter().map(|genes| gene.value(genes)).collect();
+    let (bars, bar_width) = sort_into_bins(&values, settings.gene_histogram_bins as usize);
+    histogram.bars = bars;
+    histogram.bar_width = bar_width;
+
+    // The same time can be sampled twice when the simulation is paused & a new gene is picked
+    if histogram
+        .prey_spread
+        .last()
+        .is_none_or(|(seconds, _)| *seconds < clock.seconds)
+    {
+        histogram
+            .prey_spread
+            .push((clock.seconds, spread(&prey_genes)));
+        histogram
+            .predator_spread
+            .push((clock.seconds, spread(&predator_genes)));
+    }
+}
+
+pub fn gene_histogram_ui(
+    mut contexts: EguiContexts,
+    mut histogram: ResMut<GeneHistogram>,
+    settings: Res<Settings>,
+) {
+    let histogram = histogram.as_mut();
+
+    egui::Window::new("Gene Distribution")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let (species, gene) = (histogram.species, histogram.gene);
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Species")
+                    .selected_text(match histogram.species {
+                        Species::Prey => "Prey",
+                        Species::Predator => "Predators",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut histogram.species, Species::Prey, "Prey");
+                        ui.selectable_value(&mut histogram.species, Species::Predator, "Predators");
+                    });
+                egui::ComboBox::from_label("Gene")
+                    .selected_text(histogram.gene.name())
+                    .show_ui(ui, |ui| {
+                        for gene in GeneTrait::ALL {
+                            ui.selectable_value(&mut histogram.gene, gene, gene.name());
+                        }
+                    });
+            });
+
+            // Sort the new gene into bins straight away instead of waiting for the next sample
+            if histogram.species != species || histogram.gene != gene {
+                histogram.next_sample = 0.0;
+            }
+
+            let color = to_color32(match histogram.species {
+                Species::Prey => settings.prey_color,
+                Species::Predator => settings.predator_color,
+            });
+
+            let bars: Vec<Bar> = histogram
+                .bars
+                .iter()
+                .map(|(middle, count)| Bar::new(*middle, *count).width(histogram.bar_width))
+                .collect();
+            Plot::new("gene_histogram_plot")
+                .height(200.0)
+                .x_axis_label(histogram.gene.name())
+                .y_axis_label("Creatures")
+                .include_y(0.0)
+                .show(ui, |plot_ui| {
+                    plot_ui.bar_chart(BarChart::new(bars).color(color));
+                });
+
+            if !settings.gene_histogram_spread {
+                return;
+            }
+
+            let index = GeneTrait::ALL
+                .iter()
+                .position(|gene| *gene == histogram.gene)
+                .unwrap_or(0);
+            let samples = match histogram.species {
+                Species::Prey => &histogram.prey_spread,
+                Species::Predator => &histogram.predator_spread,
+            };
+            let line = |offset: f64| -> PlotPoints {
+                samples
+                    .iter()
+                    .map(|(seconds, spread)| {
+                        let (mean, deviation) = spread[index];
+                        [*seconds, mean + deviation * offset]
+                    })
+                    .collect()
+            };
+
+            Plot::new("gene_spread_plot")
+                .height(200.0)
+                .legend(Legend::default())
+                .x_axis_label("Time (s)")
+                .y_axis_label(histogram.gene.name())
+                .show(ui, |plot_ui| {
+                    plot_ui.line(Line::new(line(0.0)).name("Mean").color(color));
+                    plot_ui.line(
+                        Line::new(line(1.0))
+                            .name("Mean ± Standard Deviation")
+                            .color(color.gamma_multiply(0.5)),
+                    );
+                    plot_ui.line(
+                        Line::new(line(-1.0))
+                            .name("Mean ± Standard Deviation")
+                            .color(color.gamma_multiply(0.5)),
+                    );
+                });
+        });
+}
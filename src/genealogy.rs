@@ -0,0 +1,37 @@
+/*
+    This file includes everything used for
+    remembering who every creature's parents were, so lineages can be followed over the generations.
+
+    Examples:
+    - The Parents component, holding the stable ids of a creature's parents
+    - Functions that work out which founder a newborn descends from & how many generations down it is
+*/
+
+use bevy::prelude::*;
+
+use crate::StableId;
+
+// Creatures that weren't born from mating (the starting creatures, immigrants & anything spawned
+// to prevent extinction) are founders, with no parents and a generation of 0
+#[derive(Reflect, Component, Default, Clone, Copy, PartialEq, Debug)]
+#[reflect(Component)]
+pub struct Parents {
+    // The parent that gave birth, whose genes the child inherits
+    pub a: Option<StableId>,
+    // Its partner
+    pub b: Option<StableId>,
+    // Lineages are followed back through the first parent. A founder's lineage starts with itself
+    pub founder: Option<StableId>,
+    pub generation: u32,
+}
+
+impl Parents {
+    pub fn child_of(parent: StableId, parent_parents: &Parents, partner: Option<StableId>) -> Self {
+        Parents {
+            a: Some(parent),
+            b: partner,
+            founder: Some(parent_parents.founder.unwrap_or(parent)),
+            generation: parent_parents.generation + 1,
+        }
+    }
+}
@@ -0,0 +1,159 @@
+/*
+    This file includes all systems & child functions used for
+    the grass grid, which splits the environment up into patches that prey graze on & that regrow over time.
+
+    Examples:
+    - The GrassGrid resource holding how much grass is left in each patch
+    - Functions that let prey eat from the patch they're standing on
+    - Functions that regrow the grass & draw it as a heatmap
+*/
+
+use bevy::prelude::*;
+
+use crate::position_systems::world_size;
+use crate::Settings;
+
+// Grass never gets completely wiped out, or it would never grow back
+const MIN_BIOMASS_FRACTION: f32 = 0.01;
+
+#[derive(Resource)]
+pub struct GrassGrid {
+    columns: usize,
+    rows: usize,
+    cell_size: Vec2,
+    // The bottom left corner of the grid in world coordinates
+    origin: Vec2,
+    // How much grass is in each patch, row by row from the bottom left
+    biomass: Vec<f32>,
+}
+
+impl GrassGrid {
+    pub fn new(settings: &Settings) -> Self {
+        let columns = settings.grass_columns.max(1);
+        let rows = settings.grass_rows.max(1);
+
+        GrassGrid {
+            columns,
+            rows,
+            cell_size: world_size(settings) / Vec2::new(columns as f32, rows as f32),
+            origin: world_size(settings) / -2.0,
+            biomass: vec![
+                settings.grass_max_biomass * settings.grass_initial_fraction;
+                columns * rows
+            ],
+        }
+    }
+
+    // Creatures past the edge of the grid graze the nearest patch
+    fn cell_index(&self, x: f32, y: f32) -> usize {
+        let column = ((x - self.origin.x) / self.cell_size.x).floor() as i64;
+        let row = ((y - self.origin.y) / self.cell_size.y).floor() as i64;
+
+        let column = column.clamp(0, self.columns as i64 - 1) as usize;
+        let row = row.clamp(0, self.rows as i64 - 1) as usize;
+
+        row * self.columns + column
+    }
+
+    fn cell_center(&self, index: usize) -> Vec2 {
+        let column = index % self.columns;
+        let row = index / self.columns;
+
+        self.origin
+            + Vec2::new(
+                (column as f32 + 0.5) * self.cell_size.x,
+                (row as f32 + 0.5) * self.cell_size.y,
+            )
+    }
+
+    // Eats up to amount from the patch at the position, returning how much was actually eaten
+    pub fn graze(&mut self, x: f32, y: f32, amount: f32, max_biomass: f32) -> f32 {
+        let index = self.cell_index(x, y);
+        let available = self.biomass[index] - max_biomass * MIN_BIOMASS_FRACTION;
+        let eaten = amount.min(available).max(0.0);
+
+        self.biomass[index] -= eaten;
+        eaten
+    }
+
+    // How full the patch at the position is, from 0 (bare) to 1 (fully grown)
+    pub fn fullness(&self, x: f32, y: f32, max_biomass: f32) -> f32 {
+        if max_biomass <= 0.0 {
+            return 0.0;
+        }
+
+        self.biomass[self.cell_index(x, y)] / max_biomass
+    }
+
+    // Multiplies every patch by the fraction, without going below what grass needs to grow back
+    pub fn scale(&mut self, fraction: f32, max_biomass: f32) {
+        for biomass in self.biomass.iter_mut() {
+            *biomass = (*biomass * fraction).max(max_biomass * MIN_BIOMASS_FRACTION);
+        }
+    }
+
+    pub fn total_biomass(&self) -> f32 {
+        self.biomass.iter().sum()
+    }
+}
+
+pub fn regrow_grass(mut grass: ResMut<GrassGrid>, settings: Res<Settings>) {
+    if !settings.grass_enabled || settings.grass_max_biomass <= 0.0 {
+        return;
+    }
+
+    // Logistic growth, so barely grazed and nearly bare patches both grow back slowly
+    // while half eaten ones recover the fastest
+    for biomass in grass.biomass.iter_mut() {
+        let growth =
+            settings.grass_regrowth_rate * *biomass * (1.0 - *biomass / settings.grass_max_biomass);
+        *biomass = (*biomass + growth).min(settings.grass_max_biomass);
+    }
+}
+
+// A square drawn over one patch of grass to show how much is left in it
+#[derive(Component)]
+pub struct GrassCell {
+    index: usize,
+}
+
+pub fn setup_grass_heatmap(mut commands: Commands, grass: Res<GrassGrid>, settings: Res<Settings>) {
+    for index in 0..grass.biomass.len() {
+        let center = grass.cell_center(index);
+
+        commands.spawn((
+            GrassCell { index },
+            Sprite {
+                color: settings.environment_color.with_alpha(0.0),
+                custom_size: Some(grass.cell_size),
+                ..default()
+            },
+            // Sit behind everything else, including cover & burrows
+            Transform::from_xyz(center.x, center.y, -2.0),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+pub fn update_grass_heatmap(
+    mut query: Query<(&GrassCell, &mut Sprite, &mut Visibility)>,
+    grass: Res<GrassGrid>,
+    settings: Res<Settings>,
+) {
+    let visible = settings.grass_enabled && settings.show_grass;
+
+    for (cell, mut sprite, mut visibility) in query.iter_mut() {
+        if !visible {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Visible;
+
+        let fullness = if settings.grass_max_biomass > 0.0 {
+            (grass.biomass[cell.index] / settings.grass_max_biomass).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        sprite.color = settings.environment_color.with_alpha(fullness * 0.4);
+    }
+}
@@ -0,0 +1,98 @@
+/*
+    This file includes all systems & child functions used for
+    checking that the assumptions the rest of the simulation is built on still hold while it runs.
+
+    Examples:
+    - Functions that make sure no creature is both a predator and a prey
+    - Functions that make sure mating targets point at creatures that are still alive
+    - Functions that make sure no two creatures share a stable id
+    - Functions that make sure the population history agrees with what's actually alive
+*/
+
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+use crate::{MatingTarget, PopulationHistory, Predator, Prey, Settings, StableId};
+
+// Life isn't checked against prey_life & predator_life, as those are only what creatures start
+// with. Eating, burrows & kills all add to it with no cap, so going over them is expected.
+pub fn check_invariants(
+    settings: Res<Settings>,
+    history: Res<PopulationHistory>,
+    both_query: Query<Entity, (With<Predator>, With<Prey>)>,
+    prey_query: Query<(&StableId, &MatingTarget), With<Prey>>,
+    predator_query: Query<(&StableId, &MatingTarget), With<Predator>>,
+) {
+    if !settings.check_invariants {
+        return;
+    }
+
+    let mut violations: Vec<String> = Vec::new();
+
+    for entity in both_query.iter() {
+        violations.push(format!("{} is both a predator and a prey", entity));
+    }
+
+    // Mating targets only keep the id of their partner, so that's all that can be checked
+    let prey_ids: HashSet<u64> = prey_query.iter().map(|(id, _)| id.0).collect();
+    let predator_ids: HashSet<u64> = predator_query.iter().map(|(id, _)| id.0).collect();
+
+    for (id, mating_target) in prey_query.iter() {
+        if let Some(partner) = mating_target.partner {
+            if !prey_ids.contains(&partner) {
+                violations.push(format!(
+                    "Prey {} wants to mate with {}, which isn't a living prey",
+                    id.0, partner
+                ));
+            }
+        }
+    }
+    for (id, mating_target) in predator_query.iter() {
+        if let Some(partner) = mating_target.partner {
+            if !predator_ids.contains(&partner) {
+                violations.push(format!(
+                    "Predator {} wants to mate with {}, which isn't a living predator",
+                    id.0, partner
+                ));
+            }
+        }
+    }
+
+    // Every creature should have its own id
+    if prey_ids.len() + predator_ids.len() != prey_query.iter().len() + predator_query.iter().len()
+        || !prey_ids.is_disjoint(&predator_ids)
+    {
+        violations.push("Two creatures share the same stable id".to_string());
+    }
+
+    let prey_count = prey_query.iter().len() as f64;
+    let predator_count = predator_query.iter().len() as f64;
+    if let Some([_, recorded]) = history.prey_population.last() {
+        if *recorded != prey_count {
+            violations.push(format!(
+                "The population history has {} prey but there are {}",
+                recorded, prey_count
+            ));
+        }
+    }
+    if let Some([_, recorded]) = history.predator_population.last() {
+        if *recorded != predator_count {
+            violations.push(format!(
+                "The population history has {} predators but there are {}",
+                recorded, predator_count
+            ));
+        }
+    }
+
+    if violations.is_empty() {
+        return;
+    }
+
+    for violation in violations.iter() {
+        error!("Invariant broken: {}", violation);
+    }
+
+    if settings.panic_on_invariant_violation {
+        panic!("Invariants broken: {}", violations.join(", "));
+    }
+}
@@ -0,0 +1,99 @@
+/*
+    This file includes all functions used for
+    starting the simulation from a hand made layout of creatures instead of random positions.
+
+    Examples:
+    - Functions that read a layout file listing each creature's species, position & energy
+*/
+
+use bevy::prelude::*;
+use std::fs;
+
+use crate::position_systems::world_size;
+use crate::sim_api::Species;
+use crate::Settings;
+
+pub struct LayoutEntry {
+    pub species: Species,
+    pub x: f32,
+    pub y: f32,
+    pub energy: i32,
+}
+
+// Layout files have one creature per line written as "species,x,y,energy", where species is
+// prey or predator and x & y are measured from the middle of the window. Blank lines and lines
+// starting with # are skipped. A line that can't be read stops the program, like a bad setting
+pub fn read_layout(settings: &Settings) -> Vec<LayoutEntry> {
+    let contents = fs::read_to_string(&settings.layout_file).unwrap_or_else(|error| {
+        panic!(
+            "Couldn't read the layout file {}: {}",
+            settings.layout_file, error
+        )
+    });
+
+    let half_size = world_size(settings) / 2.0;
+    let mut entries = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let entry = parse_layout_line(line).unwrap_or_else(|error| {
+            panic!(
+                "Line {} of the layout file {} is invalid: {}",
+                index + 1,
+                settings.layout_file,
+                error
+            )
+        });
+
+        // They're still spawned, the walls will push them back in on the first frame
+        if entry.x.abs() > half_size.x || entry.y.abs() > half_size.y {
+            warn!(
+                "Line {} of the layout file {} puts a creature at {}, {}, which is outside the window",
+                index + 1,
+                settings.layout_file,
+                entry.x,
+                entry.y
+            );
+        }
+
+        entries.push(entry);
+    }
+
+    entries
+}
+
+fn parse_layout_line(line: &str) -> Result<LayoutEntry, String> {
+    let values: Vec<&str> = line.split(',').map(|value| value.trim()).collect();
+    if values.len() != 4 {
+        return Err(format!(
+            "expected \"species,x,y,energy\" but got \"{}\"",
+            line
+        ));
+    }
+
+    let species = match values[0].to_lowercase().as_str() {
+        "prey" => Species::Prey,
+        "predator" => Species::Predator,
+        _ => return Err(format!("unknown species \"{}\"", values[0])),
+    };
+    let x = values[1]
+        .parse::<f32>()
+        .map_err(|error| format!("invalid x \"{}\": {}", values[1], error))?;
+    let y = values[2]
+        .parse::<f32>()
+        .map_err(|error| format!("invalid y \"{}\": {}", values[2], error))?;
+    let energy = values[3]
+        .parse::<i32>()
+        .map_err(|error| format!("invalid energy \"{}\": {}", values[3], error))?;
+
+    Ok(LayoutEntry {
+        species,
+        x,
+        y,
+        energy,
+    })
+}
@@ -0,0 +1,4887 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::schedule::ScheduleLabel,
+    log::LogPlugin,
+    prelude::*,
+    utils::Parallel,
+    window::Window,
+};
+use bevy_inspector_egui::quick::ResourceInspectorPlugin;
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use config::Config;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+
+mod ensemble;
+pub use ensemble::{build_headless_app, run_ensemble};
+
+mod export_systems;
+pub use export_systems::flush_exports;
+use export_systems::{
+    flush_on_exit, parse_metrics_columns, record_snapshots, setup_metrics_writer, write_metrics,
+};
+
+mod render_systems;
+use render_systems::{
+    draw_grid, draw_trails, fade_low_energy, parse_hex_color, parse_status_colors, record_trails,
+    scale_sprites_by_energy, take_screenshot, tint_by_status, to_color32, ui_visible,
+    PlotDecimation, ScreenshotState, Trail,
+};
+
+mod sim_api;
+pub use sim_api::{CreatureSnapshot, SimApi, SimSnapshot, Species};
+
+mod snapshot_file;
+pub use snapshot_file::{load_snapshot, save_snapshot, SnapshotFormat};
+
+mod creature_rng;
+use creature_rng::{creature_rng, CreatureRng};
+
+mod stable_id;
+pub use stable_id::{NextStableId, StableId};
+
+mod genealogy;
+pub use genealogy::Parents;
+
+mod logging;
+use logging::log_extinctions;
+pub use logging::{init_logging, log_settings_summary};
+
+mod food_web;
+use food_web::{
+    parse_interaction_matrix, InteractionMatrix, Layers, SpeciesId, PREDATOR_SPECIES, PREY_SPECIES,
+};
+
+mod layout;
+use layout::read_layout;
+
+mod invariants;
+use invariants::check_invariants;
+
+mod sweep;
+use sweep::{collect_sweep_results, sweep_ui, SweepPanel};
+
+mod selection;
+use selection::{
+    drag_selected, draw_selection, follow_selected, select_on_click, selection_ui, BeingDragged,
+    Selection,
+};
+
+mod settings_file;
+use settings_file::{reload_settings, save_settings};
+
+mod grass;
+use grass::{regrow_grass, setup_grass_heatmap, update_grass_heatmap, GrassGrid};
+
+mod energy_accounting;
+use energy_accounting::{update_energy_ledger, EnergyLedger};
+
+mod annotations;
+use annotations::{annotate_predator_boom, PlotAnnotations};
+
+mod perturbations;
+use perturbations::{apply_perturbations, parse_perturbations, PerturbationSchedule};
+pub use perturbations::{Perturbation, PerturbationEffect, PerturbationTime};
+
+mod run_report;
+pub use run_report::{write_run_report, RunReportFormat};
+
+mod catch_up;
+use catch_up::{catch_up, catch_up_ui, not_catching_up, CatchUp};
+
+mod density_heatmap;
+use density_heatmap::{setup_density_heatmap, update_density_heatmap};
+
+mod gene_histogram;
+use gene_histogram::{gene_histogram_ui, update_gene_histogram, GeneHistogram};
+
+mod position_systems;
+use position_systems::{
+    apply_velocity, avoid, handle_window_resize, idle_move, in_detection_cone, in_detection_range,
+    is_colliding, is_inside, move_in_direction, move_towards, record_previous_positions,
+    roll_detection, scale_collision_by_energy, separate_creatures, steer_from_walls,
+    update_transform, validate_edges, validate_positions, wiggle_squares, window_collision,
+    world_half_size, world_size, CollisionShape, DetectionFalloff, EdgeBehavior, Edges, Heading,
+    IdleBehavior, MovementModel, Patrol, PositionSize, PreviousPosition, Velocity,
+};
+
+pub mod spatial_index;
+use spatial_index::{
+    draw_spatial_indexes, probe_spatial_indexes, rebuild_spatial_indexes, SpatialIndexKind,
+    SpatialIndexes,
+};
+
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+struct PopulationHistory {
+    prey_population: Vec<[f64; 2]>,
+    predator_population: Vec<[f64; 2]>,
+    // The mean aggression gene of the living predators, only recorded with aggression_enabled
+    predator_aggression: Vec<[f64; 2]>,
+    // The mean detection range gene of each species, only recorded when it can vary
+    prey_detection_range: Vec<[f64; 2]>,
+    predator_detection_range: Vec<[f64; 2]>,
+}
+
+// How far the simulation has progressed, kept apart from how long the program has been open.
+// - ticks counts simulation updates, and is used for anything counted in frames (Age)
+// - seconds adds up the virtual time between updates, so it stops while time is paused and
+//   speeds up or slows down with the time scale. It's used for anything measured in seconds
+//   (population history, the plot's x axis, metrics sampling)
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct SimClock {
+    ticks: u64,
+    seconds: f64,
+}
+
+// Running totals of events that can't be worked out by just counting entities
+#[derive(Reflect, Resource, Default)]
+#[reflect(Resource)]
+pub struct SimStats {
+    births: u64,
+    deaths: u64,
+    // Creatures that left or arrived across the edge of an open world, arrivals including the
+    // gene immigrants
+    emigrations: u64,
+    immigrations: u64,
+    // Predators eating prey, counted in deaths too
+    kills: u64,
+    // The total age in ticks of every creature that died, and how many there were
+    prey_lifespans: (u64, u64),
+    predator_lifespans: (u64, u64),
+}
+
+// How the starting populations are worked out
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum PopulationMode {
+    Absolute, // predator_population and prey_population are used as they are
+    Fraction, // total_population is split up with predator_fraction of it being predators
+}
+
+impl FromStr for PopulationMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "absolute" => Ok(PopulationMode::Absolute),
+            "fraction" => Ok(PopulationMode::Fraction),
+            _ => Err(format!("Unknown population mode \"{}\"", value)),
+        }
+    }
+}
+
+// How the energy needed to reproduce is worked out
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum ReproductionEnergyMode {
+    Absolute, // prey_reproduction_energy and predator_reproduction_energy are used as they are
+    Fraction, // prey_repro_fraction and predator_repro_fraction of each species' full life
+}
+
+impl FromStr for ReproductionEnergyMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "absolute" => Ok(ReproductionEnergyMode::Absolute),
+            "fraction" => Ok(ReproductionEnergyMode::Fraction),
+            _ => Err(format!("Unknown reproduction energy mode \"{}\"", value)),
+        }
+    }
+}
+
+// Who gets the meal when more than one predator reaches the same prey on the same tick
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum KillSharing {
+    First,   // Whichever comes first in the query, the way it always worked
+    Nearest, // The one closest to the prey, ties going to the first in the query
+    Split,   // Every predator touching it gets an even share, the nearest gets what's left over
+}
+
+impl FromStr for KillSharing {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "first" => Ok(KillSharing::First),
+            "nearest" => Ok(KillSharing::Nearest),
+            "split" => Ok(KillSharing::Split),
+            _ => Err(format!("Unknown kill sharing \"{}\"", value)),
+        }
+    }
+}
+
+// Every bit of randomness in the simulation goes through this so runs can be repeated from a seed
+#[derive(Resource)]
+pub struct SimRng(pub StdRng);
+
+#[derive(Reflect, Resource, Clone)]
+#[reflect(Resource)]
+pub struct Settings {
+    pub window_width: f32,
+    pub window_height: f32,
+    // How many pixels one world unit is drawn as. Positions, sizes, speeds & ranges are all in
+    // world units, so the world is window_width / pixels_per_unit units wide
+    pub pixels_per_unit: f32,
+    pub population_mode: PopulationMode,
+    pub total_population: i32,
+    pub predator_fraction: f32,
+    pub predator_population: i32,
+    pub prey_population: i32,
+    pub predator_speed: f32,
+    pub prey_speed: f32,
+    pub predator_life: i32,
+    pub prey_life: i32,
+    pub prey_energy_loss: i32,
+    pub predator_energy_loss: i32,
+    pub prey_idle_energy_gain: i32,
+    pub predator_idle_energy_gain: i32,
+    pub predator_hunt_energy_gain: i32,
+    pub reproduction_energy_mode: ReproductionEnergyMode,
+    pub prey_repro_fraction: f32,
+    pub predator_repro_fraction: f32,
+    pub prey_reproduction_energy: i32,
+    pub predator_reproduction_energy: i32,
+    pub prey_detection_range: f32,
+    pub prey_panic_range: f32,
+    pub prey_wary_feeding_fraction: f32,
+    // The fraction of new prey that freeze instead of fleeing, how often a child switches from
+    // its parent's strategy, and how much less of their detection range predators see frozen prey
+    pub prey_freeze_fraction: f32,
+    pub freeze_mutation_chance: f32,
+    pub freeze_detection_penalty: f32,
+    pub predator_detection_range: f32,
+    pub prey_mating_range: f32,
+    pub predator_mating_range: f32,
+    pub default_dimensions: f32,
+    pub environment_grow_rate: f32,
+    pub environment_max: i32,
+    pub initial_environment_fraction: f32,
+    pub environment_update_interval_secs: f32,
+    pub wiggle_when_hunted: bool,
+    pub alarm_enabled: bool,
+    pub alarm_radius: f32,
+    pub collision_shape: CollisionShape,
+    pub rescale_on_resize: bool,
+    pub metrics_output: String,
+    pub metrics_interval: f32,
+    pub metrics_columns: String,
+    pub predator_satiation_threshold: i32,
+    pub predation_efficiency: f32,
+    pub predation_success_chance: f32,
+    pub predation_miss_stun_ticks: u32,
+    pub conserve_reproduction_energy: bool,
+    pub post_mate_reserve: i32,
+    pub speed_mutation: f32,
+    pub speed_metabolism_coefficient: f32,
+    pub prevent_extinction: bool,
+    pub min_prey: i32,
+    pub min_predator: i32,
+    pub cover_regions: String,
+    pub prey_seek_cover: bool,
+    pub background_color: Color,
+    pub prey_color: Color,
+    pub predator_color: Color,
+    pub environment_color: Color,
+    pub show_grid: bool,
+    pub grid_spacing: f32,
+    pub grid_color: Color,
+    pub color_by_status: bool,
+    pub status_tint_strength: f32,
+    pub prey_status_colors: String,
+    pub predator_status_colors: String,
+    pub fade_low_energy: bool,
+    pub fade_energy_fraction: f32,
+    pub fade_min_alpha: f32,
+    pub show_trails: bool,
+    pub trail_length: usize,
+    pub spatial_index: SpatialIndexKind,
+    pub spatial_cell_size: f32,
+    pub quadtree_capacity: usize,
+    pub cache_spatial_index: bool,
+    pub show_spatial_index: bool,
+    pub spatial_query_debug: bool,
+    pub spatial_query_circle: bool,
+    pub burrow_regions: String,
+    pub burrow_capacity: u32,
+    pub burrow_energy_regen: i32,
+    pub burrow_leave_energy: i32,
+    pub separation_enabled: bool,
+    pub separation_strength: f32,
+    pub grass_enabled: bool,
+    pub grass_columns: usize,
+    pub grass_rows: usize,
+    pub grass_max_biomass: f32,
+    pub grass_initial_fraction: f32,
+    pub grass_regrowth_rate: f32,
+    pub grass_graze_amount: f32,
+    pub show_grass: bool,
+    pub screenshot_hide_ui: bool,
+    pub use_fov: bool,
+    pub predator_fov_degrees: f32,
+    pub prey_fov_degrees: f32,
+    pub max_entities: usize,
+    pub validate_positions: bool,
+    pub despawn_invalid_positions: bool,
+    pub breed_resource_threshold: f32,
+    pub breed_energy_margin: i32,
+    pub predator_requires_recent_kill: bool,
+    pub kill_recency_ticks: u64,
+    pub flee_toward_herd: f32,
+    pub record_snapshots: bool,
+    pub record_interval_ticks: u64,
+    pub record_output: String,
+    pub idle_behavior: IdleBehavior,
+    pub movement_model: MovementModel,
+    pub max_accel: f32,
+    pub random_walk_turn_degrees: f32,
+    pub patrol_distance: f32,
+    pub check_invariants: bool,
+    pub panic_on_invariant_violation: bool,
+    pub scale_by_energy: bool,
+    pub energy_scale_min: f32,
+    pub energy_scale_max: f32,
+    pub energy_scale_collision: bool,
+    pub wall_avoidance: bool,
+    pub wall_margin: f32,
+    pub wall_avoidance_strength: f32,
+    pub layout_file: String,
+    pub interaction_matrix: String,
+    pub fixed_timestep_hz: f32,
+    pub predator_memory_ticks: u32,
+    pub per_entity_rng: bool,
+    pub plot_max_points: u32,
+    pub prey_wiggle: f32,
+    pub predator_wiggle: f32,
+    pub aggression_enabled: bool,
+    pub predator_aggression: f32,
+    pub aggression_mutation: f32,
+    pub aggression_energy_cost: f32,
+    pub record_format: SnapshotFormat,
+    pub detection_range_variance: f32,
+    pub detection_range_mutation: f32,
+    pub detection_metabolism_coefficient: f32,
+    pub summary_output: String,
+    pub show_prey_density: bool,
+    pub show_predator_density: bool,
+    pub density_columns: u32,
+    pub density_rows: u32,
+    pub density_opacity: f32,
+    pub density_update_interval: f32,
+    // How many bins the gene histogram has, how many simulated seconds between its samples, and
+    // whether the mean & standard deviation over time are drawn under it
+    pub gene_histogram_bins: u32,
+    pub gene_histogram_interval: f32,
+    pub gene_histogram_spread: bool,
+    pub log_energy_accounting: bool,
+    pub msaa_samples: u32,
+    pub crisp_sprites: bool,
+    pub perturbations: String,
+    pub prey_mobbing: bool,
+    pub prey_mobbing_energy_threshold: i32,
+    pub prey_mobbing_group_size: u32,
+    pub prey_mobbing_range: f32,
+    pub prey_mobbing_damage: i32,
+    pub prey_detection_mask: u32,
+    pub prey_collision_mask: u32,
+    pub predator_detection_mask: u32,
+    pub predator_collision_mask: u32,
+    pub simulate_to: f32,
+    // "easy", "normal", "hard" or a multiplier, already applied to the fields it scales
+    pub difficulty: String,
+    pub run_report: RunReportFormat,
+    pub realistic_reproduction: bool,
+    pub parallel_detection: bool,
+    pub kill_sharing: KillSharing,
+    // Whether something in range is only noticed by chance, falling off with distance past
+    // detection_certain_fraction of the range
+    pub probabilistic_detection: bool,
+    pub detection_falloff: DetectionFalloff,
+    pub detection_certain_fraction: f32,
+    pub open_world: bool,
+    pub left_edge: EdgeBehavior,
+    pub right_edge: EdgeBehavior,
+    pub bottom_edge: EdgeBehavior,
+    pub top_edge: EdgeBehavior,
+    pub cull_distance: f32,
+    pub immigration_rate: f32,
+    // How many creatures with random genes arrive at the edges every second, and how far (as a
+    // fraction) their genes can be from the species' base values
+    pub gene_immigration_rate: f32,
+    pub gene_immigration_spread: f32,
+    pub seed: u64,
+    pub ensemble_runs: u32,
+    pub ensemble_ticks: u32,
+    pub ensemble_output: String,
+}
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Mortal {
+    dead: bool,
+}
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Prey {
+    // 0 is idle, 1 is mating, 2 is avoiding, 4 is hiding in a burrow,
+    // 5 is wary (a predator is in sight but not close enough to run from),
+    // 6 is frozen (standing still instead of running, see Genes::freezes)
+    status: u16,
+}
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Predator {
+    status: u16, // 0 is idle, 1 is mating, 2 is hunting
+    // Where the prey being hunted was last seen, and for how many more ticks it's remembered
+    last_seen_prey: Option<Vec2>,
+    memory_ticks_left: u32,
+    // Ticks left standing still after a failed attack, see predation_success_chance
+    stunned_ticks_left: u32,
+}
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct MatingTarget {
+    entity: Option<PositionSize>,
+    partner: Option<u64>,
+    // The partner's StableId is stored for when we mate with the target. Eventually, the partner with
+    // the higher id will have the child. This is to prevent making twins when both the partners run
+    // reproduction code.
+}
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Life {
+    value: i32,
+}
+
+// Traits passed down from parent to child, with a bit of mutation each time
+#[derive(Reflect, Component, Clone)]
+#[reflect(Component)]
+pub struct Genes {
+    speed: f32,
+    // From 0 to 1, how far & how long a predator chases prey. Prey have it but don't use it
+    aggression: f32,
+    // How far away it can see, for predators that's prey and for prey it's predators & shelter
+    detection_range: f32,
+    // Whether a prey freezes in place when a predator gets close instead of running, which makes
+    // it harder to spot. Predators have it but don't use it
+    freezes: bool,
+}
+
+impl Genes {
+    // What creatures that weren't born start with. Everyone has their species' speed, but their
+    // detection range can be spread up to detection_range_variance either side of the base
+    fn starting_prey(settings: &Settings, rng: &mut StdRng) -> Self {
+        Genes {
+            speed: settings.prey_speed,
+            aggression: 0.0,
+            detection_range: starting_detection_range(settings.prey_detection_range, settings, rng),
+            // Only rolled for a mix, so the random numbers are unchanged when everyone flees
+            freezes: settings.prey_freeze_fraction >= 1.0
+                || (settings.prey_freeze_fraction > 0.0
+                    && rng.gen_bool(settings.prey_freeze_fraction as f64)),
+        }
+    }
+
+    fn starting_predator(settings: &Settings, rng: &mut StdRng) -> Self {
+        Genes {
+            speed: settings.predator_speed,
+            aggression: settings.predator_aggression,
+            detection_range: starting_detection_range(
+                settings.predator_detection_range,
+                settings,
+                rng,
+            ),
+            freezes: false,
+        }
+    }
+
+    // Fresh genes for gene immigrants, each spread up to gene_immigration_spread either side of
+    // the species' base value. A predator's aggression can be anything
+    fn random_prey(settings: &Settings, rng: &mut StdRng) -> Self {
+        let spread = settings.gene_immigration_spread;
+        Genes {
+            speed: (settings.prey_speed * (1.0 + rng.gen_range(-spread..=spread))).max(0.0),
+            aggression: 0.0,
+            detection_range: (settings.prey_detection_range
+                * (1.0 + rng.gen_range(-spread..=spread)))
+            .max(0.0),
+            freezes: rng.gen_bool(settings.prey_freeze_fraction as f64),
+        }
+    }
+
+    fn random_predator(settings: &Settings, rng: &mut StdRng) -> Self {
+        let spread = settings.gene_immigration_spread;
+        Genes {
+            speed: (settings.predator_speed * (1.0 + rng.gen_range(-spread..=spread))).max(0.0),
+            aggression: rng.gen_range(0.0..=1.0),
+            detection_range: (settings.predator_detection_range
+                * (1.0 + rng.gen_range(-spread..=spread)))
+            .max(0.0),
+            freezes: false,
+        }
+    }
+
+    // Aggression scales a predator's detection range & memory, with 0.5 leaving them as they are
+    // and 1 doubling them. Without aggression_enabled every predator hunts the same
+    fn pursuit_scale(&self, settings: &Settings) -> f32 {
+        if settings.aggression_enabled {
+            self.aggression * 2.0
+        } else {
+            1.0
+        }
+    }
+}
+
+// When a predator last ate, as a SimClock tick
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct LastKill {
+    tick: Option<u64>,
+}
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Age {
+    ticks: u32, // How many frames the entity has been alive for
+}
+
+// Regions like tall grass that prey can hide in, its PositionSize is the center & size of the region
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Cover;
+
+// Holes prey can shelter in while fleeing. Prey inside can't be eaten and recover energy faster,
+// but can't eat the environment. Its PositionSize is the center & size of the burrow
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Burrow;
+
+#[derive(Reflect, Component)]
+#[reflect(Component)]
+pub struct Environment {
+    energy_pool: i32,
+}
+
+fn spawn_prey(
+    commands: &mut Commands,
+    settings: &Settings,
+    x: f32,
+    y: f32,
+    life: i32,
+    genes: Genes,
+) -> Entity {
+    commands
+        .spawn((
+            Prey { status: 0 },
+            PREY_SPECIES,
+            Layers::prey(settings),
+            Mortal { dead: false },
+            MatingTarget {
+                entity: None,
+                partner: None,
+            },
+            Life { value: life },
+            genes,
+            Age { ticks: 0 },
+            // Grouped as a bundle can only hold so many components
+            (
+                // Numbered before CreatureRng, which is seeded from it
+                StableId::default(),
+                CreatureRng::default(),
+                Heading::default(),
+                Velocity::default(),
+                PreviousPosition { x, y },
+                Patrol::default(),
+                Parents::default(),
+            ),
+            Trail::default(),
+            PositionSize {
+                x,
+                y,
+                width: settings.default_dimensions,
+                height: settings.default_dimensions,
+            },
+            Sprite {
+                color: settings.prey_color,
+                custom_size: Some(Vec2::new(
+                    settings.default_dimensions,
+                    settings.default_dimensions,
+                )),
+                ..default()
+            },
+            Transform::from_xyz(x, y, 0.0),
+        ))
+        .id()
+}
+
+fn spawn_predator(
+    commands: &mut Commands,
+    settings: &Settings,
+    x: f32,
+    y: f32,
+    life: i32,
+    genes: Genes,
+) -> Entity {
+    commands
+        .spawn((
+            Predator {
+                status: 0,
+                last_seen_prey: None,
+                memory_ticks_left: 0,
+                stunned_ticks_left: 0,
+            },
+            PREDATOR_SPECIES,
+            Layers::predator(settings),
+            LastKill::default(),
+            Mortal { dead: false },
+            MatingTarget {
+                entity: None,
+                partner: None,
+            },
+            Life { value: life },
+            genes,
+            Age { ticks: 0 },
+            // Grouped as a bundle can only hold so many components
+            (
+                // Numbered before CreatureRng, which is seeded from it
+                StableId::default(),
+                CreatureRng::default(),
+                Heading::default(),
+                Velocity::default(),
+                PreviousPosition { x, y },
+                Patrol::default(),
+                Parents::default(),
+            ),
+            Trail::default(),
+            PositionSize {
+                x,
+                y,
+                width: settings.default_dimensions,
+                height: settings.default_dimensions,
+            },
+            Sprite {
+                color: settings.predator_color,
+                custom_size: Some(Vec2::new(
+                    settings.default_dimensions,
+                    settings.default_dimensions,
+                )),
+                ..default()
+            },
+            Transform::from_xyz(x, y, 0.0),
+        ))
+        .id()
+}
+
+// Picks a random spot anywhere inside the configured window
+fn random_position(rng: &mut SimRng, settings: &Settings) -> (f32, f32) {
+    let world_size = world_size(settings);
+
+    let random_x: f32 = rng
+        .0
+        .gen_range((-(world_size.x / 2.0).abs())..(world_size.x / 2.0).abs());
+    let random_y: f32 = rng
+        .0
+        .gen_range((-(world_size.y / 2.0).abs())..(world_size.y / 2.0).abs());
+
+    (random_x, random_y)
+}
+
+// The energy a creature needs before it can mate. On top of what mating costs, it has to keep
+// post_mate_reserve back so it isn't left about to starve once the child is born
+fn mating_threshold(reproduction_energy: i32, settings: &Settings) -> i32 {
+    reproduction_energy.saturating_add(settings.post_mate_reserve)
+}
+
+// Prey only go looking for a mate once they're comfortably past what mating costs them, not the
+// moment they can just about afford it
+fn prey_mating_threshold(settings: &Settings) -> i32 {
+    mating_threshold(settings.prey_reproduction_energy, settings)
+        .saturating_add(settings.breed_energy_margin)
+}
+
+fn can_mate(current_energy: i32, required_energy: i32, status: u16) -> bool {
+    // Check to make sure the predator or prey isn't hunting or being hunted
+    if status == 2 {
+        return false;
+    }
+
+    current_energy >= required_energy
+}
+
+type PreyTargets<'w, 's> =
+    Query<'w, 's, (&'static PositionSize, &'static Prey), (With<Prey>, Without<Predator>)>;
+type Covers<'w, 's> =
+    Query<'w, 's, &'static PositionSize, (With<Cover>, Without<Prey>, Without<Predator>)>;
+type Creatures<'w, 's> = Query<'w, 's, (), Or<(With<Prey>, With<Predator>)>>;
+
+// The closest prey a predator can see, and how far away it is
+#[allow(clippy::too_many_arguments)]
+fn closest_visible_prey(
+    predator_position_size: &PositionSize,
+    heading: &Heading,
+    layers: &Layers,
+    detection_range: f32,
+    preys: &PreyTargets,
+    covers: &Covers,
+    indexes: &SpatialIndexes,
+    settings: &Settings,
+    nearby_preys: &mut Vec<Entity>,
+    mut rng: Option<&mut StdRng>,
+) -> Option<(PositionSize, f32)> {
+    let mut closest: Option<(PositionSize, f32)> = None;
+
+    nearby_preys.clear();
+    if layers.detects(PREY_SPECIES) {
+        indexes.prey.query_radius(
+            Vec2::new(predator_position_size.x, predator_position_size.y),
+            detection_range,
+            nearby_preys,
+        );
+    }
+
+    for prey_entity in nearby_preys.iter() {
+        let Ok((prey_position_size, prey)) = preys.get(*prey_entity) else {
+            continue;
+        };
+
+        // Prey hiding in a burrow or cover can't be seen
+        if prey.status == 4
+            || covers
+                .iter()
+                .any(|cover| is_inside(prey_position_size, cover))
+        {
+            continue;
+        }
+
+        let (detected, distance) = if settings.use_fov {
+            in_detection_cone(
+                predator_position_size,
+                heading,
+                prey_position_size,
+                detection_range,
+                settings.predator_fov_degrees,
+            )
+        } else {
+            in_detection_range(predator_position_size, prey_position_size, detection_range)
+        };
+
+        // Frozen prey are only spotted from closer up, but once they are they don't run
+        let frozen_range = detection_range * (1.0 - settings.freeze_detection_penalty);
+        if prey.status == 6 && distance > frozen_range {
+            continue;
+        }
+
+        if detected
+            && closest
+                .as_ref()
+                .is_none_or(|(_, closest)| distance < *closest)
+            && roll_detection(distance, detection_range, settings, rng.as_deref_mut())
+        {
+            closest = Some((prey_position_size.clone(), distance));
+        }
+    }
+
+    closest
+}
+
+// The closest predator a prey can see, and how far away it is
+#[allow(clippy::too_many_arguments)]
+fn closest_visible_predator(
+    prey_position_size: &PositionSize,
+    heading: &Heading,
+    layers: &Layers,
+    genes: &Genes,
+    predators: &Query<&PositionSize, (With<Predator>, Without<Prey>)>,
+    indexes: &SpatialIndexes,
+    settings: &Settings,
+    nearby_predators: &mut Vec<Entity>,
+    mut rng: Option<&mut StdRng>,
+) -> Option<(PositionSize, f32)> {
+    let mut closest: Option<(PositionSize, f32)> = None;
+
+    nearby_predators.clear();
+    if layers.detects(PREDATOR_SPECIES) {
+        indexes.predators.query_radius(
+            Vec2::new(prey_position_size.x, prey_position_size.y),
+            genes.detection_range,
+            nearby_predators,
+        );
+    }
+
+    for predator_entity in nearby_predators.iter() {
+        let Ok(predator_position_size) = predators.get(*predator_entity) else {
+            continue;
+        };
+
+        let (detected, distance) = if settings.use_fov {
+            in_detection_cone(
+                prey_position_size,
+                heading,
+                predator_position_size,
+                genes.detection_range,
+                settings.prey_fov_degrees,
+            )
+        } else {
+            in_detection_range(
+                prey_position_size,
+                predator_position_size,
+                genes.detection_range,
+            )
+        };
+
+        if detected
+            && closest
+                .as_ref()
+                .is_none_or(|(_, closest)| distance < *closest)
+            && roll_detection(
+                distance,
+                genes.detection_range,
+                settings,
+                rng.as_deref_mut(),
+            )
+        {
+            closest = Some((predator_position_size.clone(), distance));
+        }
+    }
+
+    closest
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_predators(
+    mut predators: Query<
+        (
+            Entity,
+            &mut PositionSize,
+            &MatingTarget,
+            &mut Predator,
+            &Life,
+            &Genes,
+            &Layers,
+            &mut Heading,
+            &mut Velocity,
+            &mut Patrol,
+            &mut CreatureRng,
+        ),
+        (With<Predator>, Without<Prey>, Without<BeingDragged>),
+    >,
+    preys: PreyTargets,
+    covers: Covers,
+    indexes: Res<SpatialIndexes>,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+    mut shared_rng: ResMut<SimRng>,
+) {
+    let _span = debug_span!(target: "profiling", "update_predators").entered();
+
+    let mut nearby_preys = Vec::new();
+    let half_size = world_half_size(&windows, &settings);
+
+    // Looking for prey only reads where they are, so with parallel_detection every predator looks
+    // at once on the task pool before any of them move. What each one saw is kept by predator, so
+    // it doesn't matter which thread finished first. Rolling for probabilistic_detection needs
+    // each predator's random numbers in order, so that's always done one after another
+    let parallel = settings.parallel_detection && !settings.probabilistic_detection;
+    let mut seen_in_parallel: HashMap<Entity, Option<(PositionSize, f32)>> = HashMap::new();
+    if parallel {
+        let mut seen: Parallel<Vec<(Entity, Option<(PositionSize, f32)>)>> = Parallel::default();
+        predators.par_iter().for_each_init(
+            Vec::new,
+            |nearby_preys, (entity, predator_position_size, _, _, _, genes, layers, heading, _, _, _)| {
+                let closest = closest_visible_prey(
+                    predator_position_size,
+                    heading,
+                    layers,
+                    genes.detection_range * genes.pursuit_scale(&settings),
+                    &preys,
+                    &covers,
+                    &indexes,
+                    &settings,
+                    nearby_preys,
+                    None,
+                );
+                seen.borrow_local_mut().push((entity, closest));
+            },
+        );
+        seen_in_parallel.extend(seen.drain());
+    }
+
+    for (
+        entity,
+        mut predator_position_size,
+        mating_target,
+        mut predator,
+        life,
+        genes,
+        layers,
+        mut heading,
+        mut velocity,
+        mut patrol,
+        mut own_rng,
+    ) in predators.iter_mut()
+    {
+        let start = Vec2::new(predator_position_size.x, predator_position_size.y);
+        let rng = creature_rng(&mut shared_rng, &mut own_rng, &settings);
+
+        // Predators that just missed a kill stand still until they've recovered
+        if predator.stunned_ticks_left > 0 {
+            predator.stunned_ticks_left -= 1;
+            velocity.0 = Vec2::ZERO;
+            continue;
+        }
+
+        // Full predators don't bother hunting until they've burned off some energy
+        let satiated = settings.predator_satiation_threshold > 0
+            && life.value > settings.predator_satiation_threshold;
+
+        // More aggressive predators spot prey from further away & chase them for longer
+        let pursuit_scale = genes.pursuit_scale(&settings);
+        let detection_range = genes.detection_range * pursuit_scale;
+
+        // Store the closest position of a prey
+        let closest_prey_position = if parallel {
+            seen_in_parallel.remove(&entity).flatten()
+        } else {
+            closest_visible_prey(
+                &predator_position_size,
+                &heading,
+                layers,
+                detection_range,
+                &preys,
+                &covers,
+                &indexes,
+                &settings,
+                &mut nearby_preys,
+                Some(&mut *rng),
+            )
+        }
+        .map(|(position_size, _)| position_size);
+
+        // Remember where the prey was, so losing sight of it for a moment doesn't end the hunt
+        let mut remembered_prey: Option<PositionSize> = None;
+        if let Some(closest_prey) = &closest_prey_position {
+            predator.last_seen_prey = Some(Vec2::new(closest_prey.x, closest_prey.y));
+            predator.memory_ticks_left =
+                (settings.predator_memory_ticks as f32 * pursuit_scale).round() as u32;
+        } else if let Some(last_seen) = predator.last_seen_prey {
+            // Give up once the memory fades or the spot has been checked and there's nothing there
+            if predator.memory_ticks_left == 0
+                || Vec2::new(predator_position_size.x, predator_position_size.y).distance(last_seen)
+                    <= genes.speed
+            {
+                predator.last_seen_prey = None;
+            } else {
+                predator.memory_ticks_left -= 1;
+                remembered_prey = Some(PositionSize {
+                    x: last_seen.x,
+                    y: last_seen.y,
+                    width: 0.0,
+                    height: 0.0,
+                });
+            }
+        }
+
+        if mating_target.entity.is_some() {
+            predator.status = 1; // Mating
+        } else if (closest_prey_position.is_some() || remembered_prey.is_some()) && !satiated {
+            predator.status = 2; // Hunting
+        } else {
+            predator.status = 0; // Idle
+        }
+
+        // Check to see if we can mate, then move towards the target
+        if predator.status == 1 {
+            if let Some(target) = &mating_target.entity {
+                move_towards(&mut predator_position_size, target, genes.speed);
+            }
+        } else if predator.status == 2 {
+            if let Some(closest_prey) = closest_prey_position.as_ref().or(remembered_prey.as_ref())
+            {
+                move_towards(&mut predator_position_size, closest_prey, genes.speed);
+            }
+        } else {
+            idle_move(
+                &mut predator_position_size,
+                &heading,
+                &mut patrol,
+                genes.speed,
+                &settings,
+                rng,
+            );
+        }
+
+        steer_from_walls(start, &mut predator_position_size, half_size, &settings);
+        apply_velocity(
+            start,
+            &mut predator_position_size,
+            &mut velocity,
+            genes.speed,
+            &settings,
+        );
+
+        heading.update(
+            start,
+            Vec2::new(predator_position_size.x, predator_position_size.y),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn update_preys(
+    mut prey_query: Query<
+        (
+            Entity,
+            &mut PositionSize,
+            &mut Life,
+            &MatingTarget,
+            &mut Prey,
+            &Genes,
+            &Layers,
+            &mut Heading,
+            &mut Velocity,
+            &mut Patrol,
+            &mut CreatureRng,
+        ),
+        (With<Prey>, Without<Predator>, Without<BeingDragged>),
+    >,
+    predator_query: Query<&PositionSize, (With<Predator>, Without<Prey>)>,
+    covers: Query<&PositionSize, (With<Cover>, Without<Prey>, Without<Predator>)>,
+    burrows: Query<(Entity, &PositionSize), (With<Burrow>, Without<Prey>, Without<Predator>)>,
+    mut environment_query: Query<&mut Environment>,
+    mut grass: ResMut<GrassGrid>,
+    indexes: Res<SpatialIndexes>,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+    mut shared_rng: ResMut<SimRng>,
+) {
+    let _span = debug_span!(target: "profiling", "update_preys").entered();
+
+    let mut nearby_predators = Vec::new();
+    let mut nearby_preys = Vec::new();
+    let half_size = world_half_size(&windows, &settings);
+
+    // Looking for predators only reads where they are, so it's done for every prey at once on the
+    // task pool with parallel_detection, the same as for the predators
+    let parallel = settings.parallel_detection && !settings.probabilistic_detection;
+    let mut seen_in_parallel: HashMap<Entity, Option<(PositionSize, f32)>> = HashMap::new();
+    if parallel {
+        let mut seen: Parallel<Vec<(Entity, Option<(PositionSize, f32)>)>> = Parallel::default();
+        prey_query.par_iter().for_each_init(
+            Vec::new,
+            |nearby_predators,
+             (entity, prey_position_size, _, _, _, genes, layers, heading, _, _, _)| {
+                let closest = closest_visible_predator(
+                    prey_position_size,
+                    heading,
+                    layers,
+                    genes,
+                    &predator_query,
+                    &indexes,
+                    &settings,
+                    nearby_predators,
+                    None,
+                );
+                seen.borrow_local_mut().push((entity, closest));
+            },
+        );
+        seen_in_parallel.extend(seen.drain());
+    }
+
+    // Where every prey was at the start of the frame, for finding the middle of the herd
+    let mut prey_positions: HashMap<Entity, Vec2> = HashMap::new();
+    if settings.flee_toward_herd > 0.0 {
+        for (entity, position_size, _, _, _, _, _, _, _, _, _) in prey_query.iter() {
+            prey_positions.insert(entity, Vec2::new(position_size.x, position_size.y));
+        }
+    }
+
+    // Count who's already in each burrow so they don't fill up past their capacity
+    let mut burrow_occupants: HashMap<Entity, u32> = HashMap::new();
+    for (_, prey_position_size, _, _, prey, _, _, _, _, _, _) in prey_query.iter() {
+        if prey.status != 4 {
+            continue;
+        }
+        if let Some((burrow_entity, _)) = burrows
+            .iter()
+            .find(|(_, burrow)| is_inside(prey_position_size, burrow))
+        {
+            *burrow_occupants.entry(burrow_entity).or_insert(0) += 1;
+        }
+    }
+
+    for (
+        entity,
+        mut prey_position_size,
+        mut life,
+        mating_target,
+        mut prey,
+        genes,
+        layers,
+        mut heading,
+        mut velocity,
+        mut patrol,
+        mut own_rng,
+    ) in prey_query.iter_mut()
+    {
+        let start = Vec2::new(prey_position_size.x, prey_position_size.y);
+        let rng = creature_rng(&mut shared_rng, &mut own_rng, &settings);
+
+        let closest_predator = if parallel {
+            seen_in_parallel.remove(&entity).flatten()
+        } else {
+            closest_visible_predator(
+                &prey_position_size,
+                &heading,
+                layers,
+                genes,
+                &predator_query,
+                &indexes,
+                &settings,
+                &mut nearby_predators,
+                Some(&mut *rng),
+            )
+        };
+
+        // Store the closest position of a predator, with a humongous distance when there isn't one
+        let closest_predator_distance = closest_predator
+            .as_ref()
+            .map_or(f32::MAX, |(_, distance)| *distance);
+        let closest_predator_position = closest_predator.map(|(position_size, _)| position_size);
+
+        if prey.status == 4 {
+            if let Some((burrow_entity, _)) = burrows
+                .iter()
+                .find(|(_, burrow)| is_inside(&prey_position_size, burrow))
+            {
+                // Stay hidden & recover until we're healthy and there's no predator around
+                if life.value < settings.burrow_leave_energy || closest_predator_position.is_some()
+                {
+                    life.value += settings.burrow_energy_regen;
+                    velocity.0 = Vec2::ZERO;
+                    continue;
+                }
+
+                if let Some(occupants) = burrow_occupants.get_mut(&burrow_entity) {
+                    *occupants = occupants.saturating_sub(1);
+                }
+            }
+        }
+
+        // Predators inside the panic range send prey running, further away they only make prey
+        // wary, which keeps them grazing (less efficiently) instead of running at every sighting
+        if closest_predator_distance <= settings.prey_panic_range {
+            prey.status = if genes.freezes {
+                6 // Frozen
+            } else {
+                2 // Running
+            }
+        } else if closest_predator_position.is_some() {
+            prey.status = 5 // Wary
+        } else if can_mate(life.value, prey_mating_threshold(&settings), prey.status) {
+            prey.status = 1 // Mating
+        } else {
+            prey.status = 0 // Idle
+        }
+
+        // Frozen prey don't move or graze until the predator has gone
+        if prey.status == 6 {
+            velocity.0 = Vec2::ZERO;
+            continue;
+        }
+
+        // This code checks to see if there is a closest predator position
+        // and assigns closest predator the value to pass to the avoid function
+        if prey.status == 2 {
+            // Look for the closest burrow we can see with room left to shelter in
+            let mut closest_burrow: Option<(Entity, &PositionSize)> = None;
+            let mut closest_burrow_distance: f32 = f32::MAX;
+
+            for (burrow_entity, burrow) in burrows.iter() {
+                let occupants = burrow_occupants.get(&burrow_entity).copied().unwrap_or(0);
+                if occupants >= settings.burrow_capacity {
+                    continue;
+                }
+
+                let (detected, distance) =
+                    in_detection_range(&prey_position_size, burrow, genes.detection_range);
+
+                if detected && distance < closest_burrow_distance {
+                    closest_burrow = Some((burrow_entity, burrow));
+                    closest_burrow_distance = distance;
+                }
+            }
+
+            if let Some((burrow_entity, burrow)) = closest_burrow {
+                if is_inside(&prey_position_size, burrow) {
+                    prey.status = 4; // In a burrow
+                    *burrow_occupants.entry(burrow_entity).or_insert(0) += 1;
+                    velocity.0 = Vec2::ZERO;
+                } else {
+                    move_towards(&mut prey_position_size, burrow, genes.speed);
+                    apply_velocity(
+                        start,
+                        &mut prey_position_size,
+                        &mut velocity,
+                        genes.speed,
+                        &settings,
+                    );
+                    heading.update(start, Vec2::new(prey_position_size.x, prey_position_size.y));
+                }
+                continue;
+            }
+
+            // Look for the closest cover we can see to hide in instead of just running
+            let mut closest_cover: Option<&PositionSize> = None;
+            let mut closest_cover_distance: f32 = f32::MAX;
+
+            if settings.prey_seek_cover {
+                for cover in covers.iter() {
+                    let (detected, distance) =
+                        in_detection_range(&prey_position_size, cover, genes.detection_range);
+
+                    if detected && distance < closest_cover_distance {
+                        closest_cover = Some(cover);
+                        closest_cover_distance = distance;
+                    }
+                }
+            }
+
+            if let Some(cover) = closest_cover {
+                // Stay still once we're hidden, otherwise head for the cover
+                if !is_inside(&prey_position_size, cover) {
+                    move_towards(&mut prey_position_size, cover, genes.speed);
+                }
+            } else if let Some(closest_predator) = &closest_predator_position {
+                let position = Vec2::new(prey_position_size.x, prey_position_size.y);
+
+                // Find the middle of the other prey we can see
+                let mut herd_center: Option<Vec2> = None;
+                if settings.flee_toward_herd > 0.0 && layers.detects(PREY_SPECIES) {
+                    nearby_preys.clear();
+                    indexes
+                        .prey
+                        .query_radius(position, genes.detection_range, &mut nearby_preys);
+
+                    let herd: Vec<Vec2> = nearby_preys
+                        .iter()
+                        .filter(|nearby_entity| **nearby_entity != entity)
+                        .filter_map(|nearby_entity| prey_positions.get(nearby_entity).copied())
+                        .collect();
+                    if !herd.is_empty() {
+                        herd_center = Some(herd.iter().sum::<Vec2>() / herd.len() as f32);
+                    }
+                }
+
+                let away = (position - Vec2::new(closest_predator.x, closest_predator.y))
+                    .normalize_or_zero();
+
+                match herd_center {
+                    // Run partly back towards the herd instead of just away from the predator,
+                    // so prey stick together instead of getting cornered on their own
+                    Some(herd_center) if away != Vec2::ZERO => {
+                        let toward_herd = (herd_center - position).normalize_or_zero();
+                        let direction = away * (1.0 - settings.flee_toward_herd)
+                            + toward_herd * settings.flee_toward_herd;
+
+                        if direction == Vec2::ZERO {
+                            move_in_direction(&mut prey_position_size, away, genes.speed);
+                        } else {
+                            move_in_direction(&mut prey_position_size, direction, genes.speed);
+                        }
+                    }
+                    _ => avoid(&mut prey_position_size, closest_predator, genes.speed, rng),
+                }
+            }
+        // Check to see we can mate and there is an available mate
+        } else if prey.status == 1 && mating_target.entity.is_some() {
+            if let Some(target) = &mating_target.entity {
+                move_towards(&mut prey_position_size, target, genes.speed);
+            }
+        } else if prey.status == 0 || prey.status == 5 {
+            idle_move(
+                &mut prey_position_size,
+                &heading,
+                &mut patrol,
+                genes.speed,
+                &settings,
+                rng,
+            );
+        }
+
+        steer_from_walls(start, &mut prey_position_size, half_size, &settings);
+        apply_velocity(
+            start,
+            &mut prey_position_size,
+            &mut velocity,
+            genes.speed,
+            &settings,
+        );
+
+        heading.update(start, Vec2::new(prey_position_size.x, prey_position_size.y));
+
+        // Wary prey keep one eye on the predator, so only eat part of what they otherwise would
+        let feeding_fraction = if prey.status == 5 {
+            settings.prey_wary_feeding_fraction
+        } else {
+            1.0
+        };
+
+        // Prey "eats" the grass patch they're standing on to regain life
+        if settings.grass_enabled {
+            if prey.status != 3 {
+                let eaten = grass.graze(
+                    prey_position_size.x,
+                    prey_position_size.y,
+                    settings.grass_graze_amount * feeding_fraction,
+                    settings.grass_max_biomass,
+                );
+                // Life is a whole number, so randomly round what was eaten up or down so wary
+                // prey still get the right fraction on average. Whole amounts don't need a roll
+                let whole = eaten.floor();
+                life.value += whole as i32;
+                if eaten > whole && rng.gen::<f32>() < eaten - whole {
+                    life.value += 1;
+                }
+            }
+            continue;
+        }
+
+        // Prey "eats" the environment to regain life
+        for mut environment in environment_query.iter_mut() {
+            // Checks to make sure energy can be taken from the environment and that we aren't being chased
+            // The environment is eaten a unit at a time, so wary prey eat on only some ticks
+            if environment.energy_pool > 0
+                && prey.status != 3
+                && (feeding_fraction >= 1.0 || rng.gen_bool(feeding_fraction.max(0.0) as f64))
+            {
+                environment.energy_pool -= 1;
+                life.value += 1;
+            }
+        }
+    }
+}
+
+fn propagate_prey_alarm(
+    mut prey_query: Query<(
+        Entity,
+        &mut PositionSize,
+        &mut Prey,
+        &Genes,
+        &mut CreatureRng,
+    )>,
+    indexes: Res<SpatialIndexes>,
+    settings: Res<Settings>,
+    mut shared_rng: ResMut<SimRng>,
+) {
+    if !settings.alarm_enabled {
+        return;
+    }
+
+    // Take a snapshot of every prey that is already running so alarmed prey
+    // don't pass the alarm along any further during this frame
+    let alarming_preys: HashMap<Entity, PositionSize> = prey_query
+        .iter()
+        .filter(|(_, _, prey, _, _)| prey.status == 2)
+        .map(|(entity, position_size, _, _, _)| (entity, position_size.clone()))
+        .collect();
+
+    if alarming_preys.is_empty() {
+        return;
+    }
+
+    let mut nearby_preys = Vec::new();
+
+    for (entity, mut prey_position_size, mut prey, genes, mut own_rng) in prey_query.iter_mut() {
+        // Prey that spotted a predator themselves are already running or frozen,
+        // and prey in burrows are already safe
+        if prey.status == 2 || prey.status == 4 || prey.status == 6 {
+            continue;
+        }
+
+        let mut closest_alarm_position: Option<&PositionSize> = None;
+        let mut closest_alarm_distance: f32 = f32::MAX;
+
+        nearby_preys.clear();
+        indexes.prey.query_radius(
+            Vec2::new(prey_position_size.x, prey_position_size.y),
+            settings.alarm_radius,
+            &mut nearby_preys,
+        );
+
+        for nearby_entity in nearby_preys.iter() {
+            if *nearby_entity == entity {
+                continue;
+            }
+
+            let Some(alarming_position_size) = alarming_preys.get(nearby_entity) else {
+                continue;
+            };
+
+            let (detected, distance) = in_detection_range(
+                &prey_position_size,
+                alarming_position_size,
+                settings.alarm_radius,
+            );
+
+            if detected && distance < closest_alarm_distance {
+                closest_alarm_position = Some(alarming_position_size);
+                closest_alarm_distance = distance;
+            }
+        }
+
+        // Run away from the prey that raised the alarm, as it's the one closest to the danger
+        if let Some(closest_alarm) = closest_alarm_position {
+            if genes.freezes {
+                prey.status = 6; // Frozen
+                continue;
+            }
+            prey.status = 2; // Running
+            avoid(
+                &mut prey_position_size,
+                closest_alarm,
+                genes.speed,
+                creature_rng(&mut shared_rng, &mut own_rng, &settings),
+            );
+        }
+    }
+}
+
+// The most growth steps the environment will catch up on in one frame,
+// so a long freeze doesn't make a frame take forever
+const MAX_ENVIRONMENT_CATCH_UP: u32 = 100;
+
+fn update_environment(
+    mut query: Query<&mut Environment>,
+    settings: Res<Settings>,
+    clock: Res<SimClock>,
+    mut last_update: Local<f64>,
+) {
+    // Grow once for every interval of simulated time that's passed, so the growth doesn't
+    // depend on the frame rate. An interval of 0 grows once every frame instead.
+    let interval = settings.environment_update_interval_secs as f64;
+    let mut steps: u32 = 1;
+
+    if interval > 0.0 {
+        steps = 0;
+        while clock.seconds - *last_update >= interval && steps < MAX_ENVIRONMENT_CATCH_UP {
+            *last_update += interval;
+            steps += 1;
+        }
+
+        // Drop any time we couldn't catch up on
+        if steps == MAX_ENVIRONMENT_CATCH_UP {
+            *last_update = clock.seconds;
+        }
+    }
+
+    for mut environment in query.iter_mut() {
+        for _step in 0..steps {
+            environment.energy_pool =
+                ((environment.energy_pool as f32 * settings.environment_grow_rate).round() as i32)
+                    .min(settings.environment_max);
+        }
+    }
+}
+
+// How much food there is where the prey is standing, from 0 (none) to 1 (as much as there can be).
+// This is the grass patch under it when there's grass, otherwise the shared energy pool
+fn local_food(
+    position_size: &PositionSize,
+    settings: &Settings,
+    grass: &GrassGrid,
+    environment: Option<&Environment>,
+) -> f32 {
+    if settings.grass_enabled {
+        return grass.fullness(position_size.x, position_size.y, settings.grass_max_biomass);
+    }
+
+    match environment {
+        Some(environment) if settings.environment_max > 0 => {
+            environment.energy_pool as f32 / settings.environment_max as f32
+        }
+        _ => 0.0,
+    }
+}
+
+// Prey only breed when there's enough food around them, so booms follow the food supply
+fn enough_food_to_breed(
+    position_size: &PositionSize,
+    settings: &Settings,
+    grass: &GrassGrid,
+    environment: Option<&Environment>,
+) -> bool {
+    settings.breed_resource_threshold <= 0.0
+        || local_food(position_size, settings, grass, environment)
+            >= settings.breed_resource_threshold
+}
+
+fn try_mate_prey(
+    mut seekers: Query<(Entity, &Life, &PositionSize, &mut MatingTarget), With<Prey>>,
+    targets: Query<(Entity, &Life, &PositionSize, &StableId), With<Prey>>,
+    environment_query: Query<&Environment>,
+    grass: Res<GrassGrid>,
+    indexes: Res<SpatialIndexes>,
+    settings: Res<Settings>,
+) {
+    let _span = debug_span!(target: "profiling", "try_mate_prey").entered();
+
+    let mut nearby_targets = Vec::new();
+    let environment = environment_query.iter().next();
+
+    for (seeker_entity, seeker_life, seeker_pos, mut seeker_final_target) in seekers.iter_mut() {
+        if seeker_life.value < prey_mating_threshold(&settings)
+            || !enough_food_to_breed(seeker_pos, &settings, &grass, environment)
+        {
+            continue;
+        }
+
+        nearby_targets.clear();
+        indexes.prey.query_radius(
+            Vec2::new(seeker_pos.x, seeker_pos.y),
+            settings.prey_mating_range,
+            &mut nearby_targets,
+        );
+
+        let mut closest_target_pos = None;
+        let mut closest_target_id = None;
+        let mut min_distance = f32::MAX;
+
+        for (target_entity, target_life, target_pos, target_id) in
+            targets.iter_many(nearby_targets.iter())
+        {
+            if target_entity == seeker_entity
+                || target_life.value < prey_mating_threshold(&settings)
+                || !enough_food_to_breed(target_pos, &settings, &grass, environment)
+            {
+                continue;
+            }
+
+            let (detected, distance) =
+                in_detection_range(seeker_pos, target_pos, settings.prey_mating_range);
+            if detected && distance < min_distance {
+                min_distance = distance;
+                closest_target_pos = Some(target_pos);
+                closest_target_id = Some(target_id.0);
+            }
+        }
+
+        if let Some(target_pos) = closest_target_pos {
+            seeker_final_target.entity = Some(target_pos.clone());
+            seeker_final_target.partner = closest_target_id;
+        }
+    }
+}
+
+// Predators that haven't eaten lately don't breed, however much energy they have
+fn has_recent_kill(last_kill: &LastKill, clock: &SimClock, settings: &Settings) -> bool {
+    if !settings.predator_requires_recent_kill {
+        return true;
+    }
+
+    match last_kill.tick {
+        Some(tick) => clock.ticks - tick <= settings.kill_recency_ticks,
+        None => false,
+    }
+}
+
+fn try_mate_predator(
+    mut seekers: Query<
+        (Entity, &Life, &PositionSize, &LastKill, &mut MatingTarget),
+        With<Predator>,
+    >,
+    targets: Query<(Entity, &Life, &PositionSize, &LastKill, &StableId), With<Predator>>,
+    indexes: Res<SpatialIndexes>,
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+) {
+    let _span = debug_span!(target: "profiling", "try_mate_predator").entered();
+
+    let mut nearby_targets = Vec::new();
+
+    for (seeker_entity, seeker_life, seeker_pos, seeker_last_kill, mut seeker_final_target) in
+        seekers.iter_mut()
+    {
+        if seeker_life.value < mating_threshold(settings.predator_reproduction_energy, &settings)
+            || !has_recent_kill(seeker_last_kill, &clock, &settings)
+        {
+            continue;
+        }
+
+        nearby_targets.clear();
+        indexes.predators.query_radius(
+            Vec2::new(seeker_pos.x, seeker_pos.y),
+            settings.predator_mating_range,
+            &mut nearby_targets,
+        );
+
+        let mut closest_target = None;
+        let mut min_distance = f32::MAX;
+
+        for (target_entity, target_life, target_pos, target_last_kill, target_id) in
+            targets.iter_many(nearby_targets.iter())
+        {
+            if target_entity == seeker_entity
+                || target_life.value
+                    < mating_threshold(settings.predator_reproduction_energy, &settings)
+                || !has_recent_kill(target_last_kill, &clock, &settings)
+            {
+                continue;
+            }
+
+            let (detected, distance) =
+                in_detection_range(seeker_pos, target_pos, settings.predator_mating_range);
+            if detected && distance < min_distance {
+                min_distance = distance;
+                closest_target = Some((target_pos, target_id.0));
+            }
+        }
+
+        if let Some((target_pos, target_id)) = closest_target {
+            seeker_final_target.entity = Some(target_pos.clone());
+            seeker_final_target.partner = Some(target_id);
+        }
+    }
+}
+
+// Faster creatures burn more energy than ones at their species' base speed
+// No random numbers are used without any variance, so they're the same as before it existed
+fn starting_detection_range(base: f32, settings: &Settings, rng: &mut StdRng) -> f32 {
+    if settings.detection_range_variance <= 0.0 {
+        return base;
+    }
+
+    let change =
+        rng.gen_range(-settings.detection_range_variance..=settings.detection_range_variance);
+    (base * (1.0 + change)).max(0.0)
+}
+
+// Only worth plotting when creatures can end up seeing different distances
+fn detection_range_varies(settings: &Settings) -> bool {
+    settings.detection_range_variance > 0.0 || settings.detection_range_mutation > 0.0
+}
+
+// How a gene compares to the species' base value, 1 being the same
+fn gene_ratio(value: f32, base: f32) -> f32 {
+    if base > 0.0 {
+        value / base
+    } else {
+        1.0
+    }
+}
+
+// Faster creatures & ones that see further burn more energy than their species' base
+fn metabolic_cost(
+    energy_loss: i32,
+    genes: &Genes,
+    base_speed: f32,
+    base_detection_range: f32,
+    settings: &Settings,
+    rng: &mut StdRng,
+) -> i32 {
+    let speed_ratio = gene_ratio(genes.speed, base_speed);
+    let detection_ratio = gene_ratio(genes.detection_range, base_detection_range);
+    let scale = (1.0
+        + settings.speed_metabolism_coefficient * (speed_ratio - 1.0)
+        + settings.detection_metabolism_coefficient * (detection_ratio - 1.0))
+        .max(0.0);
+    let cost = energy_loss as f32 * scale;
+
+    // Life is a whole number, so randomly round the cost up or down
+    // so the average cost still matches the scaled one
+    let whole_cost = cost.floor();
+    if rng.gen::<f32>() < cost - whole_cost {
+        return whole_cost as i32 + 1;
+    }
+    whole_cost as i32
+}
+
+// Gives a child its parent's genes, each nudged up or down by up to the mutation amount
+fn inherit_genes(parent: &Genes, settings: &Settings, rng: &mut StdRng) -> Genes {
+    // Each roll is skipped when it can't change anything so turning the gene off doesn't shift
+    // everything else that's random
+    let speed_change = if settings.speed_mutation > 0.0 {
+        rng.gen_range(-settings.speed_mutation..=settings.speed_mutation)
+    } else {
+        0.0
+    };
+
+    let aggression_change = if settings.aggression_enabled && settings.aggression_mutation > 0.0 {
+        rng.gen_range(-settings.aggression_mutation..=settings.aggression_mutation)
+    } else {
+        0.0
+    };
+
+    let detection_range_change = if settings.detection_range_mutation > 0.0 {
+        rng.gen_range(-settings.detection_range_mutation..=settings.detection_range_mutation)
+    } else {
+        0.0
+    };
+
+    Genes {
+        speed: (parent.speed * (1.0 + speed_change)).max(0.0),
+        aggression: (parent.aggression + aggression_change).clamp(0.0, 1.0),
+        detection_range: (parent.detection_range * (1.0 + detection_range_change)).max(0.0),
+        // Children occasionally switch strategy, the roll is skipped when they never do
+        freezes: if settings.freeze_mutation_chance > 0.0
+            && rng.gen_bool(settings.freeze_mutation_chance as f64)
+        {
+            !parent.freezes
+        } else {
+            parent.freezes
+        },
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn drain_life(
+    // This query makes it so that we fetch either a predator or a prey if the option is there
+    mut query: Query<
+        (
+            &mut Mortal,
+            &mut Life,
+            &Genes,
+            Option<&Predator>,
+            Has<Prey>,
+            &mut CreatureRng,
+        ),
+        Or<(With<Predator>, With<Prey>)>,
+    >,
+    settings: Res<Settings>,
+    mut shared_rng: ResMut<SimRng>,
+) {
+    for (mut mortal, mut life, genes, predator, is_prey, mut own_rng) in query.iter_mut() {
+        let rng = creature_rng(&mut shared_rng, &mut own_rng, &settings);
+
+        if let Some(predator) = predator {
+            // Predators lose energy constantly
+            life.value -= metabolic_cost(
+                settings.predator_energy_loss,
+                genes,
+                settings.predator_speed,
+                settings.predator_detection_range,
+                &settings,
+                rng,
+            );
+
+            // Idle predators scavenge a little, so they don't all starve at once when prey are scarce
+            if predator.status == 0 {
+                life.value += settings.predator_idle_energy_gain;
+            }
+
+            // Chasing harder burns more energy, which is what keeps aggression from only going up
+            if settings.aggression_enabled && predator.status == 2 {
+                life.value -= (settings.aggression_energy_cost * genes.aggression).round() as i32;
+            }
+        }
+        if is_prey {
+            // Prey burn energy the same way, and make it back by grazing
+            life.value -= metabolic_cost(
+                settings.prey_energy_loss,
+                genes,
+                settings.prey_speed,
+                settings.prey_detection_range,
+                &settings,
+                rng,
+            );
+        }
+
+        if life.value <= 0 {
+            mortal.dead = true;
+        }
+    }
+}
+
+fn update_age(mut query: Query<&mut Age>) {
+    for mut age in query.iter_mut() {
+        age.ticks += 1;
+    }
+}
+
+fn remove_dead(
+    mut commands: Commands,
+    query: Query<(Entity, &Mortal, Option<&Age>, Has<Predator>)>,
+    mut stats: ResMut<SimStats>,
+) {
+    for (entity, mortal, age, is_predator) in query.iter() {
+        if mortal.dead {
+            commands.entity(entity).despawn_recursive();
+            stats.deaths += 1;
+
+            if let Some(age) = age {
+                let lifespans = if is_predator {
+                    &mut stats.predator_lifespans
+                } else {
+                    &mut stats.prey_lifespans
+                };
+                lifespans.0 += age.ticks as u64;
+                lifespans.1 += 1;
+            }
+        }
+    }
+}
+
+// Creatures that wander too far past a culled edge (every edge of an open world) are treated
+// as having left for good, instead of being kept around forever somewhere off screen
+#[allow(clippy::type_complexity)]
+fn cull_emigrants(
+    mut commands: Commands,
+    query: Query<(Entity, &PositionSize, &Mortal), Or<(With<Prey>, With<Predator>)>>,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+    mut stats: ResMut<SimStats>,
+) {
+    let edges = Edges::new(&settings);
+    if !edges.any(EdgeBehavior::Cull) {
+        return;
+    }
+
+    let half_size = world_half_size(&windows, &settings);
+    let max_x = half_size.x + settings.cull_distance;
+    let max_y = half_size.y + settings.cull_distance;
+
+    for (entity, position_size, mortal) in query.iter() {
+        // Dead creatures are already being cleaned up by remove_dead
+        if mortal.dead {
+            continue;
+        }
+
+        let gone = (edges.left == EdgeBehavior::Cull && position_size.x < -max_x)
+            || (edges.right == EdgeBehavior::Cull && position_size.x > max_x)
+            || (edges.bottom == EdgeBehavior::Cull && position_size.y < -max_y)
+            || (edges.top == EdgeBehavior::Cull && position_size.y > max_y);
+        if gone {
+            commands.entity(entity).despawn_recursive();
+            stats.emigrations += 1;
+        }
+    }
+}
+
+// New creatures occasionally wander in from outside an open world
+fn spawn_immigrants(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut rng: ResMut<SimRng>,
+    mut stats: ResMut<SimStats>,
+    creatures: Creatures,
+) {
+    if !settings.open_world || settings.immigration_rate <= 0.0 {
+        return;
+    }
+
+    let half_size = world_half_size(&windows, &settings);
+    let arrivals = room_under_max_entities(
+        &settings,
+        creatures.iter().count(),
+        arrivals(settings.immigration_rate, time.delta_secs(), &mut rng),
+    );
+    let predator_chance = starting_predator_chance(&settings);
+
+    for _arrival in 0..arrivals {
+        let (x, y) = random_edge_position(&mut rng, half_size);
+
+        if rng.0.gen::<f32>() < predator_chance {
+            spawn_predator(
+                &mut commands,
+                &settings,
+                x,
+                y,
+                settings.predator_life,
+                Genes::starting_predator(&settings, &mut rng.0),
+            );
+        } else {
+            spawn_prey(
+                &mut commands,
+                &settings,
+                x,
+                y,
+                settings.prey_life,
+                Genes::starting_prey(&settings, &mut rng.0),
+            );
+        }
+
+        stats.immigrations += 1;
+    }
+}
+
+// Brings fresh genes into the population at gene_immigration_rate, open world or not. Unlike the
+// open world's immigrants, their genes are picked at random around the species' base values
+#[allow(clippy::too_many_arguments)]
+fn spawn_gene_immigrants(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    clock: Res<SimClock>,
+    mut rng: ResMut<SimRng>,
+    mut stats: ResMut<SimStats>,
+    creatures: Creatures,
+) {
+    if settings.gene_immigration_rate <= 0.0 {
+        return;
+    }
+
+    let half_size = world_half_size(&windows, &settings);
+    let arrivals = room_under_max_entities(
+        &settings,
+        creatures.iter().count(),
+        arrivals(settings.gene_immigration_rate, time.delta_secs(), &mut rng),
+    );
+    let predator_chance = starting_predator_chance(&settings);
+
+    for _arrival in 0..arrivals {
+        let (x, y) = random_edge_position(&mut rng, half_size);
+
+        let (species, genes) = if rng.0.gen::<f32>() < predator_chance {
+            let genes = Genes::random_predator(&settings, &mut rng.0);
+            spawn_predator(
+                &mut commands,
+                &settings,
+                x,
+                y,
+                settings.predator_life,
+                genes.clone(),
+            );
+            ("predator", genes)
+        } else {
+            let genes = Genes::random_prey(&settings, &mut rng.0);
+            spawn_prey(
+                &mut commands,
+                &settings,
+                x,
+                y,
+                settings.prey_life,
+                genes.clone(),
+            );
+            ("prey", genes)
+        };
+
+        info!(
+            "Gene immigrant {} arrived at tick {} ({:.0}, {:.0}) with speed {:.3}, detection range {:.1} & aggression {:.2}",
+            species, clock.ticks, x, y, genes.speed, genes.detection_range, genes.aggression
+        );
+        stats.immigrations += 1;
+    }
+}
+
+// How many of the wanted new creatures fit under max_entities, so nothing that isn't born can take
+// the population past it either
+pub(crate) fn room_under_max_entities(settings: &Settings, creatures: usize, wanted: u32) -> u32 {
+    if settings.max_entities == 0 {
+        return wanted;
+    }
+
+    (wanted as usize).min(settings.max_entities.saturating_sub(creatures)) as u32
+}
+
+// How many arrive this tick when rate arrive per second on average, with the leftover fraction
+// being the chance of one more
+fn arrivals(rate: f32, delta_secs: f32, rng: &mut SimRng) -> u32 {
+    let expected = rate * delta_secs;
+    let mut arrivals = expected.floor() as u32;
+    if rng.0.gen::<f32>() < expected.fract() {
+        arrivals += 1;
+    }
+    arrivals
+}
+
+// Immigrants are the same mix of species as the starting populations
+fn starting_predator_chance(settings: &Settings) -> f32 {
+    let starting_total = settings.predator_population + settings.prey_population;
+    if starting_total > 0 {
+        settings.predator_population as f32 / starting_total as f32
+    } else {
+        0.5
+    }
+}
+
+// A random spot along a random edge of the world
+fn random_edge_position(rng: &mut SimRng, half_size: Vec2) -> (f32, f32) {
+    let (half_width, half_height) = (half_size.x, half_size.y);
+    match rng.0.gen_range(0..4) {
+        0 => (-half_width, rng.0.gen_range(-half_height..=half_height)),
+        1 => (half_width, rng.0.gen_range(-half_height..=half_height)),
+        2 => (rng.0.gen_range(-half_width..=half_width), -half_height),
+        _ => (rng.0.gen_range(-half_width..=half_width), half_height),
+    }
+}
+
+// This is a crutch for demos, not part of the simulation's natural behavior,
+// so every time it steps in it's logged to make that clear
+fn prevent_extinction(
+    mut commands: Commands,
+    prey_query: Query<&Mortal, With<Prey>>,
+    predator_query: Query<&Mortal, With<Predator>>,
+    settings: Res<Settings>,
+    mut rng: ResMut<SimRng>,
+) {
+    if !settings.prevent_extinction {
+        return;
+    }
+
+    // Dead entities are still around until their despawn is applied, so don't count them
+    let prey_count = prey_query.iter().filter(|mortal| !mortal.dead).count() as i32;
+    let predator_count = predator_query.iter().filter(|mortal| !mortal.dead).count() as i32;
+
+    if prey_count < settings.min_prey {
+        let missing = settings.min_prey - prey_count;
+        info!(
+            "Extinction floor spawned {} prey to keep them at the minimum of {}",
+            missing, settings.min_prey
+        );
+
+        for _i in 0..missing {
+            let (random_x, random_y) = random_position(&mut rng, &settings);
+            spawn_prey(
+                &mut commands,
+                &settings,
+                random_x,
+                random_y,
+                settings.prey_life,
+                Genes::starting_prey(&settings, &mut rng.0),
+            );
+        }
+    }
+
+    if predator_count < settings.min_predator {
+        let missing = settings.min_predator - predator_count;
+        info!(
+            "Extinction floor spawned {} predators to keep them at the minimum of {}",
+            missing, settings.min_predator
+        );
+
+        for _i in 0..missing {
+            let (random_x, random_y) = random_position(&mut rng, &settings);
+            spawn_predator(
+                &mut commands,
+                &settings,
+                random_x,
+                random_y,
+                settings.predator_life,
+                Genes::starting_predator(&settings, &mut rng.0),
+            );
+        }
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn handle_mating(
+    mut query: Query<
+        (
+            Entity,
+            &PositionSize,
+            &mut MatingTarget,
+            &mut Life,
+            &Genes,
+            Option<&Predator>,
+            Option<&Prey>,
+            &mut CreatureRng,
+            &StableId,
+            &Parents,
+            Option<&LastKill>,
+        ),
+        Or<(With<Predator>, With<Prey>)>,
+    >,
+    settings: Res<Settings>,
+    mut stats: ResMut<SimStats>,
+    mut shared_rng: ResMut<SimRng>,
+    mut commands: Commands,
+    environment_query: Query<&Environment>,
+    grass: Res<GrassGrid>,
+    mut at_max_entities: Local<bool>,
+    mut first_births: Local<(bool, bool)>,
+    clock: Res<SimClock>,
+) {
+    let _span = debug_span!(target: "profiling", "handle_mating").entered();
+
+    let environment = environment_query.iter().next();
+
+    // Births are collected first and spawned afterwards in a fixed order, as the order the query
+    // is iterated in isn't guaranteed and it decides who uses the random numbers first
+    let mut births: Vec<(Entity, u16, f32, f32, i32, Genes, Parents)> = Vec::new();
+
+    for (
+        entity,
+        position_size,
+        mating_target,
+        life,
+        genes,
+        predator,
+        prey,
+        _,
+        stable_id,
+        parents,
+        last_kill,
+    ) in query.iter()
+    {
+        // Check what kind of entity we're dealing with
+        let mut entity_type: u16 = 0; // 0 is prey, 1 is predator
+        let required_energy: i32; // We default to prey and overwrite if neccesary
+        let full_life: i32;
+        let entity_status: u16;
+
+        if let Some(predator) = predator {
+            entity_type = 1;
+            required_energy = settings.predator_reproduction_energy;
+            full_life = settings.predator_life;
+            entity_status = predator.status;
+        } else {
+            required_energy = settings.prey_reproduction_energy;
+            full_life = settings.prey_life;
+            entity_status = prey.unwrap().status;
+        }
+
+        // Prey can't breed while they're running away or frozen. Status 2 also means hunting for
+        // predators, but they're deliberately allowed to mate then, as update_predators puts
+        // finding a mate ahead of hunting, so a predator is only still hunting here if it found
+        // its mate this frame
+        let fleeing_prey = entity_type == 0 && (entity_status == 2 || entity_status == 6);
+
+        // Both prey and predators need enough energy to mate, and to have some left over after
+        let too_little_energy = if entity_type == 0 {
+            life.value < prey_mating_threshold(&settings)
+        } else {
+            life.value < mating_threshold(required_energy, &settings)
+        };
+
+        if fleeing_prey || too_little_energy {
+            continue;
+        }
+
+        // The kill that let a predator pick a mate could have gone stale while it was reaching them
+        if last_kill.is_some_and(|last_kill| !has_recent_kill(last_kill, &clock, &settings)) {
+            continue;
+        }
+
+        // The food could have run out since the prey found its partner
+        if entity_type == 0 && !enough_food_to_breed(position_size, &settings, &grass, environment)
+        {
+            continue;
+        }
+
+        // We check to see if there is even a mate
+        if let Some(target) = &mating_target.entity {
+            // Ensure we are actually colliding with our target
+            if !is_colliding(position_size, target, settings.collision_shape) {
+                continue;
+            }
+
+            // We give breeding priority to the mate with a higher id to prevent twins
+            // by only having one partner run the reproduction code. If this entity skips the code,
+            // their partner either had or is going to have the child entity
+            if let Some(partner) = mating_target.partner {
+                if stable_id.0 < partner {
+                    continue;
+                }
+            }
+
+            let position_x = (position_size.x + target.x) / 2.0;
+            let position_y = (position_size.y + target.y) / 2.0;
+
+            // When conserving energy the child only gets the energy the parent spends on mating
+            // (up to a full life) instead of a full life made from nothing
+            let child_life = if settings.conserve_reproduction_energy {
+                required_energy.min(full_life)
+            } else {
+                full_life
+            };
+
+            births.push((
+                entity,
+                entity_type,
+                position_x,
+                position_y,
+                child_life,
+                genes.clone(),
+                Parents::child_of(*stable_id, parents, mating_target.partner.map(StableId)),
+            ));
+        }
+    }
+
+    // Sort by the parent so the same births always happen in the same order
+    births.sort_by_key(|(parent, _, _, _, _, _, _)| *parent);
+
+    // A safety valve against runaway growth rather than part of the ecology, so it's logged
+    // whenever it starts & stops holding back births to not be mistaken for carrying capacity
+    if settings.max_entities > 0 {
+        let room = settings.max_entities.saturating_sub(query.iter().count());
+        let capped = births.len() > room;
+
+        if capped && !*at_max_entities {
+            info!(
+                "Reached max_entities ({}), holding back births until there's room",
+                settings.max_entities
+            );
+        } else if !capped && *at_max_entities && !births.is_empty() {
+            info!(
+                "Below max_entities ({}) again, births are back to normal",
+                settings.max_entities
+            );
+        }
+        if capped || !births.is_empty() {
+            *at_max_entities = capped;
+        }
+
+        // Parents that miss out keep their energy & partner to try again later
+        births.truncate(room);
+    }
+
+    for (parent, entity_type, position_x, position_y, child_life, parent_genes, child_parents) in
+        births
+    {
+        let first_birth = if entity_type == 1 {
+            &mut first_births.1
+        } else {
+            &mut first_births.0
+        };
+        if !*first_birth {
+            info!(
+                "First {} born at tick {}",
+                if entity_type == 1 { "predator" } else { "prey" },
+                clock.ticks
+            );
+            *first_birth = true;
+        }
+
+        let Ok((_, _, mut mating_target, mut life, _, _, _, mut own_rng, _, _, _)) =
+            query.get_mut(parent)
+        else {
+            continue;
+        };
+
+        // Reduce the energy of the parent
+        life.value -= if entity_type == 1 {
+            settings.predator_reproduction_energy
+        } else {
+            settings.prey_reproduction_energy
+        };
+        mating_target.entity = None;
+        mating_target.partner = None;
+
+        // Mutations come from the parent's random numbers
+        let child_genes = inherit_genes(
+            &parent_genes,
+            &settings,
+            creature_rng(&mut shared_rng, &mut own_rng, &settings),
+        );
+
+        let child = match entity_type {
+            0 => spawn_prey(
+                &mut commands,
+                &settings,
+                position_x,
+                position_y,
+                child_life,
+                child_genes,
+            ),
+            1 => spawn_predator(
+                &mut commands,
+                &settings,
+                position_x,
+                position_y,
+                child_life,
+                child_genes,
+            ),
+            _ => continue, // Handle the impossible edge case where it isn't 0 or 1
+        };
+        commands.entity(child).insert(child_parents);
+        stats.births += 1;
+    }
+}
+
+// Who eats whom comes from the interaction matrix, so this works for any number of species
+#[allow(clippy::type_complexity)]
+pub fn handle_hostile_collisions(
+    mut creatures: Query<(
+        Entity,
+        &PositionSize,
+        (&SpeciesId, &Layers),
+        &mut Mortal,
+        &mut Life,
+        Option<&Prey>,
+        Option<&mut LastKill>,
+        Option<&mut Predator>,
+    )>,
+    mut matrix: ResMut<InteractionMatrix>,
+    indexes: Res<SpatialIndexes>,
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+    mut rng: ResMut<SimRng>,
+    mut stats: ResMut<SimStats>,
+) {
+    let _span = debug_span!(target: "profiling", "handle_hostile_collisions").entered();
+
+    matrix.refresh(&settings);
+
+    // Weak predators with enough prey around them get fought off by the group instead of eating
+    let mut mobbable: HashSet<Entity> = HashSet::new();
+    if settings.prey_mobbing {
+        let mut nearby_preys = Vec::new();
+        for (entity, position_size, _, _, life, _, _, predator) in creatures.iter() {
+            if predator.is_none() || life.value >= settings.prey_mobbing_energy_threshold {
+                continue;
+            }
+
+            nearby_preys.clear();
+            indexes.prey.query_radius(
+                Vec2::new(position_size.x, position_size.y),
+                settings.prey_mobbing_range,
+                &mut nearby_preys,
+            );
+            if nearby_preys.len() >= settings.prey_mobbing_group_size as usize {
+                mobbable.insert(entity);
+            }
+        }
+    }
+    let mut mobbed: HashSet<Entity> = HashSet::new();
+
+    // Everyone's state at the start, so meals are decided before anyone's energy changes
+    let snapshot: Vec<(Entity, PositionSize, (SpeciesId, Layers), i32, bool, bool)> = creatures
+        .iter()
+        .map(
+            |(entity, position_size, (species, layers), _, life, prey, _, predator)| {
+                // Predators can't reach prey hiding in a burrow
+                let hidden = prey.map(|prey| prey.status == 4).unwrap_or(false);
+                // Or attack anything while they're still recovering from a miss
+                let stunned = predator
+                    .map(|predator| predator.stunned_ticks_left > 0)
+                    .unwrap_or(false);
+                (
+                    entity,
+                    position_size.clone(),
+                    (*species, *layers),
+                    life.value,
+                    hidden,
+                    stunned,
+                )
+            },
+        )
+        .collect();
+
+    // Where everyone is in the snapshot, so the eaters found through the spatial indexes can be
+    // tried in the same order as the query, which keeps which one gets there first the same
+    let order: HashMap<Entity, usize> = snapshot
+        .iter()
+        .enumerate()
+        .map(|(index, (entity, ..))| (*entity, index))
+        .collect();
+    // Nothing further away than this from a creature's corner can be touching it, whichever
+    // collision shape is used
+    let largest = snapshot
+        .iter()
+        .map(|(_, position_size, ..)| position_size.width.max(position_size.height))
+        .fold(0.0, f32::max);
+
+    let mut meals: Vec<(Entity, i32, i32)> = Vec::new();
+    let mut misses: HashSet<Entity> = HashSet::new();
+    let mut kills = 0;
+    let mut candidates: Vec<usize> = Vec::new();
+    let mut nearby = Vec::new();
+
+    for (eaten, eaten_position_size, eaten_species, eaten_life, hidden, _) in snapshot.iter() {
+        if *hidden {
+            continue;
+        }
+        if let Ok((_, _, _, mortal, _, _, _, _)) = creatures.get(*eaten) {
+            if mortal.dead {
+                continue;
+            }
+        }
+
+        // Only the species that eat this one are looked up, from around where it is
+        let reach = (eaten_position_size.width.max(eaten_position_size.height) + largest)
+            * std::f32::consts::SQRT_2;
+        let center = Vec2::new(eaten_position_size.x, eaten_position_size.y);
+        candidates.clear();
+        for interaction in matrix.interactions.iter() {
+            if interaction.eaten != eaten_species.0 {
+                continue;
+            }
+            nearby.clear();
+            match interaction.eater {
+                PREY_SPECIES => indexes.prey.query_radius(center, reach, &mut nearby),
+                PREDATOR_SPECIES => indexes.predators.query_radius(center, reach, &mut nearby),
+                _ => {}
+            }
+            candidates.extend(nearby.iter().filter_map(|entity| order.get(entity)));
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        // Every predator that reaches it and doesn't miss, with how far away it is
+        let mut eaters: Vec<(Entity, f32, f32)> = Vec::new();
+        for (eater, eater_position_size, eater_species, _, _, stunned) in
+            candidates.iter().map(|index| &snapshot[*index])
+        {
+            if eater == eaten || *stunned || misses.contains(eater) {
+                continue;
+            }
+            // Something eaten earlier in the tick can't go on to eat anything else
+            if let Ok((_, _, _, mortal, _, _, _, _)) = creatures.get(*eater) {
+                if mortal.dead {
+                    continue;
+                }
+            }
+
+            let (eater_species, eater_layers) = eater_species;
+            let (eaten_species, eaten_layers) = eaten_species;
+            if !Layers::collide(
+                (eater_layers, *eater_species),
+                (eaten_layers, *eaten_species),
+            ) {
+                continue;
+            }
+
+            if let Some(efficiency) = matrix.efficiency(*eater_species, *eaten_species) {
+                if is_colliding(
+                    eaten_position_size,
+                    eater_position_size,
+                    settings.collision_shape,
+                ) {
+                    // The prey fight back instead of being eaten, which doesn't need a roll either
+                    if mobbable.contains(eater) {
+                        mobbed.insert(*eater);
+                        continue;
+                    }
+
+                    // Near misses let the prey get away, and leave the attacker stunned for a bit.
+                    // The roll is skipped for certain kills so the random numbers are unchanged
+                    if settings.predation_success_chance < 1.0
+                        && !rng
+                            .0
+                            .gen_bool(settings.predation_success_chance.max(0.0) as f64)
+                    {
+                        misses.insert(*eater);
+                        continue;
+                    }
+
+                    let distance = Vec2::new(eater_position_size.x, eater_position_size.y)
+                        .distance(Vec2::new(eaten_position_size.x, eaten_position_size.y));
+                    eaters.push((*eater, distance, efficiency));
+                    if settings.kill_sharing == KillSharing::First {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if eaters.is_empty() {
+            continue;
+        }
+        // Sorting is stable, so predators the same distance away stay in the query's order
+        if settings.kill_sharing != KillSharing::First {
+            eaters.sort_by(|a, b| a.1.total_cmp(&b.1));
+        }
+        if settings.kill_sharing != KillSharing::Split {
+            eaters.truncate(1);
+        }
+
+        // Only part of the eaten creature's energy makes it to the eater,
+        // so a starving prey is a much worse meal than a well fed one
+        // When it's split the flat hunting reward is shared out the same way, so a kill is worth
+        // the same in total however many predators were in on it
+        let portions = eaters.len() as i32;
+        let eaten_life = (*eaten_life).max(0);
+        let hunt_reward = settings.predator_hunt_energy_gain;
+        for (index, (eater, _, efficiency)) in eaters.iter().enumerate() {
+            let (life_portion, reward_portion) = if index == 0 {
+                (
+                    eaten_life / portions + eaten_life % portions,
+                    hunt_reward / portions + hunt_reward % portions,
+                )
+            } else {
+                (eaten_life / portions, hunt_reward / portions)
+            };
+            meals.push((
+                *eater,
+                (efficiency * life_portion as f32) as i32,
+                reward_portion,
+            ));
+        }
+        if let Ok((_, _, _, mut mortal, _, _, _, _)) = creatures.get_mut(*eaten) {
+            mortal.dead = true;
+        }
+        kills += 1;
+    }
+
+    for predator in mobbed {
+        if let Ok((_, _, _, mut mortal, mut life, _, _, _)) = creatures.get_mut(predator) {
+            life.value -= settings.prey_mobbing_damage;
+            if life.value <= 0 {
+                mortal.dead = true;
+            }
+        }
+    }
+
+    for eater in misses {
+        if let Ok((_, _, _, _, _, _, _, Some(mut predator))) = creatures.get_mut(eater) {
+            predator.stunned_ticks_left = settings.predation_miss_stun_ticks;
+        }
+    }
+
+    stats.kills += kills;
+    for (eater, energy, reward) in meals {
+        if let Ok((_, _, _, _, mut life, _, last_kill, predator)) = creatures.get_mut(eater) {
+            life.value += energy;
+            // Predators also get a flat reward for every kill on top of their share of the meal,
+            // however little energy the prey had left
+            if predator.is_some() {
+                life.value += reward;
+            }
+            if let Some(mut last_kill) = last_kill {
+                last_kill.tick = Some(clock.ticks);
+            }
+        }
+    }
+}
+
+fn update_ui_text(
+    mut text_query: Query<&mut Text>,
+    environment_query: Query<&Environment>,
+    grass: Res<GrassGrid>,
+    settings: Res<Settings>,
+    diagnostics: Res<DiagnosticsStore>,
+    ledger: Res<EnergyLedger>,
+) {
+    for mut text in text_query.iter_mut() {
+        let environment = environment_query.single();
+        let fps = diagnostics
+            .get(&FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps_diagnostic| fps_diagnostic.average())
+            .unwrap_or(0.0);
+
+        let food = if settings.grass_enabled {
+            format!("Grass Biomass {:.0}", grass.total_biomass())
+        } else {
+            format!("Environment Energy Pool {}", environment.energy_pool)
+        };
+        *text = Text::from(format!(
+            "FPS {:.2}\n{}\nTotal Energy {:.0} ({:+.0} per tick)",
+            fps,
+            food,
+            ledger.total,
+            ledger.delta.unwrap_or(0.0)
+        ));
+    }
+}
+
+fn advance_sim_clock(time: Res<Time>, mut clock: ResMut<SimClock>) {
+    clock.ticks += 1;
+    clock.seconds += time.delta_secs_f64();
+}
+
+fn update_population_history(
+    clock: Res<SimClock>,
+    prey_query: Query<&Genes, With<Prey>>,
+    predator_query: Query<&Genes, With<Predator>>,
+    settings: Res<Settings>,
+    mut history: ResMut<PopulationHistory>,
+) {
+    let prey_count = prey_query.iter().count() as f64;
+    let predator_count = predator_query.iter().count() as f64;
+
+    let time_elapsed = clock.seconds;
+
+    history.prey_population.push([time_elapsed, prey_count]);
+    history
+        .predator_population
+        .push([time_elapsed, predator_count]);
+
+    if settings.aggression_enabled && predator_count > 0.0 {
+        let mean_aggression = predator_query
+            .iter()
+            .map(|genes| genes.aggression as f64)
+            .sum::<f64>()
+            / predator_count;
+        history
+            .predator_aggression
+            .push([time_elapsed, mean_aggression]);
+    }
+
+    if detection_range_varies(&settings) {
+        let mean_range = |genes: &mut dyn Iterator<Item = &Genes>, count: f64| {
+            genes.map(|genes| genes.detection_range as f64).sum::<f64>() / count
+        };
+        if prey_count > 0.0 {
+            let mean = mean_range(&mut prey_query.iter(), prey_count);
+            history.prey_detection_range.push([time_elapsed, mean]);
+        }
+        if predator_count > 0.0 {
+            let mean = mean_range(&mut predator_query.iter(), predator_count);
+            history.predator_detection_range.push([time_elapsed, mean]);
+        }
+    }
+}
+
+fn plot_ui(
+    mut contexts: EguiContexts,
+    history: Res<PopulationHistory>,
+    settings: Res<Settings>,
+    annotations: Res<PlotAnnotations>,
+    mut decimation: Local<(PlotDecimation, PlotDecimation)>,
+) {
+    egui::Window::new("Populations & Environment Energy Over Time")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let plot = Plot::new("entity_population_plot")
+                .legend(Legend::default())
+                .x_axis_label("Time (s)")
+                .y_axis_label("Amount")
+                .label_formatter(|name, value| {
+                    let display_name = &name.replace(" Population", "");
+                    if !display_name.is_empty() {
+                        format!(
+                            "{} Amount: {}\nTime: {}:{:04.1}s",
+                            display_name,
+                            value.y,
+                            (value.x / 60.0).floor(),
+                            value.x % 60.0
+                        )
+                    } else {
+                        "".to_owned()
+                    }
+                })
+                .show(ui, |plot_ui| {
+                    // While the plot is fitting itself to the lines every point is needed, otherwise
+                    // the bounds from the last frame are close enough to pick what to draw
+                    let bounds = plot_ui.plot_bounds();
+                    let visible_x = if plot_ui.auto_bounds().x {
+                        [f64::NEG_INFINITY, f64::INFINITY]
+                    } else {
+                        [bounds.min()[0], bounds.max()[0]]
+                    };
+                    let max_points = settings.plot_max_points as usize;
+                    let (prey_decimation, predator_decimation) = &mut *decimation;
+
+                    let prey_points =
+                        prey_decimation.points(&history.prey_population, visible_x, max_points);
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(prey_points.to_vec()))
+                            .name("Prey Population")
+                            .color(to_color32(settings.prey_color)),
+                    );
+                    let predator_points = predator_decimation.points(
+                        &history.predator_population,
+                        visible_x,
+                        max_points,
+                    );
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(predator_points.to_vec()))
+                            .name("Predator Population")
+                            .color(to_color32(settings.predator_color)),
+                    );
+
+                    annotations.draw(plot_ui)
+                });
+
+            // Hovering over the line of an event describes it
+            let hovered = plot.inner;
+            if !hovered.is_empty() {
+                plot.response.on_hover_ui_at_pointer(|ui| {
+                    for description in hovered.iter() {
+                        ui.label(description);
+                    }
+                });
+            }
+        });
+}
+
+// Shows whether selection favors keener senses, despite what they cost
+fn detection_range_plot_ui(
+    mut contexts: EguiContexts,
+    history: Res<PopulationHistory>,
+    settings: Res<Settings>,
+) {
+    if !detection_range_varies(&settings) {
+        return;
+    }
+
+    egui::Window::new("Mean Detection Range Over Time")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            Plot::new("detection_range_plot")
+                .legend(Legend::default())
+                .x_axis_label("Time (s)")
+                .y_axis_label("Detection Range")
+                .include_y(0.0)
+                .show(ui, |plot_ui| {
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(history.prey_detection_range.clone()))
+                            .name("Prey")
+                            .color(to_color32(settings.prey_color)),
+                    );
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(history.predator_detection_range.clone()))
+                            .name("Predators")
+                            .color(to_color32(settings.predator_color)),
+                    );
+                });
+        });
+}
+
+// Shows whether selection is pushing the predators' aggression up or down over time
+fn aggression_plot_ui(
+    mut contexts: EguiContexts,
+    history: Res<PopulationHistory>,
+    settings: Res<Settings>,
+) {
+    if !settings.aggression_enabled {
+        return;
+    }
+
+    egui::Window::new("Mean Predator Aggression Over Time")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            Plot::new("predator_aggression_plot")
+                .x_axis_label("Time (s)")
+                .y_axis_label("Aggression")
+                .include_y(0.0)
+                .include_y(1.0)
+                .show(ui, |plot_ui| {
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(history.predator_aggression.clone()))
+                            .name("Mean Aggression")
+                            .color(to_color32(settings.predator_color)),
+                    );
+                });
+        });
+}
+
+fn control_panel_ui(
+    mut contexts: EguiContexts,
+    mut commands: Commands,
+    mut prey_query: Query<(Entity, &mut Mortal), With<Prey>>,
+    creatures: Creatures,
+    settings: Res<Settings>,
+    mut rng: ResMut<SimRng>,
+    mut settings_status: Local<String>,
+) {
+    // Added creatures can't take the population past max_entities any more than births can
+    let room = room_under_max_entities(&settings, creatures.iter().count(), 10);
+
+    egui::Window::new("Controls")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("+10 prey").clicked() {
+                for _i in 0..room {
+                    let (random_x, random_y) = random_position(&mut rng, &settings);
+                    spawn_prey(
+                        &mut commands,
+                        &settings,
+                        random_x,
+                        random_y,
+                        settings.prey_life,
+                        Genes::starting_prey(&settings, &mut rng.0),
+                    );
+                }
+            }
+
+            if ui.button("+10 predators").clicked() {
+                for _i in 0..room {
+                    let (random_x, random_y) = random_position(&mut rng, &settings);
+                    spawn_predator(
+                        &mut commands,
+                        &settings,
+                        random_x,
+                        random_y,
+                        settings.predator_life,
+                        Genes::starting_predator(&settings, &mut rng.0),
+                    );
+                }
+            }
+
+            if ui.button("Kill 10% of prey").clicked() {
+                // Sort the prey first so the same seed always picks the same victims
+                let mut living_prey: Vec<Entity> = prey_query
+                    .iter()
+                    .filter(|(_, mortal)| !mortal.dead)
+                    .map(|(entity, _)| entity)
+                    .collect();
+                living_prey.sort();
+
+                let victim_count = (living_prey.len() as f32 * 0.1).round() as usize;
+                let victims: Vec<Entity> = living_prey
+                    .choose_multiple(&mut rng.0, victim_count)
+                    .copied()
+                    .collect();
+
+                for victim in victims {
+                    if let Ok((_, mut mortal)) = prey_query.get_mut(victim) {
+                        mortal.dead = true;
+                    }
+                }
+            }
+
+            ui.separator();
+
+            if ui.button("Save settings").clicked() {
+                *settings_status = match save_settings(&settings) {
+                    Ok(()) => "Saved settings".to_string(),
+                    Err(error) => error,
+                };
+            }
+
+            // Settings only used when the simulation starts (like the populations)
+            // won't change anything until it's restarted
+            if ui.button("Reload from disk").clicked() {
+                *settings_status = match reload_settings() {
+                    Ok(mut reloaded) => {
+                        // Keep going with the same random numbers
+                        reloaded.seed = settings.seed;
+                        commands.insert_resource(reloaded);
+                        "Reloaded settings".to_string()
+                    }
+                    Err(error) => error,
+                };
+            }
+
+            if !settings_status.is_empty() {
+                ui.label(settings_status.as_str());
+            }
+        });
+}
+
+// msaa_samples is checked to be one of these when the settings are read
+fn msaa(settings: &Settings) -> Msaa {
+    match settings.msaa_samples {
+        1 => Msaa::Off,
+        2 => Msaa::Sample2,
+        8 => Msaa::Sample8,
+        _ => Msaa::Sample4,
+    }
+}
+
+fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>, settings: Res<Settings>) {
+    // The simulation works in world units, so the camera is what turns them into pixels
+    commands.spawn((
+        Camera2d,
+        OrthographicProjection {
+            scale: 1.0 / settings.pixels_per_unit,
+            ..OrthographicProjection::default_2d()
+        },
+        msaa(&settings),
+    ));
+    commands.insert_resource(ClearColor(settings.background_color));
+
+    // Import font and use it to create ui text elements.
+    let text_font: Handle<Font> = asset_server.load("fonts/SpaceMono-Regular.ttf");
+
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            // This font is loaded and will be used instead of the default font.
+            font: text_font.clone(),
+            font_size: 15.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Right),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        },
+    ));
+}
+
+fn setup(mut commands: Commands, settings: Res<Settings>, mut rng: ResMut<SimRng>) {
+    commands.spawn(Environment {
+        energy_pool: (settings.environment_max as f32 * settings.initial_environment_fraction)
+            .round() as i32,
+    });
+
+    // The settings are checked when they're read, so this never fails
+    for region in parse_regions(&settings.cover_regions).unwrap_or_default() {
+        commands.spawn((
+            Cover,
+            Sprite {
+                color: settings.environment_color.with_alpha(0.3),
+                custom_size: Some(Vec2::new(region.width, region.height)),
+                ..default()
+            },
+            // Sit behind the predators and prey
+            Transform::from_xyz(region.x, region.y, -1.0),
+            region,
+        ));
+    }
+
+    for region in parse_regions(&settings.burrow_regions).unwrap_or_default() {
+        commands.spawn((
+            Burrow,
+            Sprite {
+                color: Color::srgba(0.45, 0.3, 0.15, 0.7),
+                custom_size: Some(Vec2::new(region.width, region.height)),
+                ..default()
+            },
+            Transform::from_xyz(region.x, region.y, -1.0),
+            region,
+        ));
+    }
+
+    // A layout file replaces the random starting populations completely
+    if !settings.layout_file.is_empty() {
+        for entry in read_layout(&settings) {
+            match entry.species {
+                Species::Prey => spawn_prey(
+                    &mut commands,
+                    &settings,
+                    entry.x,
+                    entry.y,
+                    entry.energy,
+                    Genes::starting_prey(&settings, &mut rng.0),
+                ),
+                Species::Predator => spawn_predator(
+                    &mut commands,
+                    &settings,
+                    entry.x,
+                    entry.y,
+                    entry.energy,
+                    Genes::starting_predator(&settings, &mut rng.0),
+                ),
+            };
+        }
+        return;
+    }
+
+    // Spawn all the initial predators into the simulation
+    for _i in 1..=settings.predator_population {
+        let (random_x, random_y) = random_position(&mut rng, &settings);
+
+        spawn_predator(
+            &mut commands,
+            &settings,
+            random_x,
+            random_y,
+            settings.predator_life,
+            Genes::starting_predator(&settings, &mut rng.0),
+        );
+    }
+
+    // Spawn all the initial prey into the simulation
+    for _i in 1..=settings.prey_population {
+        let (random_x, random_y) = random_position(&mut rng, &settings);
+
+        spawn_prey(
+            &mut commands,
+            &settings,
+            random_x,
+            random_y,
+            settings.prey_life,
+            Genes::starting_prey(&settings, &mut rng.0),
+        );
+    }
+}
+
+// Regions are written as "x,y,width,height" with a semicolon between each region
+fn parse_regions(value: &str) -> Result<Vec<PositionSize>, String> {
+    value
+        .split(';')
+        .map(|region| region.trim())
+        .filter(|region| !region.is_empty())
+        .map(parse_region)
+        .collect()
+}
+
+fn parse_region(region: &str) -> Result<PositionSize, String> {
+    let numbers = region
+        .split(',')
+        .map(|number| {
+            number
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|number| number.is_finite())
+        })
+        .collect::<Option<Vec<f32>>>()
+        .ok_or(format!("\"{}\" has to be four numbers", region))?;
+    let [x, y, width, height] = numbers[..] else {
+        return Err(format!("\"{}\" has to be four numbers", region));
+    };
+    if width <= 0.0 || height <= 0.0 {
+        return Err(format!(
+            "\"{}\" has to have a width and height above 0",
+            region
+        ));
+    }
+
+    Ok(PositionSize {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+// Stops the program if the settings can't be read, for when there's nothing sensible to fall back on
+pub fn read_settings() -> Settings {
+    try_read_settings().unwrap_or_else(|error| panic!("Couldn't read the settings: {}", error))
+}
+
+// Every problem with the settings is returned as an error naming the setting, so a typo in
+// Settings.toml can be reported without crashing when there's something to fall back on
+pub fn try_read_settings() -> Result<Settings, String> {
+    try_read_settings_from("Settings.toml")
+}
+
+// The same as try_read_settings, from another file laid out like Settings.toml
+pub fn try_read_settings_from(path: &str) -> Result<Settings, String> {
+    let mut settings = read_unscaled_settings(path)?;
+    apply_difficulty(&mut settings);
+    Ok(settings)
+}
+
+// The post_mate_reserve realistic_reproduction uses when it isn't set, a fifth of a default life
+const REALISTIC_POST_MATE_RESERVE: i32 = 1000;
+
+// The settings that the difficulty scales, from the prey's point of view. A harder difficulty
+// makes predators faster & further sighted, and makes prey lose energy faster
+pub const DIFFICULTY_FIELDS: [&str; 3] = [
+    "predator_speed",
+    "predator_detection_range",
+    "prey_energy_loss",
+];
+
+// The presets are the same as writing their multiplier, anything else has to be a number above 0
+pub fn difficulty_multiplier(difficulty: &str) -> Result<f32, String> {
+    let multiplier = match difficulty.to_lowercase().as_str() {
+        "easy" => 0.75,
+        "normal" => 1.0,
+        "hard" => 1.5,
+        other => other.parse::<f32>().map_err(|_| {
+            format!(
+                "difficulty has to be easy, normal, hard or a number, it's \"{}\"",
+                difficulty
+            )
+        })?,
+    };
+    if multiplier <= 0.0 || !multiplier.is_finite() {
+        return Err(format!("difficulty has to be above 0, it's {}", multiplier));
+    }
+    Ok(multiplier)
+}
+
+// Energy loss is a whole number, so it's rounded and never goes below 1
+fn apply_difficulty(settings: &mut Settings) {
+    // Checked when the settings are read
+    let multiplier = difficulty_multiplier(&settings.difficulty).unwrap_or(1.0);
+
+    settings.predator_speed *= multiplier;
+    settings.predator_detection_range *= multiplier;
+    settings.prey_energy_loss = ((settings.prey_energy_loss as f32 * multiplier).round() as i32)
+        .max(settings.prey_energy_loss.min(1));
+}
+
+fn read_unscaled_settings(path: &str) -> Result<Settings, String> {
+    let settings = Config::builder()
+        .add_source(config::File::new(path, config::FileFormat::Toml)) // Read config values from file
+        .add_source(config::Environment::with_prefix("APP")) // Also read config values from environment variables
+        .build()
+        .and_then(|config| config.try_deserialize::<HashMap<String, String>>())
+        .map_err(|error| error.to_string())?;
+
+    // A seed of 0 means we pick a random one, so every run is different unless asked otherwise
+    let mut seed = parse_setting::<u64>(&settings, "seed")?;
+    if seed == 0 {
+        seed = rand::thread_rng().gen_range(1..u64::MAX);
+    }
+
+    let population_mode = parse_setting::<PopulationMode>(&settings, "population_mode")?;
+    let total_population = parse_setting::<i32>(&settings, "total_population")?;
+    let predator_fraction = parse_setting::<f32>(&settings, "predator_fraction")?;
+
+    let (predator_population, prey_population) = match population_mode {
+        PopulationMode::Absolute => (
+            parse_setting::<i32>(&settings, "predator_population")?,
+            parse_setting::<i32>(&settings, "prey_population")?,
+        ),
+        PopulationMode::Fraction => {
+            if total_population <= 0 {
+                return Err(format!(
+                    "total_population has to be above 0, it's {}",
+                    total_population
+                ));
+            }
+            if !(0.0..=1.0).contains(&predator_fraction) {
+                return Err(format!(
+                    "predator_fraction has to be between 0 and 1, it's {}",
+                    predator_fraction
+                ));
+            }
+
+            let predators = (total_population as f32 * predator_fraction).round() as i32;
+            (predators, total_population - predators)
+        }
+    };
+
+    let reproduction_energy_mode =
+        parse_setting::<ReproductionEnergyMode>(&settings, "reproduction_energy_mode")?;
+    let prey_repro_fraction = parse_setting::<f32>(&settings, "prey_repro_fraction")?;
+    let predator_repro_fraction = parse_setting::<f32>(&settings, "predator_repro_fraction")?;
+
+    let (prey_reproduction_energy, predator_reproduction_energy) = match reproduction_energy_mode {
+        ReproductionEnergyMode::Absolute => (
+            parse_setting::<i32>(&settings, "prey_reproduction_energy")?,
+            parse_setting::<i32>(&settings, "predator_reproduction_energy")?,
+        ),
+        ReproductionEnergyMode::Fraction => {
+            for (key, fraction) in [
+                ("prey_repro_fraction", prey_repro_fraction),
+                ("predator_repro_fraction", predator_repro_fraction),
+            ] {
+                if fraction <= 0.0 || fraction > 1.0 {
+                    return Err(format!(
+                        "{} has to be above 0 and at most 1, it's {}",
+                        key, fraction
+                    ));
+                }
+            }
+
+            // Worked out from the full life so they stay in step when the life is changed
+            let prey_life = parse_setting::<i32>(&settings, "prey_life")?;
+            let predator_life = parse_setting::<i32>(&settings, "predator_life")?;
+            (
+                (prey_life as f32 * prey_repro_fraction).round() as i32,
+                (predator_life as f32 * predator_repro_fraction).round() as i32,
+            )
+        }
+    };
+
+    let predation_efficiency = parse_setting::<f32>(&settings, "predation_efficiency")?;
+    if !(0.0..=1.0).contains(&predation_efficiency) {
+        return Err(format!(
+            "predation_efficiency has to be between 0 and 1, it's {}",
+            predation_efficiency
+        ));
+    }
+
+    let prey_detection_range = parse_setting::<f32>(&settings, "prey_detection_range")?;
+    let prey_panic_range = parse_setting::<f32>(&settings, "prey_panic_range")?;
+    if prey_panic_range > prey_detection_range {
+        return Err(format!(
+            "prey_panic_range can't be more than prey_detection_range ({}), it's {}",
+            prey_detection_range, prey_panic_range
+        ));
+    }
+
+    let predator_detection_range = parse_setting::<f32>(&settings, "predator_detection_range")?;
+    let prey_mating_range =
+        mating_range_setting(&settings, "prey_mating_range", prey_detection_range)?;
+    let predator_mating_range =
+        mating_range_setting(&settings, "predator_mating_range", predator_detection_range)?;
+
+    let prey_wary_feeding_fraction = parse_setting::<f32>(&settings, "prey_wary_feeding_fraction")?;
+    if !(0.0..=1.0).contains(&prey_wary_feeding_fraction) {
+        return Err(format!(
+            "prey_wary_feeding_fraction has to be between 0 and 1, it's {}",
+            prey_wary_feeding_fraction
+        ));
+    }
+
+    let prey_freeze_fraction = parse_setting::<f32>(&settings, "prey_freeze_fraction")?;
+    let freeze_mutation_chance = parse_setting::<f32>(&settings, "freeze_mutation_chance")?;
+    let freeze_detection_penalty = parse_setting::<f32>(&settings, "freeze_detection_penalty")?;
+    for (name, value) in [
+        ("prey_freeze_fraction", prey_freeze_fraction),
+        ("freeze_mutation_chance", freeze_mutation_chance),
+        ("freeze_detection_penalty", freeze_detection_penalty),
+    ] {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(format!(
+                "{} has to be between 0 and 1, it's {}",
+                name, value
+            ));
+        }
+    }
+
+    let predator_aggression = parse_setting::<f32>(&settings, "predator_aggression")?;
+    if !(0.0..=1.0).contains(&predator_aggression) {
+        return Err(format!(
+            "predator_aggression has to be between 0 and 1, it's {}",
+            predator_aggression
+        ));
+    }
+
+    let initial_environment_fraction =
+        parse_setting::<f32>(&settings, "initial_environment_fraction")?;
+    if !(0.0..=1.0).contains(&initial_environment_fraction) {
+        return Err(format!(
+            "initial_environment_fraction has to be between 0 and 1, it's {}",
+            initial_environment_fraction
+        ));
+    }
+
+    let grass_initial_fraction = parse_setting::<f32>(&settings, "grass_initial_fraction")?;
+    if !(0.0..=1.0).contains(&grass_initial_fraction) {
+        return Err(format!(
+            "grass_initial_fraction has to be between 0 and 1, it's {}",
+            grass_initial_fraction
+        ));
+    }
+
+    let predation_success_chance = parse_setting::<f32>(&settings, "predation_success_chance")?;
+    if !(0.0..=1.0).contains(&predation_success_chance) {
+        return Err(format!(
+            "predation_success_chance has to be between 0 and 1, it's {}",
+            predation_success_chance
+        ));
+    }
+
+    let msaa_samples = parse_setting::<u32>(&settings, "msaa_samples")?;
+    if ![1, 2, 4, 8].contains(&msaa_samples) {
+        return Err(format!(
+            "msaa_samples has to be 1 (off), 2, 4 or 8, it's {}",
+            msaa_samples
+        ));
+    }
+
+    let perturbations = text_setting(&settings, "perturbations")?;
+    parse_perturbations(&perturbations).map_err(|error| format!("perturbations: {}", error))?;
+
+    let metrics_columns = text_setting(&settings, "metrics_columns")?;
+    parse_metrics_columns(&metrics_columns)
+        .map_err(|error| format!("metrics_columns: {}", error))?;
+    let cover_regions = text_setting(&settings, "cover_regions")?;
+    parse_regions(&cover_regions).map_err(|error| format!("cover_regions: {}", error))?;
+    let burrow_regions = text_setting(&settings, "burrow_regions")?;
+    parse_regions(&burrow_regions).map_err(|error| format!("burrow_regions: {}", error))?;
+
+    let interaction_matrix = text_setting(&settings, "interaction_matrix")?;
+    parse_interaction_matrix(
+        &interaction_matrix,
+        parse_setting::<f32>(&settings, "predation_efficiency")?,
+    )
+    .map_err(|error| format!("interaction_matrix: {}", error))?;
+
+    let max_accel = parse_setting::<f32>(&settings, "max_accel")?;
+    if max_accel <= 0.0 {
+        return Err(format!("max_accel has to be above 0, it's {}", max_accel));
+    }
+
+    let simulate_to = parse_setting::<f32>(&settings, "simulate_to")?;
+    if simulate_to < 0.0 {
+        return Err(format!(
+            "simulate_to can't be below 0, it's {}",
+            simulate_to
+        ));
+    }
+
+    // realistic_reproduction turns on every reproduction rule that makes breeding cost what it
+    // would in nature, but only the ones left out of the config. Rules set by hand are kept
+    let realistic_reproduction = parse_setting::<bool>(&settings, "realistic_reproduction")?;
+    let conserve_reproduction_energy = reproduction_rule_setting(
+        &settings,
+        "conserve_reproduction_energy",
+        realistic_reproduction,
+        true,
+        false,
+    )?;
+    let post_mate_reserve = reproduction_rule_setting(
+        &settings,
+        "post_mate_reserve",
+        realistic_reproduction,
+        REALISTIC_POST_MATE_RESERVE,
+        0,
+    )?;
+    if post_mate_reserve < 0 {
+        return Err(format!(
+            "post_mate_reserve can't be below 0, it's {}",
+            post_mate_reserve
+        ));
+    }
+
+    let difficulty = text_setting(&settings, "difficulty")?;
+    difficulty_multiplier(&difficulty)?;
+
+    let left_edge = parse_setting::<EdgeBehavior>(&settings, "left_edge")?;
+    let right_edge = parse_setting::<EdgeBehavior>(&settings, "right_edge")?;
+    let bottom_edge = parse_setting::<EdgeBehavior>(&settings, "bottom_edge")?;
+    let top_edge = parse_setting::<EdgeBehavior>(&settings, "top_edge")?;
+    validate_edges(&Edges {
+        left: left_edge,
+        right: right_edge,
+        bottom: bottom_edge,
+        top: top_edge,
+    })?;
+
+    let status_tint_strength = parse_setting::<f32>(&settings, "status_tint_strength")?;
+    if !(0.0..=1.0).contains(&status_tint_strength) {
+        return Err(format!(
+            "status_tint_strength has to be between 0 and 1, it's {}",
+            status_tint_strength
+        ));
+    }
+    let prey_status_colors = text_setting(&settings, "prey_status_colors")?;
+    parse_status_colors(&prey_status_colors)
+        .map_err(|error| format!("prey_status_colors: {}", error))?;
+    let predator_status_colors = text_setting(&settings, "predator_status_colors")?;
+    parse_status_colors(&predator_status_colors)
+        .map_err(|error| format!("predator_status_colors: {}", error))?;
+
+    let fade_energy_fraction = parse_setting::<f32>(&settings, "fade_energy_fraction")?;
+    if !(0.0..=1.0).contains(&fade_energy_fraction) {
+        return Err(format!(
+            "fade_energy_fraction has to be between 0 and 1, it's {}",
+            fade_energy_fraction
+        ));
+    }
+    let fade_min_alpha = parse_setting::<f32>(&settings, "fade_min_alpha")?;
+    if !(0.0..=1.0).contains(&fade_min_alpha) {
+        return Err(format!(
+            "fade_min_alpha has to be between 0 and 1, it's {}",
+            fade_min_alpha
+        ));
+    }
+    let pixels_per_unit = parse_setting::<f32>(&settings, "pixels_per_unit")?;
+    if !(pixels_per_unit > 0.0 && pixels_per_unit.is_finite()) {
+        return Err(format!(
+            "pixels_per_unit has to be above 0, it's {}",
+            pixels_per_unit
+        ));
+    }
+    let gene_histogram_bins = parse_setting::<u32>(&settings, "gene_histogram_bins")?;
+    if gene_histogram_bins == 0 {
+        return Err("gene_histogram_bins has to be at least 1".to_string());
+    }
+    let gene_immigration_spread = parse_setting::<f32>(&settings, "gene_immigration_spread")?;
+    if !(0.0..=1.0).contains(&gene_immigration_spread) {
+        return Err(format!(
+            "gene_immigration_spread has to be between 0 and 1, it's {}",
+            gene_immigration_spread
+        ));
+    }
+    let detection_certain_fraction = parse_setting::<f32>(&settings, "detection_certain_fraction")?;
+    if !(0.0..=1.0).contains(&detection_certain_fraction) {
+        return Err(format!(
+            "detection_certain_fraction has to be between 0 and 1, it's {}",
+            detection_certain_fraction
+        ));
+    }
+
+    let breed_energy_margin = parse_setting::<i32>(&settings, "breed_energy_margin")?;
+    if breed_energy_margin < 0 {
+        return Err(format!(
+            "breed_energy_margin can't be below 0, it's {}",
+            breed_energy_margin
+        ));
+    }
+
+    let speed_mutation = parse_setting::<f32>(&settings, "speed_mutation")?;
+    if speed_mutation < 0.0 {
+        return Err(format!(
+            "speed_mutation can't be below 0, it's {}",
+            speed_mutation
+        ));
+    }
+    let speed_metabolism_coefficient =
+        parse_setting::<f32>(&settings, "speed_metabolism_coefficient")?;
+    if !(0.0..=1.0).contains(&speed_metabolism_coefficient) {
+        return Err(format!(
+            "speed_metabolism_coefficient has to be between 0 and 1, it's {}",
+            speed_metabolism_coefficient
+        ));
+    }
+
+    // DO NOT MESS UP THE TYPE IN THE CONFIG
+    Ok(Settings {
+        window_width: parse_setting::<f32>(&settings, "window_width")?,
+        window_height: parse_setting::<f32>(&settings, "window_height")?,
+        pixels_per_unit,
+        population_mode,
+        total_population,
+        predator_fraction,
+        predator_population,
+        prey_population,
+        predator_speed: parse_setting::<f32>(&settings, "predator_speed")?,
+        prey_speed: parse_setting::<f32>(&settings, "prey_speed")?,
+        predator_life: parse_setting::<i32>(&settings, "predator_life")?,
+        prey_life: parse_setting::<i32>(&settings, "prey_life")?,
+        prey_energy_loss: parse_setting::<i32>(&settings, "prey_energy_loss")?,
+        predator_energy_loss: parse_setting::<i32>(&settings, "predator_energy_loss")?,
+        prey_idle_energy_gain: parse_setting::<i32>(&settings, "prey_idle_energy_gain")?,
+        predator_idle_energy_gain: parse_setting::<i32>(&settings, "predator_idle_energy_gain")?,
+        predator_hunt_energy_gain: parse_setting::<i32>(&settings, "predator_hunt_energy_gain")?,
+        reproduction_energy_mode,
+        kill_sharing: parse_setting::<KillSharing>(&settings, "kill_sharing")?,
+        probabilistic_detection: parse_setting::<bool>(&settings, "probabilistic_detection")?,
+        detection_falloff: parse_setting::<DetectionFalloff>(&settings, "detection_falloff")?,
+        detection_certain_fraction,
+        prey_repro_fraction,
+        predator_repro_fraction,
+        prey_reproduction_energy,
+        predator_reproduction_energy,
+        prey_detection_range,
+        prey_panic_range,
+        prey_wary_feeding_fraction,
+        prey_freeze_fraction,
+        freeze_mutation_chance,
+        freeze_detection_penalty,
+        predator_detection_range,
+        prey_mating_range,
+        predator_mating_range,
+        default_dimensions: parse_setting::<f32>(&settings, "default_dimensions")?,
+        environment_grow_rate: parse_setting::<f32>(&settings, "environment_grow_rate")?,
+        environment_max: parse_setting::<i32>(&settings, "environment_max")?,
+        initial_environment_fraction,
+        environment_update_interval_secs: parse_setting::<f32>(
+            &settings,
+            "environment_update_interval_secs",
+        )?,
+        wiggle_when_hunted: parse_setting::<bool>(&settings, "wiggle_when_hunted")?,
+        alarm_enabled: parse_setting::<bool>(&settings, "alarm_enabled")?,
+        alarm_radius: parse_setting::<f32>(&settings, "alarm_radius")?,
+        collision_shape: parse_setting::<CollisionShape>(&settings, "collision_shape")?,
+        rescale_on_resize: parse_setting::<bool>(&settings, "rescale_on_resize")?,
+        metrics_output: text_setting(&settings, "metrics_output")?,
+        metrics_interval: parse_setting::<f32>(&settings, "metrics_interval")?,
+        metrics_columns,
+        predator_satiation_threshold: parse_setting::<i32>(
+            &settings,
+            "predator_satiation_threshold",
+        )?,
+        post_mate_reserve,
+        conserve_reproduction_energy,
+        speed_mutation,
+        speed_metabolism_coefficient,
+        prevent_extinction: parse_setting::<bool>(&settings, "prevent_extinction")?,
+        min_prey: parse_setting::<i32>(&settings, "min_prey")?,
+        min_predator: parse_setting::<i32>(&settings, "min_predator")?,
+        cover_regions,
+        prey_seek_cover: parse_setting::<bool>(&settings, "prey_seek_cover")?,
+        background_color: color_setting(&settings, "background_color")?,
+        prey_color: color_setting(&settings, "prey_color")?,
+        predator_color: color_setting(&settings, "predator_color")?,
+        environment_color: color_setting(&settings, "environment_color")?,
+        show_grid: parse_setting::<bool>(&settings, "show_grid")?,
+        grid_spacing: parse_setting::<f32>(&settings, "grid_spacing")?,
+        grid_color: color_setting(&settings, "grid_color")?,
+        color_by_status: parse_setting::<bool>(&settings, "color_by_status")?,
+        status_tint_strength,
+        prey_status_colors,
+        predator_status_colors,
+        fade_low_energy: parse_setting::<bool>(&settings, "fade_low_energy")?,
+        fade_energy_fraction,
+        fade_min_alpha,
+        show_trails: parse_setting::<bool>(&settings, "show_trails")?,
+        trail_length: parse_setting::<usize>(&settings, "trail_length")?,
+        spatial_index: parse_setting::<SpatialIndexKind>(&settings, "spatial_index")?,
+        spatial_cell_size: parse_setting::<f32>(&settings, "spatial_cell_size")?,
+        quadtree_capacity: parse_setting::<usize>(&settings, "quadtree_capacity")?,
+        cache_spatial_index: parse_setting::<bool>(&settings, "cache_spatial_index")?,
+        show_spatial_index: parse_setting::<bool>(&settings, "show_spatial_index")?,
+        spatial_query_debug: parse_setting::<bool>(&settings, "spatial_query_debug")?,
+        spatial_query_circle: parse_setting::<bool>(&settings, "spatial_query_circle")?,
+        burrow_regions,
+        burrow_capacity: parse_setting::<u32>(&settings, "burrow_capacity")?,
+        burrow_energy_regen: parse_setting::<i32>(&settings, "burrow_energy_regen")?,
+        burrow_leave_energy: parse_setting::<i32>(&settings, "burrow_leave_energy")?,
+        separation_enabled: parse_setting::<bool>(&settings, "separation_enabled")?,
+        separation_strength: parse_setting::<f32>(&settings, "separation_strength")?,
+        grass_enabled: parse_setting::<bool>(&settings, "grass_enabled")?,
+        grass_columns: parse_setting::<usize>(&settings, "grass_columns")?,
+        grass_rows: parse_setting::<usize>(&settings, "grass_rows")?,
+        grass_max_biomass: parse_setting::<f32>(&settings, "grass_max_biomass")?,
+        grass_initial_fraction,
+        grass_regrowth_rate: parse_setting::<f32>(&settings, "grass_regrowth_rate")?,
+        grass_graze_amount: parse_setting::<f32>(&settings, "grass_graze_amount")?,
+        show_grass: parse_setting::<bool>(&settings, "show_grass")?,
+        screenshot_hide_ui: parse_setting::<bool>(&settings, "screenshot_hide_ui")?,
+        use_fov: parse_setting::<bool>(&settings, "use_fov")?,
+        predator_fov_degrees: parse_setting::<f32>(&settings, "predator_fov_degrees")?,
+        prey_fov_degrees: parse_setting::<f32>(&settings, "prey_fov_degrees")?,
+        max_entities: parse_setting::<usize>(&settings, "max_entities")?,
+        validate_positions: parse_setting::<bool>(&settings, "validate_positions")?,
+        despawn_invalid_positions: parse_setting::<bool>(&settings, "despawn_invalid_positions")?,
+        breed_resource_threshold: parse_setting::<f32>(&settings, "breed_resource_threshold")?,
+        breed_energy_margin,
+        predator_requires_recent_kill: parse_setting::<bool>(
+            &settings,
+            "predator_requires_recent_kill",
+        )?,
+        kill_recency_ticks: parse_setting::<u64>(&settings, "kill_recency_ticks")?,
+        flee_toward_herd: parse_setting::<f32>(&settings, "flee_toward_herd")?,
+        record_snapshots: parse_setting::<bool>(&settings, "record_snapshots")?,
+        record_interval_ticks: parse_setting::<u64>(&settings, "record_interval_ticks")?,
+        record_output: text_setting(&settings, "record_output")?,
+        idle_behavior: parse_setting::<IdleBehavior>(&settings, "idle_behavior")?,
+        movement_model: parse_setting::<MovementModel>(&settings, "movement_model")?,
+        max_accel,
+        random_walk_turn_degrees: parse_setting::<f32>(&settings, "random_walk_turn_degrees")?,
+        patrol_distance: parse_setting::<f32>(&settings, "patrol_distance")?,
+        check_invariants: parse_setting::<bool>(&settings, "check_invariants")?,
+        panic_on_invariant_violation: parse_setting::<bool>(
+            &settings,
+            "panic_on_invariant_violation",
+        )?,
+        scale_by_energy: parse_setting::<bool>(&settings, "scale_by_energy")?,
+        energy_scale_min: parse_setting::<f32>(&settings, "energy_scale_min")?,
+        energy_scale_max: parse_setting::<f32>(&settings, "energy_scale_max")?,
+        energy_scale_collision: parse_setting::<bool>(&settings, "energy_scale_collision")?,
+        wall_avoidance: parse_setting::<bool>(&settings, "wall_avoidance")?,
+        wall_margin: parse_setting::<f32>(&settings, "wall_margin")?,
+        wall_avoidance_strength: parse_setting::<f32>(&settings, "wall_avoidance_strength")?,
+        layout_file: text_setting(&settings, "layout_file")?,
+        interaction_matrix,
+        fixed_timestep_hz: parse_setting::<f32>(&settings, "fixed_timestep_hz")?,
+        predator_memory_ticks: parse_setting::<u32>(&settings, "predator_memory_ticks")?,
+        per_entity_rng: parse_setting::<bool>(&settings, "per_entity_rng")?,
+        plot_max_points: parse_setting::<u32>(&settings, "plot_max_points")?,
+        prey_wiggle: parse_setting::<f32>(&settings, "prey_wiggle")?,
+        predator_wiggle: parse_setting::<f32>(&settings, "predator_wiggle")?,
+        aggression_enabled: parse_setting::<bool>(&settings, "aggression_enabled")?,
+        predator_aggression,
+        aggression_mutation: parse_setting::<f32>(&settings, "aggression_mutation")?,
+        aggression_energy_cost: parse_setting::<f32>(&settings, "aggression_energy_cost")?,
+        record_format: parse_setting::<SnapshotFormat>(&settings, "record_format")?,
+        detection_range_variance: parse_setting::<f32>(&settings, "detection_range_variance")?,
+        detection_range_mutation: parse_setting::<f32>(&settings, "detection_range_mutation")?,
+        detection_metabolism_coefficient: parse_setting::<f32>(
+            &settings,
+            "detection_metabolism_coefficient",
+        )?,
+        summary_output: text_setting(&settings, "summary_output")?,
+        show_prey_density: parse_setting::<bool>(&settings, "show_prey_density")?,
+        show_predator_density: parse_setting::<bool>(&settings, "show_predator_density")?,
+        density_columns: parse_setting::<u32>(&settings, "density_columns")?,
+        density_rows: parse_setting::<u32>(&settings, "density_rows")?,
+        density_opacity: parse_setting::<f32>(&settings, "density_opacity")?,
+        density_update_interval: parse_setting::<f32>(&settings, "density_update_interval")?,
+        gene_histogram_bins,
+        gene_histogram_interval: parse_setting::<f32>(&settings, "gene_histogram_interval")?,
+        gene_histogram_spread: parse_setting::<bool>(&settings, "gene_histogram_spread")?,
+        log_energy_accounting: parse_setting::<bool>(&settings, "log_energy_accounting")?,
+        msaa_samples,
+        crisp_sprites: parse_setting::<bool>(&settings, "crisp_sprites")?,
+        perturbations,
+        prey_mobbing: parse_setting::<bool>(&settings, "prey_mobbing")?,
+        prey_mobbing_energy_threshold: parse_setting::<i32>(
+            &settings,
+            "prey_mobbing_energy_threshold",
+        )?,
+        prey_mobbing_group_size: parse_setting::<u32>(&settings, "prey_mobbing_group_size")?,
+        prey_mobbing_range: parse_setting::<f32>(&settings, "prey_mobbing_range")?,
+        prey_mobbing_damage: parse_setting::<i32>(&settings, "prey_mobbing_damage")?,
+        prey_detection_mask: parse_setting::<u32>(&settings, "prey_detection_mask")?,
+        prey_collision_mask: parse_setting::<u32>(&settings, "prey_collision_mask")?,
+        predator_detection_mask: parse_setting::<u32>(&settings, "predator_detection_mask")?,
+        predator_collision_mask: parse_setting::<u32>(&settings, "predator_collision_mask")?,
+        simulate_to,
+        difficulty,
+        run_report: parse_setting::<RunReportFormat>(&settings, "run_report")?,
+        realistic_reproduction,
+        parallel_detection: parse_setting::<bool>(&settings, "parallel_detection")?,
+        open_world: parse_setting::<bool>(&settings, "open_world")?,
+        left_edge,
+        right_edge,
+        bottom_edge,
+        top_edge,
+        cull_distance: parse_setting::<f32>(&settings, "cull_distance")?,
+        immigration_rate: parse_setting::<f32>(&settings, "immigration_rate")?,
+        gene_immigration_rate: parse_setting::<f32>(&settings, "gene_immigration_rate")?,
+        gene_immigration_spread,
+        predation_efficiency,
+        predation_success_chance,
+        predation_miss_stun_ticks: parse_setting::<u32>(&settings, "predation_miss_stun_ticks")?,
+        seed,
+        ensemble_runs: parse_setting::<u32>(&settings, "ensemble_runs")?,
+        ensemble_ticks: parse_setting::<u32>(&settings, "ensemble_ticks")?,
+        ensemble_output: text_setting(&settings, "ensemble_output")?,
+    })
+}
+
+fn text_setting(settings: &HashMap<String, String>, key: &str) -> Result<String, String> {
+    settings
+        .get(key)
+        .cloned()
+        .ok_or(format!("{} is missing from the settings", key))
+}
+
+fn parse_setting<T: FromStr>(settings: &HashMap<String, String>, key: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = text_setting(settings, key)?;
+    value
+        .parse::<T>()
+        .map_err(|error| format!("{} = \"{}\" is invalid: {}", key, value, error))
+}
+
+// Mating ranges were added after the detection ranges, which were used for finding mates before
+// that, so a config without one keeps using the detection range
+fn mating_range_setting(
+    settings: &HashMap<String, String>,
+    key: &str,
+    detection_range: f32,
+) -> Result<f32, String> {
+    if !settings.contains_key(key) {
+        return Ok(detection_range);
+    }
+
+    let range = parse_setting::<f32>(settings, key)?;
+    if range <= 0.0 {
+        return Err(format!("{} has to be above 0, it's {}", key, range));
+    }
+    Ok(range)
+}
+
+// A reproduction rule left out of the config is realistic_value with realistic_reproduction on,
+// and off_value with it off
+fn reproduction_rule_setting<T: FromStr>(
+    settings: &HashMap<String, String>,
+    key: &str,
+    realistic_reproduction: bool,
+    realistic_value: T,
+    off_value: T,
+) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    if settings.contains_key(key) {
+        return parse_setting::<T>(settings, key);
+    }
+
+    Ok(if realistic_reproduction {
+        realistic_value
+    } else {
+        off_value
+    })
+}
+
+fn color_setting(settings: &HashMap<String, String>, key: &str) -> Result<Color, String> {
+    parse_hex_color(&text_setting(settings, key)?).map_err(|error| format!("{}: {}", key, error))
+}
+
+// The simulation's own schedules, run one after the other by tick_simulation for each tick.
+// Keeping them apart from the app's schedules lets a tick run anywhere, including many times in a
+// frame while catching up, without running the rendering & ui along with it
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimPreUpdate;
+
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimUpdate;
+
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimPostUpdate;
+
+pub fn tick_simulation(world: &mut World) {
+    world.run_schedule(SimPreUpdate);
+    world.run_schedule(SimUpdate);
+    world.run_schedule(SimPostUpdate);
+}
+
+// Everything needed to run the simulation itself, without any windows, rendering or ui.
+// The Settings resource has to be inserted before this plugin is added.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = app.world().resource::<Settings>().seed;
+        app.insert_resource(SimRng(StdRng::seed_from_u64(seed)));
+
+        app.add_systems(Startup, (setup, setup_metrics_writer));
+        app.insert_resource(PopulationHistory {
+            prey_population: Vec::new(),
+            predator_population: Vec::new(),
+            predator_aggression: Vec::new(),
+            prey_detection_range: Vec::new(),
+            predator_detection_range: Vec::new(),
+        });
+        app.init_resource::<SimStats>();
+        app.init_resource::<SimClock>();
+        app.init_resource::<EnergyLedger>();
+        app.init_resource::<NextStableId>();
+        let spatial_indexes = SpatialIndexes::new(app.world().resource::<Settings>());
+        app.insert_resource(spatial_indexes);
+        let grass = GrassGrid::new(app.world().resource::<Settings>());
+        app.insert_resource(grass);
+        let interaction_matrix = InteractionMatrix::new(app.world().resource::<Settings>());
+        app.insert_resource(interaction_matrix);
+        let perturbation_schedule = PerturbationSchedule::new(app.world().resource::<Settings>());
+        app.insert_resource(perturbation_schedule);
+        app.init_resource::<PlotAnnotations>();
+        app.init_resource::<CatchUp>();
+
+        // These components and resources are being "registered" to appear in the inspector gui
+        app.register_type::<PopulationHistory>();
+        app.register_type::<Settings>();
+        app.register_type::<PositionSize>();
+        app.register_type::<Mortal>();
+        app.register_type::<Prey>();
+        app.register_type::<Predator>();
+        app.register_type::<MatingTarget>();
+        app.register_type::<Life>();
+        app.register_type::<Genes>();
+        app.register_type::<Age>();
+        app.register_type::<LastKill>();
+        app.register_type::<SpeciesId>();
+        app.register_type::<Layers>();
+        app.register_type::<StableId>();
+        app.register_type::<Parents>();
+        app.register_type::<BeingDragged>();
+        app.register_type::<NextStableId>();
+        app.register_type::<Heading>();
+        app.register_type::<Velocity>();
+        app.register_type::<PreviousPosition>();
+        app.register_type::<Trail>();
+        app.register_type::<SimStats>();
+        app.register_type::<SimClock>();
+        app.register_type::<EnergyLedger>();
+        app.register_type::<Cover>();
+        app.register_type::<Burrow>();
+        app.register_type::<Environment>();
+
+        // With a fixed timestep the simulation ticks at a steady rate however fast frames are drawn,
+        // otherwise it ticks once every frame. It doesn't tick on its own while catching up, as
+        // that steps it as many times as it needs to instead
+        let fixed_timestep_hz = app.world().resource::<Settings>().fixed_timestep_hz;
+        let tick_schedule = if fixed_timestep_hz > 0.0 {
+            app.insert_resource(Time::<Fixed>::from_hz(fixed_timestep_hz as f64));
+            FixedUpdate.intern()
+        } else {
+            PreUpdate.intern()
+        };
+        app.init_schedule(SimPreUpdate);
+        app.init_schedule(SimUpdate);
+        app.init_schedule(SimPostUpdate);
+        app.add_systems(tick_schedule, tick_simulation.run_if(not_catching_up));
+
+        // The clock ticks before anything else so every system sees the same time in a frame,
+        // and the spatial indexes are rebuilt so every system looks up the same positions (apart
+        // from predation, which needs where everyone ended up)
+        app.add_systems(
+            SimPreUpdate,
+            (
+                advance_sim_clock,
+                rebuild_spatial_indexes,
+                record_previous_positions,
+            ),
+        );
+
+        // Run in this order every tick. Most of these share SimRng, and left unordered Bevy can
+        // pick a different order each time the program starts, so the same seed wouldn't always
+        // play out the same way
+        app.add_systems(
+            SimUpdate,
+            (
+                update_environment,
+                regrow_grass,
+                wiggle_squares,
+                handle_mating,
+                update_preys,
+                propagate_prey_alarm,
+                update_predators,
+                try_mate_prey,
+                try_mate_predator,
+                separate_creatures,
+                window_collision,
+                drain_life,
+                // Everyone has moved & been born since the start of the tick, and predators
+                // are looked up from the indexes, so they're brought up to date first
+                (rebuild_spatial_indexes, handle_hostile_collisions).chain(),
+                remove_dead,
+                prevent_extinction,
+                cull_emigrants,
+                (spawn_immigrants, spawn_gene_immigrants).chain(),
+                update_age,
+                update_population_history,
+            )
+                .chain(),
+        );
+
+        // Anything a perturbation kills is removed & counted along with every other death
+        app.add_systems(
+            SimUpdate,
+            apply_perturbations
+                .after(handle_hostile_collisions)
+                .before(remove_dead),
+        );
+
+        // Checked against the history sample taken this frame
+        app.add_systems(SimUpdate, check_invariants.after(update_population_history));
+        app.add_systems(SimUpdate, annotate_predator_boom.after(remove_dead));
+        app.add_systems(
+            SimUpdate,
+            log_extinctions
+                .after(remove_dead)
+                .after(prevent_extinction)
+                .after(cull_emigrants),
+        );
+        app.add_systems(
+            SimUpdate,
+            scale_collision_by_energy
+                .after(drain_life)
+                .before(handle_hostile_collisions),
+        );
+
+        // Checked once everything has moved for the frame
+        app.add_systems(SimPostUpdate, validate_positions);
+        // Once everything that can change anyone's energy this tick has run
+        app.add_systems(SimPostUpdate, update_energy_ledger);
+
+        // Exports run after everything else in the tick so they see its final state. They're part
+        // of the tick, so a frame that runs several (with a fixed timestep or while catching up)
+        // exports every one of them, and a paused simulation doesn't export the same tick again
+        app.add_systems(
+            SimPostUpdate,
+            (write_metrics, record_snapshots)
+                .after(validate_positions)
+                .after(update_energy_ledger),
+        );
+        app.add_systems(Last, flush_on_exit);
+    }
+}
+
+// Runs the simulation in a window along with all the ui, until the window is closed
+pub fn run_windowed(settings: Settings) {
+    let mut app = App::new();
+
+    // Nearest neighbor sampling keeps the edges of scaled up images sharp instead of blurring them
+    let image_plugin = if settings.crisp_sprites {
+        ImagePlugin::default_nearest()
+    } else {
+        ImagePlugin::default_linear()
+    };
+
+    app.add_plugins((
+        DefaultPlugins
+            .set(image_plugin)
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Predator Prey Simulation".to_string(),
+                    resolution: (settings.window_width, settings.window_height).into(),
+                    ..default()
+                }),
+                ..default()
+            })
+            // Logging is set up by init_logging before the window opens
+            .disable::<LogPlugin>(),
+        FrameTimeDiagnosticsPlugin,
+        EguiPlugin,
+    ));
+
+    // Make sure settings resource is created BEFORE
+    // setting up the simulation with all the necessary values
+    let simulate_to = settings.simulate_to;
+    app.insert_resource(settings);
+    app.add_plugins(SimulationPlugin);
+    // Only the window catches up, everything headless steps the simulation itself
+    app.insert_resource(CatchUp::new(simulate_to));
+
+    // These are all the functions to add the ui elements to the simulation
+    app.add_plugins((
+        ResourceInspectorPlugin::<Settings>::default().run_if(ui_visible),
+        WorldInspectorPlugin::new().run_if(ui_visible),
+    ));
+    // app.add_systems(Update, plot_ui);
+
+    app.init_resource::<ScreenshotState>();
+    app.init_resource::<Selection>();
+    app.init_resource::<SweepPanel>();
+    app.init_resource::<GeneHistogram>();
+    app.add_systems(
+        Startup,
+        (setup_ui, setup_grass_heatmap, setup_density_heatmap),
+    );
+    app.add_systems(PreUpdate, take_screenshot);
+    // Resizing moves the walls, so it's done before the simulation ticks against them
+    app.add_systems(PreUpdate, handle_window_resize.before(tick_simulation));
+    app.add_systems(First, catch_up);
+    app.add_systems(
+        Update,
+        (
+            update_transform,
+            update_ui_text,
+            plot_ui.run_if(ui_visible),
+            (
+                aggression_plot_ui.run_if(ui_visible),
+                detection_range_plot_ui.run_if(ui_visible),
+                update_gene_histogram,
+                gene_histogram_ui
+                    .after(update_gene_histogram)
+                    .run_if(ui_visible),
+            ),
+            control_panel_ui.run_if(ui_visible),
+            draw_grid,
+            record_trails,
+            (
+                scale_sprites_by_energy,
+                tint_by_status,
+                fade_low_energy.after(tint_by_status),
+            ),
+            draw_trails.after(record_trails),
+            (draw_spatial_indexes, probe_spatial_indexes),
+            update_grass_heatmap,
+            update_density_heatmap,
+            select_on_click,
+            draw_selection.after(select_on_click),
+            selection_ui.after(select_on_click).run_if(ui_visible),
+            (
+                follow_selected.after(select_on_click),
+                drag_selected.after(select_on_click),
+            ),
+            collect_sweep_results,
+            sweep_ui.after(collect_sweep_results).run_if(ui_visible),
+            catch_up_ui,
+        ),
+    );
+
+    app.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The settings file with a fixed seed & nobody spawned at the start, so each test can put
+    // exactly the creatures it needs where it needs them
+    fn test_settings() -> Settings {
+        let mut settings = read_settings();
+        settings.seed = 1;
+        settings.population_mode = PopulationMode::Absolute;
+        settings.prey_population = 0;
+        settings.predator_population = 0;
+        settings
+    }
+
+    // A headless simulation that's been through its startup, with nothing in it yet
+    fn empty_app(settings: Settings) -> App {
+        let mut app = build_headless_app(settings);
+        app.update();
+        app
+    }
+
+    fn add_prey(app: &mut App, x: f32, y: f32, life: i32) -> Entity {
+        let world = app.world_mut();
+        let settings = world.resource::<Settings>().clone();
+        let genes = Genes::starting_prey(&settings, &mut world.resource_mut::<SimRng>().0);
+        let prey = spawn_prey(&mut world.commands(), &settings, x, y, life, genes);
+        world.flush();
+        prey
+    }
+
+    fn add_predator(app: &mut App, x: f32, y: f32, life: i32) -> Entity {
+        let world = app.world_mut();
+        let settings = world.resource::<Settings>().clone();
+        let genes = Genes::starting_predator(&settings, &mut world.resource_mut::<SimRng>().0);
+        let predator = spawn_predator(&mut world.commands(), &settings, x, y, life, genes);
+        world.flush();
+        predator
+    }
+
+    // Starts a tick like the simulation does, then runs only the systems being tested
+    fn run<M>(app: &mut App, systems: impl IntoSystemConfigs<M>) {
+        app.world_mut().run_schedule(SimPreUpdate);
+        let mut schedule = Schedule::default();
+        schedule.add_systems(systems);
+        schedule.run(app.world_mut());
+    }
+
+    fn prey_status(app: &App, prey: Entity) -> u16 {
+        app.world().get::<Prey>(prey).unwrap().status
+    }
+
+    fn life(app: &App, entity: Entity) -> i32 {
+        app.world().get::<Life>(entity).unwrap().value
+    }
+
+    fn position(app: &App, entity: Entity) -> Vec2 {
+        let position_size = app.world().get::<PositionSize>(entity).unwrap();
+        Vec2::new(position_size.x, position_size.y)
+    }
+
+    fn prey_count(app: &mut App) -> usize {
+        let world = app.world_mut();
+        world.query::<&Prey>().iter(world).count()
+    }
+
+    // Two prey on the same spot with plenty of energy, who've picked each other to mate with
+    fn mating_prey(app: &mut App) -> (Entity, Entity) {
+        let settings = app.world().resource::<Settings>().clone();
+        let life = prey_mating_threshold(&settings) * 2;
+        let first = add_prey(app, 0.0, 0.0, life);
+        let second = add_prey(app, 0.0, 0.0, life);
+
+        run(app, try_mate_prey);
+        (first, second)
+    }
+
+    fn predator_status(app: &App, predator: Entity) -> u16 {
+        app.world().get::<Predator>(predator).unwrap().status
+    }
+
+    fn band_settings() -> Settings {
+        let mut settings = test_settings();
+        settings.prey_detection_range = 50.0;
+        settings.prey_panic_range = 20.0;
+        settings
+    }
+
+    #[test]
+    fn predators_in_the_panic_range_send_prey_running() {
+        let mut app = empty_app(band_settings());
+        let prey = add_prey(&mut app, 0.0, 0.0, 100);
+        add_predator(&mut app, 10.0, 0.0, 100);
+
+        run(&mut app, update_preys);
+
+        assert_eq!(prey_status(&app, prey), 2);
+        assert!(position(&app, prey).x < 0.0);
+    }
+
+    #[test]
+    fn predators_between_the_ranges_make_prey_wary() {
+        let mut app = empty_app(band_settings());
+        let prey = add_prey(&mut app, 0.0, 0.0, 100);
+        add_predator(&mut app, 35.0, 0.0, 100);
+
+        run(&mut app, update_preys);
+
+        assert_eq!(prey_status(&app, prey), 5);
+        assert_eq!(position(&app, prey), Vec2::ZERO);
+    }
+
+    #[test]
+    fn predators_out_of_range_are_ignored() {
+        let mut app = empty_app(band_settings());
+        let prey = add_prey(&mut app, 0.0, 0.0, 100);
+        add_predator(&mut app, 80.0, 0.0, 100);
+
+        run(&mut app, update_preys);
+
+        assert_eq!(prey_status(&app, prey), 0);
+    }
+
+    #[test]
+    fn full_predators_leave_prey_alone() {
+        let mut settings = test_settings();
+        settings.predator_satiation_threshold = 500;
+        let mut app = empty_app(settings);
+        add_prey(&mut app, 0.0, 0.0, 100);
+        let full = add_predator(&mut app, 10.0, 0.0, 1000);
+        let hungry = add_predator(&mut app, -10.0, 0.0, 400);
+
+        run(&mut app, update_predators);
+
+        assert_eq!(predator_status(&app, full), 0);
+        assert_eq!(predator_status(&app, hungry), 2);
+    }
+
+    #[test]
+    fn predators_cant_see_prey_in_cover() {
+        let mut settings = test_settings();
+        settings.cover_regions = "0,0,20,20".to_string();
+        let mut app = empty_app(settings);
+        add_prey(&mut app, 0.0, 0.0, 100);
+        let predator = add_predator(&mut app, 30.0, 0.0, 100);
+
+        run(&mut app, update_predators);
+        assert_eq!(predator_status(&app, predator), 0);
+
+        // The same prey out in the open is hunted
+        let mut app = empty_app(test_settings());
+        add_prey(&mut app, 0.0, 0.0, 100);
+        let predator = add_predator(&mut app, 30.0, 0.0, 100);
+
+        run(&mut app, update_predators);
+        assert_eq!(predator_status(&app, predator), 2);
+    }
+
+    #[test]
+    fn bad_regions_are_errors() {
+        let regions = parse_regions("-200,100,120,80; 250,-150,100,100;").unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[1].x, 250.0);
+        assert_eq!(regions[1].height, 100.0);
+
+        assert!(parse_regions("0,0,20").is_err());
+        assert!(parse_regions("0,0,20,20,5").is_err());
+        assert!(parse_regions("0,0,twenty,20").is_err());
+        assert!(parse_regions("0,0,inf,20").is_err());
+        assert!(parse_regions("0,0,20,-20").is_err());
+    }
+
+    #[test]
+    fn faster_predators_burn_energy_faster() {
+        let mut settings = test_settings();
+        settings.predator_energy_loss = 2;
+        settings.predator_idle_energy_gain = 0;
+        settings.speed_metabolism_coefficient = 0.5;
+        let mut app = empty_app(settings.clone());
+        let baseline = add_predator(&mut app, -100.0, 0.0, 1000);
+        let fast = add_predator(&mut app, 100.0, 0.0, 1000);
+        app.world_mut().get_mut::<Genes>(fast).unwrap().speed = settings.predator_speed * 2.0;
+
+        for _tick in 0..100 {
+            run(&mut app, drain_life);
+        }
+
+        // Half as much again for being twice as fast
+        assert_eq!(life(&app, baseline), 800);
+        assert_eq!(life(&app, fast), 700);
+    }
+
+    // How much is in the environment's pool after one simulated second at this frame rate
+    fn environment_after_a_second(fps: u32) -> i32 {
+        let mut settings = test_settings();
+        settings.environment_max = 1_000_000;
+        settings.initial_environment_fraction = 0.001;
+        settings.environment_grow_rate = 1.1;
+        settings.environment_update_interval_secs = 0.15;
+        let mut app = empty_app(settings);
+        app.world_mut().resource_mut::<SimClock>().seconds = 0.0;
+
+        // Kept for the whole second, as update_environment remembers when it last grew
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_environment);
+        for _frame in 0..fps {
+            app.world_mut().resource_mut::<SimClock>().seconds += 1.0 / fps as f64;
+            schedule.run(app.world_mut());
+        }
+
+        let world = app.world_mut();
+        world.query::<&Environment>().single(world).energy_pool
+    }
+
+    #[test]
+    fn the_environment_grows_the_same_at_any_frame_rate() {
+        // Six intervals of growth fit in the second, rounded after each one
+        assert_eq!(environment_after_a_second(30), 1771);
+        assert_eq!(environment_after_a_second(120), 1771);
+    }
+
+    #[test]
+    fn idle_predators_scavenge_a_little() {
+        let mut settings = test_settings();
+        settings.predator_energy_loss = 2;
+        settings.predator_idle_energy_gain = 1;
+        let mut app = empty_app(settings);
+        let idle = add_predator(&mut app, -100.0, 0.0, 1000);
+        let hunting = add_predator(&mut app, 100.0, 0.0, 1000);
+        app.world_mut().get_mut::<Predator>(hunting).unwrap().status = 2;
+
+        for _tick in 0..100 {
+            run(&mut app, drain_life);
+        }
+
+        assert_eq!(life(&app, idle), 900);
+        assert_eq!(life(&app, hunting), 800);
+    }
+
+    #[test]
+    fn predators_chase_prey_they_just_lost_sight_of() {
+        let mut settings = test_settings();
+        settings.predator_memory_ticks = 5;
+        let mut app = empty_app(settings);
+        let prey = add_prey(&mut app, 40.0, 0.0, 100);
+        let predator = add_predator(&mut app, 0.0, 0.0, 100);
+        run(&mut app, update_predators);
+
+        // The prey gets away, but the predator keeps heading to where it last saw it for a while
+        app.world_mut().get_mut::<PositionSize>(prey).unwrap().x = 500.0;
+        for _tick in 0..5 {
+            let before = position(&app, predator).x;
+            run(&mut app, update_predators);
+            assert_eq!(predator_status(&app, predator), 2);
+            assert!(position(&app, predator).x > before);
+        }
+
+        run(&mut app, update_predators);
+        assert_eq!(predator_status(&app, predator), 0);
+    }
+
+    #[test]
+    fn wiggling_goes_as_far_in_every_direction() {
+        let mut settings = test_settings();
+        settings.idle_behavior = IdleBehavior::Jitter;
+        settings.prey_wiggle = 1.0;
+        let mut app = empty_app(settings);
+        let prey = add_prey(&mut app, 0.0, 0.0, 100);
+
+        // How many wiggles went each eighth of the way around, and how far they went in total
+        let mut octants = [(0, 0.0); 8];
+        for _tick in 0..4000 {
+            *app.world_mut().get_mut::<PositionSize>(prey).unwrap() = PositionSize {
+                x: 0.0,
+                y: 0.0,
+                width: 3.0,
+                height: 3.0,
+            };
+            run(&mut app, wiggle_squares);
+
+            let moved = position(&app, prey);
+            let octant = ((moved.to_angle() / std::f32::consts::FRAC_PI_4).floor() + 4.0) as usize;
+            octants[octant.min(7)].0 += 1;
+            octants[octant.min(7)].1 += moved.length();
+        }
+
+        for (count, distance) in octants {
+            assert!((400..=600).contains(&count), "{:?}", octants);
+            assert!(
+                (distance / count as f32 - 1.0).abs() < 0.001,
+                "{:?}",
+                octants
+            );
+        }
+    }
+
+    #[test]
+    fn only_creatures_wiggle() {
+        let mut settings = test_settings();
+        settings.cover_regions = "-100,0,40,40".to_string();
+        settings.burrow_regions = "100,0,40,40".to_string();
+        let mut app = empty_app(settings);
+        // Anything else with a place in the world, like a wall would be
+        let wall = app
+            .world_mut()
+            .spawn(PositionSize {
+                x: 0.0,
+                y: 50.0,
+                width: 200.0,
+                height: 5.0,
+            })
+            .id();
+        add_prey(&mut app, 0.0, 0.0, 100);
+
+        let places = |app: &mut App| -> Vec<(Entity, Vec2)> {
+            let world = app.world_mut();
+            world
+                .query_filtered::<(Entity, &PositionSize), Without<Prey>>()
+                .iter(world)
+                .map(|(entity, position_size)| {
+                    (entity, Vec2::new(position_size.x, position_size.y))
+                })
+                .collect()
+        };
+        let before = places(&mut app);
+        assert_eq!(before.len(), 3);
+        assert!(before.iter().any(|(entity, _)| *entity == wall));
+
+        for _tick in 0..10 {
+            run(&mut app, wiggle_squares);
+        }
+
+        assert_eq!(places(&mut app), before);
+    }
+
+    #[test]
+    fn faster_prey_burn_energy_faster() {
+        let mut settings = test_settings();
+        settings.prey_energy_loss = 2;
+        settings.speed_metabolism_coefficient = 0.5;
+        let mut app = empty_app(settings.clone());
+        let baseline = add_prey(&mut app, -100.0, 0.0, 1000);
+        let fast = add_prey(&mut app, 100.0, 0.0, 1000);
+        app.world_mut().get_mut::<Genes>(fast).unwrap().speed = settings.prey_speed * 2.0;
+
+        for _tick in 0..100 {
+            run(&mut app, drain_life);
+        }
+
+        assert_eq!(life(&app, baseline), 800);
+        assert_eq!(life(&app, fast), 700);
+    }
+
+    #[test]
+    fn genes_that_cant_mutate_dont_use_random_numbers() {
+        let mut settings = test_settings();
+        settings.speed_mutation = 0.0;
+        settings.aggression_enabled = false;
+        settings.detection_range_mutation = 0.0;
+        settings.freeze_mutation_chance = 0.0;
+        let mut rng = StdRng::seed_from_u64(1);
+        let parent = Genes::starting_prey(&settings, &mut rng);
+        let mut untouched = rng.clone();
+
+        let child = inherit_genes(&parent, &settings, &mut rng);
+
+        assert_eq!(child.speed, parent.speed);
+        assert_eq!(rng.gen::<u64>(), untouched.gen::<u64>());
+    }
+
+    #[test]
+    fn wary_prey_graze_their_share_of_the_grass() {
+        let mut settings = band_settings();
+        settings.grass_enabled = true;
+        settings.grass_graze_amount = 1.0;
+        settings.grass_max_biomass = 1_000_000.0;
+        settings.prey_wary_feeding_fraction = 0.5;
+        let mut app = empty_app(settings);
+        let calm = add_prey(&mut app, -200.0, 0.0, 100);
+        let wary = add_prey(&mut app, 200.0, 0.0, 100);
+        add_predator(&mut app, 235.0, 0.0, 100);
+
+        for _tick in 0..400 {
+            run(&mut app, update_preys);
+        }
+
+        assert_eq!(prey_status(&app, wary), 5);
+        assert_eq!(life(&app, calm), 500);
+        // Half a unit a tick, rounded up or down at random, comes to about 200
+        let gained = life(&app, wary) - 100;
+        assert!((150..=250).contains(&gained), "gained {}", gained);
+    }
+
+    #[test]
+    fn frozen_prey_stop_moving() {
+        let mut settings = band_settings();
+        settings.prey_freeze_fraction = 1.0;
+        let mut app = empty_app(settings);
+        let prey = add_prey(&mut app, 0.0, 0.0, 100);
+        add_predator(&mut app, 10.0, 0.0, 100);
+
+        for _tick in 0..10 {
+            run(&mut app, (update_preys, wiggle_squares).chain());
+        }
+
+        assert_eq!(prey_status(&app, prey), 6);
+        assert_eq!(position(&app, prey), Vec2::ZERO);
+    }
+
+    #[test]
+    fn frozen_prey_dont_mate() {
+        let mut app = empty_app(test_settings());
+        let (first, second) = mating_prey(&mut app);
+        for prey in [first, second] {
+            app.world_mut().get_mut::<Prey>(prey).unwrap().status = 6;
+        }
+
+        run(&mut app, handle_mating);
+
+        assert_eq!(prey_count(&mut app), 2);
+    }
+
+    // Whether the prey has somewhere to go to mate, and who with
+    fn mating_target(app: &App, prey: Entity) -> (bool, Option<u64>) {
+        let target = app.world().get::<MatingTarget>(prey).unwrap();
+        (target.entity.is_some(), target.partner)
+    }
+
+    #[test]
+    fn prey_that_picked_each_other_have_one_child() {
+        let mut app = empty_app(test_settings());
+        let (first, second) = mating_prey(&mut app);
+        let before = life(&app, first);
+        assert!(mating_target(&app, first).1.is_some());
+        assert!(mating_target(&app, second).1.is_some());
+
+        run(&mut app, handle_mating);
+
+        // Only the partner with the higher id has the child, and pays for it
+        let settings = app.world().resource::<Settings>().clone();
+        assert_eq!(prey_count(&mut app), 3);
+        assert_eq!(life(&app, first), before);
+        assert_eq!(
+            life(&app, second),
+            before - settings.prey_reproduction_energy
+        );
+        assert_eq!(mating_target(&app, second), (false, None));
+        assert!(mating_target(&app, first).1.is_some());
+
+        // The other partner's target moved on, so nobody has a second child next tick
+        run(&mut app, handle_mating);
+        assert_eq!(prey_count(&mut app), 3);
+    }
+
+    #[test]
+    fn a_prey_whose_partner_didnt_pick_it_still_has_one_child() {
+        let mut app = empty_app(test_settings());
+        let (first, second) = mating_prey(&mut app);
+        let before = life(&app, second);
+        *app.world_mut().get_mut::<MatingTarget>(first).unwrap() = MatingTarget {
+            entity: None,
+            partner: None,
+        };
+
+        run(&mut app, handle_mating);
+
+        let settings = app.world().resource::<Settings>().clone();
+        assert_eq!(prey_count(&mut app), 3);
+        assert_eq!(
+            life(&app, second),
+            before - settings.prey_reproduction_energy
+        );
+        assert_eq!(mating_target(&app, second), (false, None));
+    }
+
+    #[test]
+    fn children_remember_both_parents() {
+        let mut app = empty_app(test_settings());
+        let (first, second) = mating_prey(&mut app);
+        let first_id = *app.world().get::<StableId>(first).unwrap();
+        let second_id = *app.world().get::<StableId>(second).unwrap();
+
+        run(&mut app, handle_mating);
+
+        let world = app.world_mut();
+        let children: Vec<Parents> = world
+            .query::<&Parents>()
+            .iter(world)
+            .filter(|parents| parents.generation > 0)
+            .copied()
+            .collect();
+        assert_eq!(
+            children,
+            vec![Parents {
+                a: Some(second_id),
+                b: Some(first_id),
+                founder: Some(second_id),
+                generation: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn prey_in_a_burrow_cant_be_eaten() {
+        let mut settings = test_settings();
+        settings.burrow_regions = "0,0,20,20".to_string();
+        let mut app = empty_app(settings);
+        let prey = add_prey(&mut app, 0.0, 0.0, 100);
+        add_predator(&mut app, 1.0, 0.0, 100);
+
+        for _tick in 0..10 {
+            run(&mut app, (update_preys, handle_hostile_collisions).chain());
+        }
+
+        assert_eq!(prey_status(&app, prey), 4);
+        assert!(!app.world().get::<Mortal>(prey).unwrap().dead);
+    }
+
+    #[test]
+    fn predators_get_their_share_of_the_prey_energy() {
+        let mut settings = test_settings();
+        settings.predation_efficiency = 0.2;
+        settings.predator_hunt_energy_gain = 0;
+        let mut app = empty_app(settings);
+        let fat = add_prey(&mut app, -100.0, 0.0, 500);
+        let thin = add_prey(&mut app, 100.0, 0.0, 50);
+        let first = add_predator(&mut app, -99.0, 0.0, 1000);
+        let second = add_predator(&mut app, 101.0, 0.0, 1000);
+
+        run(&mut app, handle_hostile_collisions);
+
+        assert!(app.world().get::<Mortal>(fat).unwrap().dead);
+        assert!(app.world().get::<Mortal>(thin).unwrap().dead);
+        assert_eq!(life(&app, first), 1000 + 100);
+        assert_eq!(life(&app, second), 1000 + 10);
+    }
+
+    #[test]
+    fn the_hunt_gain_comes_with_each_kill_not_each_tick() {
+        let mut settings = test_settings();
+        settings.predation_efficiency = 0.0;
+        settings.predator_hunt_energy_gain = 300;
+        settings.predator_energy_loss = 0;
+        let mut app = empty_app(settings);
+        add_prey(&mut app, 0.0, 0.0, 100);
+        let predator = add_predator(&mut app, 1.0, 0.0, 1000);
+
+        // Chasing on its own is worth nothing
+        app.world_mut()
+            .get_mut::<Predator>(predator)
+            .unwrap()
+            .status = 2;
+        run(&mut app, drain_life);
+        assert_eq!(life(&app, predator), 1000);
+
+        // The whole reward comes with the kill, even when the prey itself is no meal at all
+        run(&mut app, handle_hostile_collisions);
+        assert_eq!(life(&app, predator), 1300);
+    }
+
+    #[test]
+    fn attacks_succeed_as_often_as_the_chance_says() {
+        let mut settings = test_settings();
+        settings.predation_success_chance = 0.3;
+        let mut app = empty_app(settings);
+        // 400 predators each touching their own prey, far enough apart to not reach any other
+        let mut preys = Vec::new();
+        for row in 0..20 {
+            for column in 0..20 {
+                let (x, y) = (column as f32 * 20.0 - 200.0, row as f32 * 20.0 - 200.0);
+                preys.push(add_prey(&mut app, x, y, 100));
+                add_predator(&mut app, x + 1.0, y, 1000);
+            }
+        }
+
+        run(&mut app, handle_hostile_collisions);
+
+        let kills = preys
+            .iter()
+            .filter(|prey| app.world().get::<Mortal>(**prey).unwrap().dead)
+            .count();
+        assert!((90..=150).contains(&kills), "{} kills", kills);
+        let world = app.world_mut();
+        let stunned = world
+            .query::<&Predator>()
+            .iter(world)
+            .filter(|predator| predator.stunned_ticks_left > 0)
+            .count();
+        assert_eq!(kills + stunned, 400);
+    }
+
+    // A predator with this much energy touching a prey, with four more prey around it
+    fn surrounded_predator(life: i32) -> (App, Entity, Entity) {
+        let mut settings = test_settings();
+        settings.prey_mobbing = true;
+        settings.prey_mobbing_energy_threshold = 2000;
+        settings.prey_mobbing_group_size = 4;
+        settings.prey_mobbing_range = 15.0;
+        settings.prey_mobbing_damage = 200;
+        settings.predator_hunt_energy_gain = 0;
+        let mut app = empty_app(settings);
+        let predator = add_predator(&mut app, 0.0, 0.0, life);
+        let prey = add_prey(&mut app, 1.0, 0.0, 100);
+        for (x, y) in [(10.0, 0.0), (-10.0, 0.0), (0.0, 10.0), (0.0, -10.0)] {
+            add_prey(&mut app, x, y, 100);
+        }
+
+        run(&mut app, handle_hostile_collisions);
+        (app, predator, prey)
+    }
+
+    #[test]
+    fn prey_fight_off_weak_predators() {
+        let (app, predator, prey) = surrounded_predator(500);
+
+        assert_eq!(life(&app, predator), 300);
+        assert!(!app.world().get::<Mortal>(prey).unwrap().dead);
+    }
+
+    #[test]
+    fn strong_predators_still_eat_surrounded_prey() {
+        let (app, predator, prey) = surrounded_predator(3000);
+
+        assert!(life(&app, predator) > 3000);
+        assert!(app.world().get::<Mortal>(prey).unwrap().dead);
+    }
+
+    #[test]
+    fn split_kills_share_out_all_the_energy() {
+        let mut settings = test_settings();
+        settings.kill_sharing = KillSharing::Split;
+        settings.predation_efficiency = 1.0;
+        settings.predator_hunt_energy_gain = 11;
+        let mut app = empty_app(settings);
+        let prey = add_prey(&mut app, 0.0, 0.0, 101);
+        let far = add_predator(&mut app, -2.0, 0.0, 1000);
+        let near = add_predator(&mut app, 1.0, 0.0, 1000);
+
+        run(&mut app, handle_hostile_collisions);
+
+        assert!(app.world().get::<Mortal>(prey).unwrap().dead);
+        // An even share each, with the odd unit of the meal & reward going to the nearest
+        let (near_gain, far_gain) = (life(&app, near) - 1000, life(&app, far) - 1000);
+        assert_eq!((near_gain, far_gain), (51 + 6, 50 + 5));
+        assert_eq!(near_gain + far_gain, 101 + 11);
+    }
+
+    fn total_life(app: &mut App) -> i32 {
+        let world = app.world_mut();
+        world
+            .query::<&Life>()
+            .iter(world)
+            .map(|life| life.value)
+            .sum()
+    }
+
+    #[test]
+    fn conserving_births_dont_make_energy() {
+        let mut settings = test_settings();
+        settings.conserve_reproduction_energy = true;
+        let mut app = empty_app(settings);
+        mating_prey(&mut app);
+        let before = total_life(&mut app);
+
+        run(&mut app, handle_mating);
+
+        assert_eq!(prey_count(&mut app), 3);
+        assert!(total_life(&mut app) <= before);
+    }
+
+    // Three pairs of prey that all have a child on the same tick, and who was born where with
+    // which genes
+    fn simultaneous_births() -> Vec<(u64, f32, f32, f32)> {
+        let mut app = empty_app(test_settings());
+        let life = prey_mating_threshold(app.world().resource::<Settings>()) * 2;
+        for x in [-200.0, 0.0, 200.0] {
+            add_prey(&mut app, x, 0.0, life);
+            add_prey(&mut app, x, 0.0, life);
+        }
+
+        run(&mut app, (try_mate_prey, handle_mating).chain());
+
+        let world = app.world_mut();
+        let mut children: Vec<(u64, f32, f32, f32)> = world
+            .query::<(&StableId, &PositionSize, &Genes, &Parents)>()
+            .iter(world)
+            .filter(|(_, _, _, parents)| parents.generation > 0)
+            .map(|(id, position_size, genes, _)| {
+                (id.0, position_size.x, position_size.y, genes.speed)
+            })
+            .collect();
+        children.sort_by_key(|(id, ..)| *id);
+        children
+    }
+
+    #[test]
+    fn simultaneous_births_replay_the_same() {
+        let children = simultaneous_births();
+
+        assert_eq!(children.len(), 3);
+        assert_eq!(simultaneous_births(), children);
+    }
+
+    #[test]
+    fn births_wait_for_room_under_max_entities() {
+        let mut settings = test_settings();
+        settings.max_entities = 5;
+        let mut app = empty_app(settings);
+        let life = prey_mating_threshold(app.world().resource::<Settings>()) * 2;
+        for x in [-200.0, 200.0] {
+            add_prey(&mut app, x, 0.0, life);
+            add_prey(&mut app, x, 0.0, life);
+        }
+        run(&mut app, try_mate_prey);
+
+        // Only one of the pairs fits, and the other keeps waiting while it's full
+        run(&mut app, handle_mating);
+        assert_eq!(prey_count(&mut app), 5);
+        run(&mut app, handle_mating);
+        assert_eq!(prey_count(&mut app), 5);
+
+        // Until something dies
+        let world = app.world_mut();
+        let child = world
+            .query::<(Entity, &Parents)>()
+            .iter(world)
+            .find(|(_, parents)| parents.generation > 0)
+            .map(|(entity, _)| entity)
+            .unwrap();
+        world.despawn(child);
+        run(&mut app, handle_mating);
+        assert_eq!(prey_count(&mut app), 5);
+        assert_eq!(app.world().resource::<SimStats>().births, 2);
+    }
+
+    fn mates_with_life(settings: Settings, life: i32) -> bool {
+        let mut app = empty_app(settings);
+        add_prey(&mut app, 0.0, 0.0, life);
+        add_prey(&mut app, 0.0, 0.0, life);
+
+        run(&mut app, (try_mate_prey, handle_mating).chain());
+        prey_count(&mut app) == 3
+    }
+
+    #[test]
+    fn prey_need_the_margin_on_top_of_the_threshold() {
+        let mut settings = test_settings();
+        settings.breed_energy_margin = 100;
+        let threshold = mating_threshold(settings.prey_reproduction_energy, &settings);
+
+        assert!(!mates_with_life(settings.clone(), threshold));
+        assert!(!mates_with_life(settings.clone(), threshold + 99));
+        assert!(mates_with_life(settings, threshold + 100));
+    }
+
+    #[test]
+    fn parents_keep_the_reserve_after_mating() {
+        let mut settings = test_settings();
+        settings.post_mate_reserve = 500;
+        let bare = settings.prey_reproduction_energy;
+
+        assert!(!mates_with_life(settings.clone(), bare + 1));
+        assert!(!mates_with_life(settings.clone(), bare + 499));
+        assert!(mates_with_life(settings, bare + 500));
+    }
+
+    fn predator_count(app: &mut App) -> usize {
+        let world = app.world_mut();
+        world.query::<&Predator>().iter(world).count()
+    }
+
+    // Two well fed predators on the same spot that last ate at last_kill and have picked each
+    // other to mate with at tick 1000
+    fn mating_predators(app: &mut App, last_kill: u64) {
+        let settings = app.world().resource::<Settings>().clone();
+        let life = mating_threshold(settings.predator_reproduction_energy, &settings) * 2;
+        for _ in 0..2 {
+            let predator = add_predator(app, 0.0, 0.0, life);
+            app.world_mut().get_mut::<LastKill>(predator).unwrap().tick = Some(last_kill);
+        }
+
+        app.world_mut().resource_mut::<SimClock>().ticks = 999;
+        run(app, try_mate_predator);
+    }
+
+    fn recent_kill_settings() -> Settings {
+        let mut settings = test_settings();
+        settings.predator_requires_recent_kill = true;
+        settings.kill_recency_ticks = 100;
+        settings
+    }
+
+    fn have_mates(app: &mut App) -> Vec<bool> {
+        let world = app.world_mut();
+        world
+            .query_filtered::<&MatingTarget, With<Predator>>()
+            .iter(world)
+            .map(|target| target.entity.is_some())
+            .collect()
+    }
+
+    #[test]
+    fn predators_with_an_old_kill_dont_look_for_a_mate() {
+        let mut app = empty_app(recent_kill_settings());
+        mating_predators(&mut app, 800);
+        assert_eq!(have_mates(&mut app), [false, false]);
+
+        let mut app = empty_app(recent_kill_settings());
+        mating_predators(&mut app, 950);
+        assert_eq!(have_mates(&mut app), [true, true]);
+    }
+
+    #[test]
+    fn fleeing_prey_dont_mate() {
+        let mut app = empty_app(test_settings());
+        let (first, second) = mating_prey(&mut app);
+        for prey in [first, second] {
+            app.world_mut().get_mut::<Prey>(prey).unwrap().status = 2;
+        }
+
+        run(&mut app, handle_mating);
+
+        assert_eq!(prey_count(&mut app), 2);
+    }
+
+    #[test]
+    fn prey_that_ran_low_on_energy_dont_mate() {
+        let mut app = empty_app(test_settings());
+        let (first, second) = mating_prey(&mut app);
+        let threshold = prey_mating_threshold(app.world().resource::<Settings>());
+        for prey in [first, second] {
+            app.world_mut().get_mut::<Life>(prey).unwrap().value = threshold - 1;
+        }
+
+        run(&mut app, handle_mating);
+
+        assert_eq!(prey_count(&mut app), 2);
+    }
+
+    #[test]
+    fn hunting_predators_still_mate() {
+        let mut app = empty_app(test_settings());
+        mating_predators(&mut app, 950);
+        let world = app.world_mut();
+        for mut predator in world.query::<&mut Predator>().iter_mut(world) {
+            predator.status = 2;
+        }
+
+        run(&mut app, handle_mating);
+
+        assert_eq!(predator_count(&mut app), 3);
+    }
+
+    #[test]
+    fn predators_breed_while_their_kill_is_recent() {
+        let mut app = empty_app(recent_kill_settings());
+        mating_predators(&mut app, 950);
+
+        run(&mut app, handle_mating);
+
+        assert_eq!(predator_count(&mut app), 3);
+    }
+
+    #[test]
+    fn predators_whose_kill_went_stale_before_the_birth_dont_breed() {
+        let mut app = empty_app(recent_kill_settings());
+        mating_predators(&mut app, 950);
+
+        app.world_mut().resource_mut::<SimClock>().ticks = 1099;
+        run(&mut app, handle_mating);
+
+        assert_eq!(predator_count(&mut app), 2);
+    }
+
+    // A copy of Settings.toml with some settings changed, or taken out when they're None
+    fn settings_file_with(file_name: &str, changes: &[(&str, Option<&str>)]) -> String {
+        let mut lines: Vec<String> = std::fs::read_to_string("Settings.toml")
+            .unwrap()
+            .lines()
+            .filter(|line| {
+                let name = line.split('=').next().unwrap_or_default().trim();
+                !changes.iter().any(|(changed, _)| *changed == name)
+            })
+            .map(|line| line.to_string())
+            .collect();
+        for (name, value) in changes {
+            if let Some(value) = value {
+                lines.push(format!("{} = {}", name, value));
+            }
+        }
+
+        let path = std::env::temp_dir().join(file_name);
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn bad_text_settings_are_reported_by_name() {
+        for (name, value) in [
+            ("perturbations", "\"500 cull wolves 0.5\""),
+            ("interaction_matrix", "\"0>one\""),
+            ("cover_regions", "\"0,0,20\""),
+            ("burrow_regions", "\"0,0,20,0\""),
+            ("metrics_columns", "\"time,prey_cuont\""),
+        ] {
+            let path = settings_file_with(
+                &format!("predator_prey_bad_{}.toml", name),
+                &[(name, Some(value))],
+            );
+            let error = try_read_settings_from(&path).err().unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            assert!(error.starts_with(&format!("{}: ", name)), "{}", error);
+        }
+    }
+
+    #[test]
+    fn realistic_reproduction_only_fills_in_rules_that_arent_set() {
+        let read = |file_name: &str, changes: &[(&str, Option<&str>)]| {
+            let path = settings_file_with(file_name, changes);
+            let settings = try_read_settings_from(&path).unwrap();
+            let _ = std::fs::remove_file(&path);
+            (
+                settings.conserve_reproduction_energy,
+                settings.post_mate_reserve,
+            )
+        };
+
+        assert_eq!(read("predator_prey_unrealistic.toml", &[]), (false, 0));
+        assert_eq!(
+            read(
+                "predator_prey_realistic.toml",
+                &[("realistic_reproduction", Some("true"))]
+            ),
+            (true, REALISTIC_POST_MATE_RESERVE)
+        );
+        assert_eq!(
+            read(
+                "predator_prey_realistic_overridden.toml",
+                &[
+                    ("realistic_reproduction", Some("true")),
+                    ("conserve_reproduction_energy", Some("false")),
+                    ("post_mate_reserve", Some("0")),
+                ]
+            ),
+            (false, 0)
+        );
+    }
+}
@@ -0,0 +1,105 @@
+/*
+    This file includes all systems & child functions used for
+    reporting what the simulation is doing through the log, so runs can be followed without a debugger.
+
+    Examples:
+    - Functions that set up the log, filtered by the RUST_LOG environment variable
+    - Functions that log a summary of the settings a run started with
+    - Functions that log when a species dies out or comes back, marking extinctions on the plot
+*/
+
+use bevy::log::tracing_subscriber::{self, fmt::format::FmtSpan, EnvFilter};
+use bevy::prelude::*;
+
+use crate::annotations::{AnnotationKind, PlotAnnotations};
+use crate::position_systems::world_size;
+use crate::{Predator, Prey, Settings, SimClock};
+
+// What's logged when RUST_LOG isn't set, the same as Bevy's own default
+const DEFAULT_LOG_FILTER: &str = "info,wgpu=error,naga=warn";
+
+// The heaviest simulation systems are wrapped in debug spans under the "profiling" target, so
+// RUST_LOG=info,profiling=debug logs how long each one took every tick. They're filtered out by
+// default, which costs next to nothing when nobody's looking
+
+// Set up once for the whole program, before the settings are read, so both windowed and headless
+// runs log the same way. Bevy's LogPlugin is turned off in the window so it doesn't set up a second log
+pub fn init_logging() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+
+    if let Err(error) = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_span_events(FmtSpan::CLOSE)
+        .try_init()
+    {
+        eprintln!("Couldn't set up logging: {}", error);
+    }
+}
+
+pub fn log_settings_summary(settings: &Settings) {
+    info!(
+        "Starting with {} predators & {} prey in a {}x{} world, seed {}",
+        settings.predator_population,
+        settings.prey_population,
+        world_size(settings).x,
+        world_size(settings).y,
+        settings.seed
+    );
+    if !settings.layout_file.is_empty() {
+        info!(
+            "The starting creatures come from {} instead",
+            settings.layout_file
+        );
+    }
+    if settings.ensemble_runs > 0 {
+        info!(
+            "Running an ensemble of {} runs for {} ticks each into {}",
+            settings.ensemble_runs, settings.ensemble_ticks, settings.ensemble_output
+        );
+    }
+    debug!("Full settings: {:?}", settings.as_partial_reflect());
+}
+
+// Logged when each species dies out and if it ever comes back, not every frame it's gone
+pub fn log_extinctions(
+    prey_query: Query<(), With<Prey>>,
+    predator_query: Query<(), With<Predator>>,
+    clock: Res<SimClock>,
+    mut annotations: ResMut<PlotAnnotations>,
+    mut extinct: Local<Option<(bool, bool)>>,
+) {
+    let prey_extinct = prey_query.is_empty();
+    let predator_extinct = predator_query.is_empty();
+
+    // Nothing has spawned yet on the very first tick
+    let Some((was_prey_extinct, was_predator_extinct)) = *extinct else {
+        *extinct = Some((prey_extinct, predator_extinct));
+        return;
+    };
+
+    if prey_extinct && !was_prey_extinct {
+        warn!("Prey went extinct at tick {}", clock.ticks);
+        annotations.add(
+            clock.seconds,
+            AnnotationKind::Extinction,
+            "Prey went extinct".to_string(),
+        );
+    } else if !prey_extinct && was_prey_extinct {
+        info!("Prey are back at tick {}", clock.ticks);
+    }
+
+    if predator_extinct && !was_predator_extinct {
+        warn!("Predators went extinct at tick {}", clock.ticks);
+        annotations.add(
+            clock.seconds,
+            AnnotationKind::Extinction,
+            "Predators went extinct".to_string(),
+        );
+    } else if !predator_extinct && was_predator_extinct {
+        info!("Predators are back at tick {}", clock.ticks);
+    }
+
+    *extinct = Some((prey_extinct, predator_extinct));
+}
@@ -0,0 +1,408 @@
+/*
+    This file includes all systems & child functions used for
+    disturbing the simulation on a schedule, to see how the populations recover afterwards.
+
+    Examples:
+    - Functions that read the schedule of perturbations out of the settings
+    - Functions that cull creatures, spread disease, cut down the food & bring in new species at
+      the scheduled time
+    - Functions that log every perturbation & mark it on the population plot
+*/
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use std::str::FromStr;
+
+use crate::annotations::{AnnotationKind, PlotAnnotations};
+use crate::grass::GrassGrid;
+use crate::sim_api::Species;
+use crate::{
+    random_position, room_under_max_entities, spawn_predator, spawn_prey, Environment, Genes, Life,
+    Mortal, Predator, Prey, Settings, SimClock, SimRng, StableId,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PerturbationTime {
+    Tick(u64),
+    Seconds(f64),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PerturbationEffect {
+    // Kills this fraction of the species
+    Cull {
+        species: Species,
+        fraction: f32,
+    },
+    // Takes energy away from this fraction of the species
+    Disease {
+        species: Species,
+        fraction: f32,
+        energy: i32,
+    },
+    // Multiplies the food left in the environment & the grass by the fraction
+    FoodShock {
+        fraction: f32,
+    },
+    // Spawns this many of the species at random spots, like the creatures at the start
+    Introduce {
+        species: Species,
+        count: u32,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Perturbation {
+    pub time: PerturbationTime,
+    pub effect: PerturbationEffect,
+}
+
+fn parse_species(value: &str) -> Result<Species, String> {
+    match value.to_lowercase().as_str() {
+        "prey" => Ok(Species::Prey),
+        "predator" | "predators" => Ok(Species::Predator),
+        _ => Err(format!("Unknown species \"{}\"", value)),
+    }
+}
+
+fn parse_fraction(value: &str) -> Result<f32, String> {
+    let fraction = value
+        .parse::<f32>()
+        .map_err(|_| format!("\"{}\" isn't a number", value))?;
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(format!("{} has to be between 0 and 1", fraction));
+    }
+    Ok(fraction)
+}
+
+impl FromStr for PerturbationTime {
+    type Err = String;
+
+    // A plain number is a tick, a number ending in s is a time in seconds
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.strip_suffix('s') {
+            Some(seconds) => seconds
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| *seconds >= 0.0)
+                .map(PerturbationTime::Seconds)
+                .ok_or(format!("\"{}\" isn't a time in seconds", value)),
+            None => value
+                .parse::<u64>()
+                .map(PerturbationTime::Tick)
+                .map_err(|_| format!("\"{}\" isn't a tick", value)),
+        }
+    }
+}
+
+impl FromStr for Perturbation {
+    type Err = String;
+
+    // Written as "<when> <effect> <arguments>", for example "500 cull prey 0.5"
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let Some((when, rest)) = words.split_first() else {
+            return Err("Empty perturbation".to_string());
+        };
+        let time = when.parse::<PerturbationTime>()?;
+
+        let effect = match rest {
+            ["cull", species, fraction] => PerturbationEffect::Cull {
+                species: parse_species(species)?,
+                fraction: parse_fraction(fraction)?,
+            },
+            ["disease", species, fraction, energy] => PerturbationEffect::Disease {
+                species: parse_species(species)?,
+                fraction: parse_fraction(fraction)?,
+                energy: energy
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|energy| *energy >= 0)
+                    .ok_or(format!("\"{}\" isn't an amount of energy", energy))?,
+            },
+            ["food_shock", fraction] => PerturbationEffect::FoodShock {
+                fraction: parse_fraction(fraction)?,
+            },
+            ["introduce", species, count] => PerturbationEffect::Introduce {
+                species: parse_species(species)?,
+                count: count
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or(format!("\"{}\" isn't a number of creatures above 0", count))?,
+            },
+            _ => return Err(format!("Unknown perturbation \"{}\"", value)),
+        };
+
+        Ok(Perturbation { time, effect })
+    }
+}
+
+// Perturbations are separated by semicolons
+pub fn parse_perturbations(value: &str) -> Result<Vec<Perturbation>, String> {
+    value
+        .split(';')
+        .map(|perturbation| perturbation.trim())
+        .filter(|perturbation| !perturbation.is_empty())
+        .map(|perturbation| perturbation.parse::<Perturbation>())
+        .collect()
+}
+
+// The perturbations still waiting to happen, which SimApi can add to while the simulation runs
+#[derive(Resource, Default)]
+pub struct PerturbationSchedule {
+    pending: Vec<Perturbation>,
+}
+
+impl PerturbationSchedule {
+    // The settings are checked when they're read, so this never fails
+    pub fn new(settings: &Settings) -> Self {
+        PerturbationSchedule {
+            pending: parse_perturbations(&settings.perturbations).unwrap_or_default(),
+        }
+    }
+
+    pub fn add(&mut self, perturbation: Perturbation) {
+        self.pending.push(perturbation);
+    }
+}
+
+// Picks the creatures a perturbation hits. They're put in the order of their stable ids before
+// being shuffled, so the same seed always hits the same creatures
+fn pick_victims(mut creatures: Vec<(u64, Entity)>, fraction: f32, rng: &mut SimRng) -> Vec<Entity> {
+    creatures.sort_by_key(|(id, _)| *id);
+    creatures.shuffle(&mut rng.0);
+
+    let count = (creatures.len() as f32 * fraction).round() as usize;
+    creatures
+        .into_iter()
+        .take(count)
+        .map(|(_, entity)| entity)
+        .collect()
+}
+
+fn species_name(species: Species) -> &'static str {
+    match species {
+        Species::Prey => "prey",
+        Species::Predator => "predators",
+    }
+}
+
+type CreatureQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static StableId,
+        &'static mut Life,
+        &'static mut Mortal,
+        Has<Predator>,
+    ),
+    Or<(With<Prey>, With<Predator>)>,
+>;
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_perturbations(
+    mut commands: Commands,
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+    mut schedule: ResMut<PerturbationSchedule>,
+    mut annotations: ResMut<PlotAnnotations>,
+    mut rng: ResMut<SimRng>,
+    mut grass: ResMut<GrassGrid>,
+    mut creatures: CreatureQuery,
+    mut environment_query: Query<&mut Environment>,
+) {
+    let (due, pending): (Vec<Perturbation>, Vec<Perturbation>) =
+        std::mem::take(&mut schedule.pending)
+            .into_iter()
+            .partition(|perturbation| match perturbation.time {
+                PerturbationTime::Tick(tick) => clock.ticks >= tick,
+                PerturbationTime::Seconds(seconds) => clock.seconds >= seconds,
+            });
+    schedule.pending = pending;
+
+    for perturbation in due {
+        let description = match perturbation.effect {
+            PerturbationEffect::Cull { species, fraction } => {
+                let victims =
+                    pick_victims(species_members(&creatures, species), fraction, &mut rng);
+                for entity in victims.iter() {
+                    if let Ok((_, _, _, mut mortal, _)) = creatures.get_mut(*entity) {
+                        mortal.dead = true;
+                    }
+                }
+                format!("Culled {} {}", victims.len(), species_name(species))
+            }
+            PerturbationEffect::Disease {
+                species,
+                fraction,
+                energy,
+            } => {
+                let victims =
+                    pick_victims(species_members(&creatures, species), fraction, &mut rng);
+                for entity in victims.iter() {
+                    if let Ok((_, _, mut life, mut mortal, _)) = creatures.get_mut(*entity) {
+                        life.value -= energy;
+                        if life.value <= 0 {
+                            mortal.dead = true;
+                        }
+                    }
+                }
+                format!(
+                    "Disease took {} energy from {} {}",
+                    energy,
+                    victims.len(),
+                    species_name(species)
+                )
+            }
+            PerturbationEffect::FoodShock { fraction } => {
+                for mut environment in environment_query.iter_mut() {
+                    environment.energy_pool = (environment.energy_pool as f32 * fraction) as i32;
+                }
+                grass.scale(fraction, settings.grass_max_biomass);
+                format!("Food shock left {:.0}% of the food", fraction * 100.0)
+            }
+            PerturbationEffect::Introduce { species, count } => {
+                // Like births, introductions can't take the population past max_entities
+                let introduced =
+                    room_under_max_entities(&settings, creatures.iter().count(), count);
+
+                for _ in 0..introduced {
+                    let (x, y) = random_position(&mut rng, &settings);
+                    match species {
+                        Species::Prey => spawn_prey(
+                            &mut commands,
+                            &settings,
+                            x,
+                            y,
+                            settings.prey_life,
+                            Genes::starting_prey(&settings, &mut rng.0),
+                        ),
+                        Species::Predator => spawn_predator(
+                            &mut commands,
+                            &settings,
+                            x,
+                            y,
+                            settings.predator_life,
+                            Genes::starting_predator(&settings, &mut rng.0),
+                        ),
+                    };
+                }
+                if introduced < count {
+                    format!(
+                        "Introduced {} {}, {} more would have gone past max_entities",
+                        introduced,
+                        species_name(species),
+                        count - introduced
+                    )
+                } else {
+                    format!("Introduced {} {}", count, species_name(species))
+                }
+            }
+        };
+
+        info!(
+            "Perturbation at tick {} ({:.1}s): {}",
+            clock.ticks, clock.seconds, description
+        );
+        annotations.add(clock.seconds, AnnotationKind::Perturbation, description);
+    }
+}
+
+fn species_members(creatures: &CreatureQuery, species: Species) -> Vec<(u64, Entity)> {
+    creatures
+        .iter()
+        .filter(|(_, _, _, mortal, is_predator)| {
+            !mortal.dead && *is_predator == (species == Species::Predator)
+        })
+        .map(|(entity, id, _, _, _)| (id.0, entity))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_settings, PopulationMode, SimApi};
+
+    #[test]
+    fn introductions_stop_at_max_entities() {
+        let mut settings = read_settings();
+        settings.seed = 7;
+        settings.population_mode = PopulationMode::Absolute;
+        settings.prey_population = 20;
+        settings.predator_population = 0;
+        settings.max_entities = 25;
+
+        let mut api = SimApi::new(settings);
+        api.schedule_perturbation(Perturbation {
+            time: PerturbationTime::Tick(2),
+            effect: PerturbationEffect::Introduce {
+                species: Species::Prey,
+                count: 10,
+            },
+        });
+        api.step(5);
+
+        assert_eq!(api.snapshot().prey_count, 25);
+    }
+
+    #[test]
+    fn gene_immigrants_stop_at_max_entities() {
+        let mut settings = read_settings();
+        settings.seed = 7;
+        settings.population_mode = PopulationMode::Absolute;
+        settings.prey_population = 20;
+        settings.predator_population = 0;
+        settings.max_entities = 25;
+        settings.gene_immigration_rate = 1000.0;
+
+        let mut api = SimApi::new(settings);
+        api.step(5);
+
+        let snapshot = api.snapshot();
+        assert_eq!(snapshot.prey_count + snapshot.predator_count, 25);
+    }
+
+    #[test]
+    fn open_world_immigrants_stop_at_max_entities() {
+        let mut settings = read_settings();
+        settings.seed = 7;
+        settings.population_mode = PopulationMode::Absolute;
+        settings.prey_population = 20;
+        settings.predator_population = 0;
+        settings.max_entities = 25;
+        settings.open_world = true;
+        settings.immigration_rate = 1000.0;
+
+        let mut api = SimApi::new(settings);
+        api.step(5);
+
+        let snapshot = api.snapshot();
+        assert_eq!(snapshot.prey_count + snapshot.predator_count, 25);
+    }
+
+    #[test]
+    fn introductions_are_uncapped_without_max_entities() {
+        let mut settings = read_settings();
+        settings.seed = 7;
+        settings.population_mode = PopulationMode::Absolute;
+        settings.prey_population = 20;
+        settings.predator_population = 0;
+        settings.max_entities = 0;
+        // Out of reach, so no prey are born while it runs
+        settings.prey_reproduction_energy = settings.prey_life * 10;
+
+        let mut api = SimApi::new(settings);
+        api.schedule_perturbation(Perturbation {
+            time: PerturbationTime::Tick(2),
+            effect: PerturbationEffect::Introduce {
+                species: Species::Prey,
+                count: 10,
+            },
+        });
+        api.step(5);
+
+        assert_eq!(api.snapshot().prey_count, 30);
+    }
+}
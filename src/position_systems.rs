@@ -9,8 +9,16 @@
     - Functions that restrict entity movement to the bounds of the window
 */
 
-use bevy::prelude::*;
-use rand::Rng;
+use bevy::{ecs::query::QueryFilter, prelude::*, window::WindowResized};
+use rand::{rngs::StdRng, Rng};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::creature_rng::{creature_rng, CreatureRng};
+use crate::food_web::{InteractionMatrix, Layers, SpeciesId, PREDATOR_SPECIES, PREY_SPECIES};
+use crate::selection::BeingDragged;
+use crate::spatial_index::SpatialIndexes;
+use crate::{Life, Predator, Prey, Settings, SimRng, StableId};
 
 #[derive(Reflect, Component, Clone)]
 #[reflect(Component)]
@@ -21,37 +29,398 @@ pub struct PositionSize {
     pub height: f32,
 }
 
-pub fn is_colliding(entity1: &PositionSize, entity2: &PositionSize) -> bool {
+// Where a creature was at the start of the current simulation tick, so it can be drawn part way
+// between there and where it is now when frames are drawn more often than the simulation ticks
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct PreviousPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+// The direction a creature last moved in on purpose (not wiggling), in radians from +x
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Heading {
+    pub angle: f32,
+}
+
+impl Heading {
+    // Point along the movement between two positions, keeping the old heading if it didn't move
+    pub fn update(&mut self, from: Vec2, to: Vec2) {
+        let movement = to - from;
+        if movement.length_squared() > 0.0 {
+            self.angle = movement.y.atan2(movement.x);
+        }
+    }
+}
+
+// What creatures do when there's nothing to run from, hunt or mate with
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum IdleBehavior {
+    // Only the random wiggle everyone gets
+    Jitter,
+    // Wander smoothly, turning a little each tick
+    RandomWalk,
+    // Don't move at all, not even wiggling
+    StayPut,
+    // Walk back and forth between where they first went idle and a point nearby
+    Patrol,
+}
+
+impl FromStr for IdleBehavior {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "jitter" => Ok(IdleBehavior::Jitter),
+            "randomwalk" => Ok(IdleBehavior::RandomWalk),
+            "stayput" => Ok(IdleBehavior::StayPut),
+            "patrol" => Ok(IdleBehavior::Patrol),
+            _ => Err(format!("Unknown idle behavior \"{}\"", value)),
+        }
+    }
+}
+
+// How the way a creature wants to move each tick turns into how it actually moves
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum MovementModel {
+    // Moves exactly where it wants to go straight away
+    Instant,
+    // Has a velocity that turns towards where it wants to go by at most max_accel each tick,
+    // so it speeds up, slows down & turns smoothly instead of changing direction on the spot
+    Steered,
+}
+
+impl FromStr for MovementModel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "instant" => Ok(MovementModel::Instant),
+            "steered" => Ok(MovementModel::Steered),
+            _ => Err(format!("Unknown movement model \"{}\"", value)),
+        }
+    }
+}
+
+// What happens to a creature that goes past one edge of the world
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum EdgeBehavior {
+    // A wall that stops it at the edge
+    Clamp,
+    // Comes back in at the opposite edge, which has to wrap too
+    Wrap,
+    // Open, it leaves the simulation for good once it's cull_distance past the edge
+    Cull,
+}
+
+impl FromStr for EdgeBehavior {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "clamp" => Ok(EdgeBehavior::Clamp),
+            "wrap" => Ok(EdgeBehavior::Wrap),
+            "cull" => Ok(EdgeBehavior::Cull),
+            _ => Err(format!("Unknown edge behavior \"{}\"", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Edges {
+    pub left: EdgeBehavior,
+    pub right: EdgeBehavior,
+    pub bottom: EdgeBehavior,
+    pub top: EdgeBehavior,
+}
+
+impl Edges {
+    // An open world is culled on every side, whatever the edges are set to
+    pub fn new(settings: &Settings) -> Self {
+        if settings.open_world {
+            return Edges {
+                left: EdgeBehavior::Cull,
+                right: EdgeBehavior::Cull,
+                bottom: EdgeBehavior::Cull,
+                top: EdgeBehavior::Cull,
+            };
+        }
+
+        Edges {
+            left: settings.left_edge,
+            right: settings.right_edge,
+            bottom: settings.bottom_edge,
+            top: settings.top_edge,
+        }
+    }
+
+    pub fn any(&self, behavior: EdgeBehavior) -> bool {
+        [self.left, self.right, self.bottom, self.top].contains(&behavior)
+    }
+}
+
+// Wrapping only makes sense if a creature leaving one side has somewhere to come back in
+pub fn validate_edges(edges: &Edges) -> Result<(), String> {
+    if (edges.left == EdgeBehavior::Wrap) != (edges.right == EdgeBehavior::Wrap) {
+        return Err("left_edge and right_edge have to both wrap or neither wrap".to_string());
+    }
+    if (edges.bottom == EdgeBehavior::Wrap) != (edges.top == EdgeBehavior::Wrap) {
+        return Err("bottom_edge and top_edge have to both wrap or neither wrap".to_string());
+    }
+    Ok(())
+}
+
+// How far a creature moved in the last tick
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Velocity(pub Vec2);
+
+// The two points a creature walks between when patrolling, picked the first time it goes idle
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Patrol {
+    pub points: Option<(Vec2, Vec2)>,
+    pub heading_to_second: bool,
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum CollisionShape {
+    Aabb,
+    Circle,
+}
+
+impl FromStr for CollisionShape {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "aabb" => Ok(CollisionShape::Aabb),
+            "circle" => Ok(CollisionShape::Circle),
+            _ => Err(format!("Unknown collision shape \"{}\"", value)),
+        }
+    }
+}
+
+pub fn is_colliding(entity1: &PositionSize, entity2: &PositionSize, shape: CollisionShape) -> bool {
+    match shape {
+        CollisionShape::Aabb => is_colliding_aabb(entity1, entity2),
+        CollisionShape::Circle => is_colliding_circle(entity1, entity2),
+    }
+}
+
+pub fn is_colliding_aabb(entity1: &PositionSize, entity2: &PositionSize) -> bool {
     // Used this resource for intersections https://silentmatt.com/rectangle-intersection/
 
-    if entity1.x < entity2.x + entity2.width
+    // Rectangles that are only touching along an edge don't count as colliding
+    entity1.x < entity2.x + entity2.width
         && entity1.x + entity1.width > entity2.x
         && entity1.y < entity2.y + entity2.height
         && entity1.y + entity1.height > entity2.y
-    {
-        return true;
-    }
-    return false;
 }
 
-pub fn avoid(entity: &mut PositionSize, target: &PositionSize, speed: f32) {
+pub fn is_colliding_circle(entity1: &PositionSize, entity2: &PositionSize) -> bool {
+    // Measure from the middle of each box so the circle sits where the rectangle would be,
+    // with the circle fitting the larger side of the box
+    let center_x1 = entity1.x + entity1.width / 2.0;
+    let center_y1 = entity1.y + entity1.height / 2.0;
+    let center_x2 = entity2.x + entity2.width / 2.0;
+    let center_y2 = entity2.y + entity2.height / 2.0;
+
+    let radius1 = entity1.width.max(entity1.height) / 2.0;
+    let radius2 = entity2.width.max(entity2.height) / 2.0;
+
+    let distance = ((center_x1 - center_x2).powf(2.0) + (center_y1 - center_y2).powf(2.0)).sqrt();
+
+    // Just touching doesn't count, the same as the rectangle check
+    distance < radius1 + radius2
+}
+
+pub fn is_inside(entity: &PositionSize, region: &PositionSize) -> bool {
+    // Regions are measured from their center, unlike the entity rectangles
+    (entity.x - region.x).abs() <= region.width / 2.0
+        && (entity.y - region.y).abs() <= region.height / 2.0
+}
+
+// Entities closer together than this are treated as being on the same spot, where there's
+// no real direction between them (atan2(0, 0) would always point along +x)
+const COINCIDENT_DISTANCE: f32 = 0.001;
+
+fn is_coincident(entity: &PositionSize, target: &PositionSize) -> bool {
+    (target.x - entity.x).abs() < COINCIDENT_DISTANCE
+        && (target.y - entity.y).abs() < COINCIDENT_DISTANCE
+}
+
+pub fn avoid(entity: &mut PositionSize, target: &PositionSize, speed: f32, rng: &mut StdRng) {
     // This sweet answer obtained from
     // https://math.stackexchange.com/questions/707673/find-angle-in-degrees-from-one-point-to-another-in-2d-space
-    let angle = (target.y - entity.y).atan2(target.x - entity.x);
+    let mut angle = (target.y - entity.y).atan2(target.x - entity.x);
+
+    // Run off in a random direction when sitting right on top of what we're avoiding,
+    // so everyone doesn't escape the same way
+    if is_coincident(entity, target) {
+        angle = rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+    }
 
-    entity.x += angle.cos() * -1.0 * speed;
-    entity.y += angle.sin() * -1.0 * speed;
+    entity.x += -angle.cos() * speed;
+    entity.y += -angle.sin() * speed;
 }
 
 pub fn move_towards(entity: &mut PositionSize, target: &PositionSize, speed: f32) {
+    // Already there, so there's nowhere to move to
+    if is_coincident(entity, target) {
+        return;
+    }
+
     // This sweet answer obtained from
     // https://math.stackexchange.com/questions/707673/find-angle-in-degrees-from-one-point-to-another-in-2d-space
+    // This can overshoot the target when it's closer than speed
     let angle = (target.y - entity.y).atan2(target.x - entity.x);
 
     entity.x += angle.cos() * speed;
     entity.y += angle.sin() * speed;
 }
 
+// Moves speed along a direction, which doesn't need to be normalized
+pub fn move_in_direction(entity: &mut PositionSize, direction: Vec2, speed: f32) {
+    let direction = direction.normalize_or_zero();
+
+    entity.x += direction.x * speed;
+    entity.y += direction.y * speed;
+}
+
+// Moves an idle creature however the idle_behavior setting says to
+pub fn idle_move(
+    entity: &mut PositionSize,
+    heading: &Heading,
+    patrol: &mut Patrol,
+    speed: f32,
+    settings: &Settings,
+    rng: &mut StdRng,
+) {
+    match settings.idle_behavior {
+        // Handled by wiggle_squares & staying still
+        IdleBehavior::Jitter | IdleBehavior::StayPut => {}
+        IdleBehavior::RandomWalk => {
+            let max_turn = settings.random_walk_turn_degrees.to_radians();
+            let angle = heading.angle + rng.gen_range(-max_turn..=max_turn);
+            move_in_direction(entity, Vec2::from_angle(angle), speed);
+        }
+        IdleBehavior::Patrol => {
+            let position = Vec2::new(entity.x, entity.y);
+            let (first, second) = *patrol.points.get_or_insert_with(|| {
+                // Keep the second point inside the window so it can always be reached
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let half_size = world_size(settings) / 2.0;
+                let second = (position + Vec2::from_angle(angle) * settings.patrol_distance)
+                    .clamp(-half_size, half_size);
+                (position, second)
+            });
+
+            let target = if patrol.heading_to_second {
+                second
+            } else {
+                first
+            };
+
+            // Turn around once the point is reached, without overshooting it
+            let distance = position.distance(target);
+            if distance <= speed {
+                entity.x = target.x;
+                entity.y = target.y;
+                patrol.heading_to_second = !patrol.heading_to_second;
+            } else {
+                move_in_direction(entity, target - position, speed);
+            }
+        }
+    }
+}
+
+// Everything in the simulation is measured in world units, which are only turned into pixels when
+// drawn. This is how big the configured window is in world units
+pub fn world_size(settings: &Settings) -> Vec2 {
+    Vec2::new(settings.window_width, settings.window_height) / settings.pixels_per_unit
+}
+
+// The half width & height of the walled in world, which is the window when there is one
+pub fn world_half_size<F: QueryFilter>(windows: &Query<&Window, F>, settings: &Settings) -> Vec2 {
+    match windows.get_single() {
+        Ok(window) => Vec2::new(window.width(), window.height()) / settings.pixels_per_unit / 2.0,
+        Err(_) => world_size(settings) / 2.0,
+    }
+}
+
+// Bends a creature's movement this tick away from any wall it's within wall_margin of, more so the
+// closer it is, so it curves along the wall instead of running into it and getting pinned.
+// It only turns the creature, it doesn't change how far it moved
+pub fn steer_from_walls(
+    start: Vec2,
+    entity: &mut PositionSize,
+    half_size: Vec2,
+    settings: &Settings,
+) {
+    let edges = Edges::new(settings);
+    if !settings.wall_avoidance || !edges.any(EdgeBehavior::Clamp) || settings.wall_margin <= 0.0 {
+        return;
+    }
+
+    let movement = Vec2::new(entity.x, entity.y) - start;
+    let distance = movement.length();
+    if distance <= 0.0 {
+        return;
+    }
+
+    // How far into the margin the creature is on a side, from 0 at its inner edge to 1 at the wall.
+    // Only walls are steered away from, the other edges can be walked through
+    let closeness = |distance_to_wall: f32, edge: EdgeBehavior| {
+        if edge != EdgeBehavior::Clamp {
+            return 0.0;
+        }
+        ((settings.wall_margin - distance_to_wall) / settings.wall_margin).clamp(0.0, 1.0)
+    };
+    let push = Vec2::new(
+        closeness(start.x + half_size.x, edges.left)
+            - closeness(half_size.x - start.x, edges.right),
+        closeness(start.y + half_size.y, edges.bottom)
+            - closeness(half_size.y - start.y, edges.top),
+    );
+    if push == Vec2::ZERO {
+        return;
+    }
+
+    let direction = movement / distance + push * settings.wall_avoidance_strength;
+    let end = start + direction.normalize_or_zero() * distance;
+
+    entity.x = end.x;
+    entity.y = end.y;
+}
+
+// Everything that decides where a creature goes moves it straight there. With the steered
+// movement model that's only where it wants to go, so this moves it back to its start and then
+// along its velocity, after turning the velocity towards the movement it wanted
+pub fn apply_velocity(
+    start: Vec2,
+    entity: &mut PositionSize,
+    velocity: &mut Velocity,
+    max_speed: f32,
+    settings: &Settings,
+) {
+    let desired = Vec2::new(entity.x, entity.y) - start;
+
+    if settings.movement_model == MovementModel::Instant {
+        velocity.0 = desired;
+        return;
+    }
+
+    let change = (desired - velocity.0).clamp_length_max(settings.max_accel);
+    velocity.0 = (velocity.0 + change).clamp_length_max(max_speed);
+
+    let end = start + velocity.0;
+    entity.x = end.x;
+    entity.y = end.y;
+}
+
 pub fn in_detection_range(
     entity1: &PositionSize,
     entity2: &PositionSize,
@@ -60,40 +429,522 @@ pub fn in_detection_range(
     // Formula from https://www.calculator.net/distance-calculator.html
     let distance = ((entity1.x - entity2.x).powf(2.0) + (entity1.y - entity2.y).powf(2.0)).sqrt();
 
-    return (distance <= detection_range, distance);
+    // Something exactly at the edge of the range is still detected
+    (distance <= detection_range, distance)
 }
 
-pub fn wiggle_squares(_time: Res<Time>, mut query: Query<&mut PositionSize>) {
-    for mut position_size in query.iter_mut() {
-        let random_x: f32 = rand::thread_rng().gen_range(-1.0..1.0);
-        let random_y: f32 = rand::thread_rng().gen_range(-1.0..1.0);
+// How the chance of noticing something falls off with distance under probabilistic_detection
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum DetectionFalloff {
+    Linear,
+    InverseSquare,
+}
 
-        let wiggle_amount = Vec2::from_array((random_x, random_y).into());
+impl FromStr for DetectionFalloff {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "linear" => Ok(DetectionFalloff::Linear),
+            "inverse_square" => Ok(DetectionFalloff::InverseSquare),
+            _ => Err(format!("Unknown detection falloff \"{}\"", value)),
+        }
+    }
+}
+
+// The chance of noticing something this far away. Anything within detection_certain_fraction of
+// the range is always noticed, further out it falls to nothing at the edge of the range when
+// linear, or to detection_certain_fraction squared at the edge when inverse square
+pub fn detection_chance(distance: f32, detection_range: f32, settings: &Settings) -> f32 {
+    let certain_range = detection_range * settings.detection_certain_fraction;
+    if distance <= certain_range {
+        return 1.0;
+    }
+    if distance > detection_range {
+        return 0.0;
+    }
+
+    match settings.detection_falloff {
+        DetectionFalloff::Linear => {
+            1.0 - (distance - certain_range) / (detection_range - certain_range)
+        }
+        DetectionFalloff::InverseSquare => (certain_range / distance).powi(2),
+    }
+}
+
+// Rolls whether something already in range is noticed, which always succeeds unless
+// probabilistic_detection is on. Without random numbers to roll with it's always noticed
+pub fn roll_detection(
+    distance: f32,
+    detection_range: f32,
+    settings: &Settings,
+    rng: Option<&mut StdRng>,
+) -> bool {
+    if !settings.probabilistic_detection {
+        return true;
+    }
+    let Some(rng) = rng else {
+        return true;
+    };
+
+    rng.gen::<f32>() < detection_chance(distance, detection_range, settings)
+}
+
+// The same as in_detection_range, but only sees things within fov_degrees either side of
+// where the entity is heading. A fov of 180 degrees or more sees all the way around
+pub fn in_detection_cone(
+    entity: &PositionSize,
+    heading: &Heading,
+    target: &PositionSize,
+    detection_range: f32,
+    fov_degrees: f32,
+) -> (bool, f32) {
+    let (detected, distance) = in_detection_range(entity, target, detection_range);
+    if !detected || fov_degrees >= 180.0 || distance == 0.0 {
+        return (detected, distance);
+    }
+
+    let facing = Vec2::from_angle(heading.angle);
+    let direction = Vec2::new(target.x - entity.x, target.y - entity.y) / distance;
+
+    (
+        facing.dot(direction) >= fov_degrees.to_radians().cos(),
+        distance,
+    )
+}
+
+// Only creatures wiggle
+#[allow(clippy::type_complexity)]
+pub fn wiggle_squares(
+    _time: Res<Time>,
+    mut query: Query<
+        (
+            &mut PositionSize,
+            Option<&Prey>,
+            Option<&Predator>,
+            Option<&mut CreatureRng>,
+        ),
+        // Cover, burrows & anything else placed in the world stay put, and so does anything
+        // being dragged with the mouse
+        (Or<(With<Prey>, With<Predator>)>, Without<BeingDragged>),
+    >,
+    settings: Res<Settings>,
+    mut shared_rng: ResMut<SimRng>,
+) {
+    for (mut position_size, prey, predator, own_rng) in query.iter_mut() {
+        // Frozen prey hold perfectly still, that's the point of freezing
+        if prey.is_some_and(|prey| prey.status == 6) {
+            continue;
+        }
+
+        // Idle creatures only wiggle when jittering is their idle behavior
+        // Wary prey carry on as if idle
+        let idle = prey
+            .map(|prey| prey.status == 0 || prey.status == 5)
+            .unwrap_or(false)
+            || predator
+                .map(|predator| predator.status == 0)
+                .unwrap_or(false);
+        if idle && settings.idle_behavior != IdleBehavior::Jitter {
+            continue;
+        }
+
+        // A random direction with a fixed length, as separate random x & y offsets wiggle
+        // further along the diagonals than along the axes
+        let rng = match own_rng {
+            Some(own_rng) => creature_rng(&mut shared_rng, own_rng.into_inner(), &settings),
+            None => &mut shared_rng.0,
+        };
+        let angle: f32 = rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI);
+
+        // How far it wiggles each tick depends on its species
+        let wiggle_distance = if prey.is_some() {
+            settings.prey_wiggle
+        } else {
+            settings.predator_wiggle
+        };
+        let wiggle_amount = Vec2::from_angle(angle) * wiggle_distance;
 
         position_size.x += wiggle_amount.x;
         position_size.y += wiggle_amount.y;
     }
 }
 
-pub fn window_collision(mut query: Query<&mut PositionSize>, windows: Query<&Window>) {
-    let window = windows.get_single().unwrap();
-    let window_width: f32 = window.width();
-    let window_height: f32 = window.height();
+#[allow(clippy::type_complexity)]
+pub fn separate_creatures(
+    mut query: Query<
+        (Entity, &mut PositionSize, &SpeciesId, &Layers),
+        (Or<(With<Prey>, With<Predator>)>, Without<BeingDragged>),
+    >,
+    indexes: Res<SpatialIndexes>,
+    matrix: Res<InteractionMatrix>,
+    settings: Res<Settings>,
+) {
+    let _span = debug_span!(target: "profiling", "separate_creatures").entered();
+
+    if !settings.separation_enabled || settings.separation_strength <= 0.0 {
+        return;
+    }
+
+    // Work out every push from where everyone is before anyone moves,
+    // so it doesn't matter what order the creatures are gone through in
+    let positions: HashMap<Entity, (PositionSize, SpeciesId, Layers)> = query
+        .iter()
+        .map(|(entity, position_size, species, layers)| {
+            (entity, (position_size.clone(), *species, *layers))
+        })
+        .collect();
+
+    let mut nearby = Vec::new();
+    let mut pushes: Vec<(Entity, Vec2)> = Vec::new();
+
+    for (entity, position_size, species, layers) in query.iter() {
+        let center = Vec2::new(position_size.x, position_size.y);
+        let size = position_size.width.max(position_size.height);
+
+        nearby.clear();
+        if layers.collision_mask & PREY_SPECIES.bit() != 0 {
+            indexes.prey.query_radius(center, size, &mut nearby);
+        }
+        if layers.collision_mask & PREDATOR_SPECIES.bit() != 0 {
+            indexes.predators.query_radius(center, size, &mut nearby);
+        }
+
+        let mut push = Vec2::ZERO;
+        for other_entity in nearby.iter() {
+            if *other_entity == entity {
+                continue;
+            }
+            let Some((other, other_species, other_layers)) = positions.get(other_entity) else {
+                continue;
+            };
+
+            // Only push apart creatures that can touch, and never a meal from what eats it,
+            // as predators still need to reach their prey
+            if !Layers::collide((layers, *species), (other_layers, *other_species))
+                || matrix.efficiency(*species, *other_species).is_some()
+                || matrix.efficiency(*other_species, *species).is_some()
+            {
+                continue;
+            }
+
+            let offset = center - Vec2::new(other.x, other.y);
+            let overlap = (size + other.width.max(other.height)) / 2.0 - offset.length();
+            if overlap <= 0.0 {
+                continue;
+            }
+
+            // Creatures sitting exactly on top of each other have no direction between them,
+            // so split them apart sideways with the lower entity going left
+            let direction = if offset.length() > 0.0 {
+                offset.normalize()
+            } else if entity < *other_entity {
+                Vec2::NEG_X
+            } else {
+                Vec2::X
+            };
+
+            // Each of the pair moves half the overlap, so together they just stop overlapping
+            push += direction * overlap / 2.0 * settings.separation_strength;
+        }
+
+        if push != Vec2::ZERO {
+            pushes.push((entity, push));
+        }
+    }
+
+    for (entity, push) in pushes {
+        if let Ok((_, mut position_size, _, _)) = query.get_mut(entity) {
+            position_size.x += push.x;
+            position_size.y += push.y;
+        }
+    }
+}
+
+pub fn clamp_to_bounds(position_size: &mut PositionSize, width: f32, height: f32) {
+    position_size.x = position_size.x.min(width / 2.0);
+    position_size.x = position_size.x.max(width / -2.0);
+
+    position_size.y = position_size.y.min(height / 2.0);
+    position_size.y = position_size.y.max(height / -2.0);
+}
+
+// Where a creature that's gone past the low or high edge of one axis ends up. Culled edges leave
+// it where it is, for cull_emigrants to deal with
+fn wrap_or_clamp(value: f32, size: f32, low: EdgeBehavior, high: EdgeBehavior) -> f32 {
+    let half = size / 2.0;
+    if value > half {
+        match high {
+            EdgeBehavior::Clamp => half,
+            EdgeBehavior::Wrap => value - size,
+            EdgeBehavior::Cull => value,
+        }
+    } else if value < -half {
+        match low {
+            EdgeBehavior::Clamp => -half,
+            EdgeBehavior::Wrap => value + size,
+            EdgeBehavior::Cull => value,
+        }
+    } else {
+        value
+    }
+}
+
+// Whether wrap_or_clamp would move the value to the other side, instead of just up to the edge
+fn wraps(value: f32, size: f32, low: EdgeBehavior, high: EdgeBehavior) -> bool {
+    let half = size / 2.0;
+    (value > half && high == EdgeBehavior::Wrap) || (value < -half && low == EdgeBehavior::Wrap)
+}
+
+// Keeps one creature inside the world. A creature that wraps around is drawn straight on the other
+// side, instead of sliding all the way across the world between two ticks
+fn keep_inside(
+    position_size: &mut PositionSize,
+    previous_position: Option<&mut PreviousPosition>,
+    world_size: Vec2,
+    edges: &Edges,
+) {
+    let wrapped = wraps(position_size.x, world_size.x, edges.left, edges.right)
+        || wraps(position_size.y, world_size.y, edges.bottom, edges.top);
+
+    position_size.x = wrap_or_clamp(position_size.x, world_size.x, edges.left, edges.right);
+    position_size.y = wrap_or_clamp(position_size.y, world_size.y, edges.bottom, edges.top);
+
+    if let (true, Some(previous_position)) = (wrapped, previous_position) {
+        previous_position.x = position_size.x;
+        previous_position.y = position_size.y;
+    }
+}
+
+// Only creatures are kept inside the window. Cover, burrows & anything else placed in the world
+// are left wherever they were put
+#[allow(clippy::type_complexity)]
+pub fn window_collision(
+    mut query: Query<
+        (&mut PositionSize, Option<&mut PreviousPosition>),
+        Or<(With<Prey>, With<Predator>)>,
+    >,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+) {
+    // Creatures far past a culled edge are culled instead
+    let edges = Edges::new(&settings);
+    if !edges.any(EdgeBehavior::Clamp) && !edges.any(EdgeBehavior::Wrap) {
+        return;
+    }
+
+    // Without a window (like when running headless) the configured size is used as the bounds
+    let world_size = world_half_size(&windows, &settings) * 2.0;
+
+    // A minimized window has no size, so don't squash everything into the middle
+    if world_size.x <= 0.0 || world_size.y <= 0.0 {
+        return;
+    }
+
+    for (mut position_size, mut previous_position) in query.iter_mut() {
+        keep_inside(
+            &mut position_size,
+            previous_position.as_deref_mut(),
+            world_size,
+            &edges,
+        );
+    }
+}
+
+// A debugging aid for numerical problems: any creature whose position has become NaN or
+// infinite is logged along with what it was, then put back in the world or removed.
+// This checks everything with a position, not just creatures, as anything could go wrong
+#[allow(clippy::type_complexity)]
+pub fn validate_positions(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &mut PositionSize,
+        Option<&Life>,
+        Option<&StableId>,
+        Has<Prey>,
+        Has<Predator>,
+    )>,
+    windows: Query<&Window>,
+    settings: Res<Settings>,
+) {
+    if !settings.validate_positions {
+        return;
+    }
+
+    let world_size = world_half_size(&windows, &settings) * 2.0;
+
+    for (entity, mut position_size, life, stable_id, is_prey, is_predator) in query.iter_mut() {
+        if position_size.x.is_finite() && position_size.y.is_finite() {
+            continue;
+        }
+
+        let kind = if is_prey {
+            "prey"
+        } else if is_predator {
+            "predator"
+        } else {
+            "entity"
+        };
+        // Creatures are named by their stable id, as their entity can be reused once they're gone
+        let name = match stable_id {
+            Some(stable_id) => stable_id.0.to_string(),
+            None => entity.to_string(),
+        };
+        error!(
+            "{} {} has an invalid position ({}, {}) with size {}x{} and life {:?}",
+            kind,
+            name,
+            position_size.x,
+            position_size.y,
+            position_size.width,
+            position_size.height,
+            life.map(|life| life.value)
+        );
+
+        if settings.despawn_invalid_positions {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // NaN has no sensible place to go so it's put in the middle,
+        // while infinity gets pushed back to the nearest edge
+        if position_size.x.is_nan() {
+            position_size.x = 0.0;
+        }
+        if position_size.y.is_nan() {
+            position_size.y = 0.0;
+        }
+        clamp_to_bounds(&mut position_size, world_size.x, world_size.y);
+    }
+}
+
+// Like window_collision, only creatures are moved. The world's structures are placed from the
+// settings so they stay where they were configured
+#[allow(clippy::type_complexity)]
+pub fn handle_window_resize(
+    mut resize_events: EventReader<WindowResized>,
+    mut query: Query<
+        (&mut PositionSize, Option<&mut PreviousPosition>),
+        Or<(With<Prey>, With<Predator>)>,
+    >,
+    mut previous_size: Local<Option<Vec2>>,
+    settings: Res<Settings>,
+) {
+    // Only the latest size matters if the window was resized several times this frame
+    let Some(resized) = resize_events.read().last() else {
+        return;
+    };
+
+    let new_size = Vec2::new(resized.width, resized.height) / settings.pixels_per_unit;
+    if new_size.x <= 0.0 || new_size.y <= 0.0 {
+        return;
+    }
+
+    // The window starts at the configured size, so use that until we've seen a resize
+    let old_size = previous_size.unwrap_or(world_size(&settings));
+    *previous_size = Some(new_size);
+
+    if new_size == old_size {
+        return;
+    }
+
+    // The edges apply to the new size just like they do every tick, so wrapping edges keep
+    // wrapping and culled ones leave creatures outside for cull_emigrants
+    let edges = Edges::new(&settings);
+
+    for (mut position_size, mut previous_position) in query.iter_mut() {
+        // Stretch or squash everyone's position along with the window if enabled
+        if settings.rescale_on_resize && old_size.x > 0.0 && old_size.y > 0.0 {
+            position_size.x *= new_size.x / old_size.x;
+            position_size.y *= new_size.y / old_size.y;
+        }
+
+        keep_inside(
+            &mut position_size,
+            previous_position.as_deref_mut(),
+            new_size,
+            &edges,
+        );
+    }
+}
+
+// How much bigger or smaller than default_dimensions a creature is drawn for its energy,
+// from energy_scale_min with none left up to energy_scale_max with a full life or more
+pub fn energy_scale(life: i32, full_life: i32, settings: &Settings) -> f32 {
+    let fullness = if full_life > 0 {
+        (life as f32 / full_life as f32).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+
+    settings.energy_scale_min + (settings.energy_scale_max - settings.energy_scale_min) * fullness
+}
+
+// When the energy scale also applies to collisions, it's done by resizing the creatures themselves,
+// so hungry creatures are harder to catch and to mate with
+#[allow(clippy::type_complexity)]
+pub fn scale_collision_by_energy(
+    mut query: Query<(&mut PositionSize, &Life, Has<Predator>), Or<(With<Prey>, With<Predator>)>>,
+    settings: Res<Settings>,
+) {
+    if !settings.scale_by_energy || !settings.energy_scale_collision {
+        return;
+    }
 
-    for mut position_size in query.iter_mut() {
-        position_size.x = position_size.x.min(window_width / 2.0);
-        position_size.x = position_size.x.max(window_width / -2.0);
+    for (mut position_size, life, is_predator) in query.iter_mut() {
+        let full_life = if is_predator {
+            settings.predator_life
+        } else {
+            settings.prey_life
+        };
+        let size = settings.default_dimensions * energy_scale(life.value, full_life, &settings);
 
-        position_size.y = position_size.y.min(window_height / 2.0);
-        position_size.y = position_size.y.max(window_height / -2.0);
+        position_size.width = size;
+        position_size.height = size;
     }
 }
 
-pub fn update_transform(mut query: Query<(&PositionSize, &mut Transform, &mut Sprite)>) {
-    for (position_size, mut transform, mut sprite) in query.iter_mut() {
+// Every entity that's drawn between ticks, which is only creatures as nothing else moves
+pub fn record_previous_positions(mut query: Query<(&PositionSize, &mut PreviousPosition)>) {
+    for (position_size, mut previous_position) in query.iter_mut() {
+        previous_position.x = position_size.x;
+        previous_position.y = position_size.y;
+    }
+}
+
+// Unlike the systems moving things around, this syncs every entity that's drawn, creatures and
+// the world's structures alike. Transforms stay in world units too, the camera's projection is
+// what scales them up by pixels_per_unit
+pub fn update_transform(
+    mut query: Query<(
+        &PositionSize,
+        Option<&PreviousPosition>,
+        &mut Transform,
+        &mut Sprite,
+    )>,
+    fixed_time: Res<Time<Fixed>>,
+    settings: Res<Settings>,
+) {
+    // How far the next simulation tick has got, used to smooth movement over the frames between
+    // ticks. Without a fixed timestep the simulation ticks every frame so there's nothing to smooth
+    let interpolation = if settings.fixed_timestep_hz > 0.0 {
+        fixed_time.overstep_fraction()
+    } else {
+        1.0
+    };
+
+    for (position_size, previous_position, mut transform, mut sprite) in query.iter_mut() {
         // Make sure the transform components line up with their entities position
-        transform.translation.x = position_size.x;
-        transform.translation.y = position_size.y;
+        let current = Vec2::new(position_size.x, position_size.y);
+        let drawn = match previous_position {
+            Some(previous_position) => {
+                Vec2::new(previous_position.x, previous_position.y).lerp(current, interpolation)
+            }
+            None => current,
+        };
+        transform.translation.x = drawn.x;
+        transform.translation.y = drawn.y;
 
         // Shouldn't be used regularly, but if the size of PositionSize changes, it will be updated in the sprite
         sprite.custom_size = Some(Vec2::new(
@@ -102,3 +953,333 @@ pub fn update_transform(mut query: Query<(&PositionSize, &mut Transform, &mut Sp
         ));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn creature(x: f32, y: f32) -> PositionSize {
+        PositionSize {
+            x,
+            y,
+            width: 10.0,
+            height: 10.0,
+        }
+    }
+
+    fn distance(entity1: &PositionSize, entity2: &PositionSize) -> f32 {
+        in_detection_range(entity1, entity2, 0.0).1
+    }
+
+    #[test]
+    fn rectangles_collide_only_when_they_overlap() {
+        let entity = creature(0.0, 0.0);
+
+        assert!(!is_colliding(
+            &entity,
+            &creature(20.0, 0.0),
+            CollisionShape::Aabb
+        ));
+        assert!(!is_colliding(
+            &entity,
+            &creature(10.0, 0.0),
+            CollisionShape::Aabb
+        ));
+        assert!(!is_colliding(
+            &entity,
+            &creature(0.0, -10.0),
+            CollisionShape::Aabb
+        ));
+        assert!(is_colliding(
+            &entity,
+            &creature(9.0, 0.0),
+            CollisionShape::Aabb
+        ));
+        assert!(is_colliding(
+            &entity,
+            &creature(-5.0, 5.0),
+            CollisionShape::Aabb
+        ));
+        assert!(is_colliding(
+            &entity,
+            &creature(0.0, 0.0),
+            CollisionShape::Aabb
+        ));
+    }
+
+    #[test]
+    fn circles_collide_only_when_they_overlap() {
+        let entity = creature(0.0, 0.0);
+
+        assert!(!is_colliding(
+            &entity,
+            &creature(20.0, 0.0),
+            CollisionShape::Circle
+        ));
+        assert!(!is_colliding(
+            &entity,
+            &creature(10.0, 0.0),
+            CollisionShape::Circle
+        ));
+        assert!(!is_colliding(
+            &entity,
+            &creature(6.0, 8.0),
+            CollisionShape::Circle
+        ));
+        assert!(is_colliding(
+            &entity,
+            &creature(9.0, 0.0),
+            CollisionShape::Circle
+        ));
+        assert!(is_colliding(
+            &entity,
+            &creature(6.0, -7.9),
+            CollisionShape::Circle
+        ));
+        assert!(is_colliding(
+            &entity,
+            &creature(0.0, 0.0),
+            CollisionShape::Circle
+        ));
+    }
+
+    #[test]
+    fn circles_leave_out_the_corners_of_the_boxes() {
+        let entity = creature(0.0, 0.0);
+        let diagonal = creature(8.0, 8.0);
+
+        assert!(is_colliding(&entity, &diagonal, CollisionShape::Aabb));
+        assert!(!is_colliding(&entity, &diagonal, CollisionShape::Circle));
+    }
+
+    #[test]
+    fn moving_towards_gets_closer_and_avoiding_gets_further() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let target = creature(30.0, -40.0);
+
+        for start in [(0.0, 0.0), (100.0, 0.0), (-20.0, 60.0), (30.0, -45.0)] {
+            let mut towards = creature(start.0, start.1);
+            let before = distance(&towards, &target);
+            move_towards(&mut towards, &target, 2.0);
+            assert!(distance(&towards, &target) < before);
+
+            let mut away = creature(start.0, start.1);
+            avoid(&mut away, &target, 2.0, &mut rng);
+            assert!(distance(&away, &target) > before);
+        }
+    }
+
+    #[test]
+    fn detection_includes_the_edge_of_the_range() {
+        let entity = creature(0.0, 0.0);
+
+        assert_eq!(
+            in_detection_range(&entity, &creature(30.0, 40.0), 50.0),
+            (true, 50.0)
+        );
+        assert!(!in_detection_range(&entity, &creature(30.0, 40.1), 50.0).0);
+        assert_eq!(
+            in_detection_range(&entity, &creature(0.0, 0.0), 0.0),
+            (true, 0.0)
+        );
+    }
+
+    #[test]
+    fn coincident_creatures_dont_all_go_the_same_way() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let target = creature(5.0, 5.0);
+
+        // Already there, so it stays put instead of heading off along +x
+        let mut towards = creature(5.0, 5.0);
+        move_towards(&mut towards, &target, 2.0);
+        assert_eq!((towards.x, towards.y), (5.0, 5.0));
+
+        // Avoiding still moves the full speed away, but not always along +x
+        let mut directions = Vec::new();
+        for _ in 0..10 {
+            let mut away = creature(5.0, 5.0);
+            avoid(&mut away, &target, 2.0, &mut rng);
+            assert!((distance(&away, &target) - 2.0).abs() < 0.001);
+            directions.push(((away.x - 5.0) * 1000.0).round() as i32);
+        }
+        directions.dedup();
+        assert!(directions.len() > 1);
+    }
+
+    #[test]
+    fn avoiding_from_the_same_spot_has_no_favorite_direction() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let target = creature(5.0, 5.0);
+        let mut total = Vec2::ZERO;
+        let mut quadrants = [0; 4];
+
+        for _ in 0..1000 {
+            let mut away = creature(5.0, 5.0);
+            avoid(&mut away, &target, 2.0, &mut rng);
+            let moved = Vec2::new(away.x - 5.0, away.y - 5.0);
+
+            assert!((moved.length() - 2.0).abs() < 0.001);
+            total += moved;
+            quadrants[(moved.x > 0.0) as usize * 2 + (moved.y > 0.0) as usize] += 1;
+        }
+
+        // Going every which way, the moves nearly cancel out
+        assert!((total / 1000.0).length() < 0.2, "{}", total / 1000.0);
+        assert!(
+            quadrants.iter().all(|count| *count > 200),
+            "{:?}",
+            quadrants
+        );
+    }
+
+    // How many of 2000 rolls notice something at each distance, for a detection range of 100
+    fn detections(falloff: DetectionFalloff, distances: &[f32]) -> Vec<usize> {
+        let mut settings = crate::read_settings();
+        settings.probabilistic_detection = true;
+        settings.detection_falloff = falloff;
+        settings.detection_certain_fraction = 0.25;
+        let mut rng = StdRng::seed_from_u64(1);
+
+        distances
+            .iter()
+            .map(|distance| {
+                (0..2000)
+                    .filter(|_| roll_detection(*distance, 100.0, &settings, Some(&mut rng)))
+                    .count()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detection_gets_less_likely_with_distance() {
+        for falloff in [DetectionFalloff::Linear, DetectionFalloff::InverseSquare] {
+            let counts = detections(falloff, &[10.0, 25.0, 40.0, 60.0, 80.0, 99.0, 101.0]);
+
+            assert_eq!(counts[..2], [2000, 2000], "{:?}", falloff);
+            assert!(
+                counts[1..6].windows(2).all(|pair| pair[0] > pair[1]),
+                "{:?}: {:?}",
+                falloff,
+                counts
+            );
+            assert_eq!(counts[6], 0, "{:?}", falloff);
+        }
+    }
+
+    #[test]
+    fn detection_rolls_match_the_chance() {
+        // Linear falloff from 25 to 100 gives a 60% chance at 55
+        let counts = detections(DetectionFalloff::Linear, &[55.0]);
+        assert!((1100..=1300).contains(&counts[0]), "{}", counts[0]);
+    }
+
+    fn edges(horizontal: EdgeBehavior, vertical: EdgeBehavior) -> Edges {
+        Edges {
+            left: horizontal,
+            right: horizontal,
+            bottom: vertical,
+            top: vertical,
+        }
+    }
+
+    #[test]
+    fn wrap_or_clamp_leaves_values_inside_alone() {
+        for behavior in [EdgeBehavior::Clamp, EdgeBehavior::Wrap, EdgeBehavior::Cull] {
+            assert_eq!(wrap_or_clamp(30.0, 100.0, behavior, behavior), 30.0);
+            assert_eq!(wrap_or_clamp(50.0, 100.0, behavior, behavior), 50.0);
+            assert_eq!(wrap_or_clamp(-50.0, 100.0, behavior, behavior), -50.0);
+        }
+    }
+
+    #[test]
+    fn wrap_or_clamp_handles_each_edge() {
+        let (clamp, wrap, cull) = (EdgeBehavior::Clamp, EdgeBehavior::Wrap, EdgeBehavior::Cull);
+
+        assert_eq!(wrap_or_clamp(60.0, 100.0, clamp, clamp), 50.0);
+        assert_eq!(wrap_or_clamp(-60.0, 100.0, clamp, clamp), -50.0);
+        assert_eq!(wrap_or_clamp(60.0, 100.0, wrap, wrap), -40.0);
+        assert_eq!(wrap_or_clamp(-60.0, 100.0, wrap, wrap), 40.0);
+        assert_eq!(wrap_or_clamp(60.0, 100.0, cull, cull), 60.0);
+        assert_eq!(wrap_or_clamp(-60.0, 100.0, cull, cull), -60.0);
+
+        // Only the edge that was crossed matters
+        assert_eq!(wrap_or_clamp(60.0, 100.0, cull, clamp), 50.0);
+        assert_eq!(wrap_or_clamp(-60.0, 100.0, cull, clamp), -60.0);
+    }
+
+    #[test]
+    fn clamped_x_and_wrapped_y_are_independent() {
+        let edges = edges(EdgeBehavior::Clamp, EdgeBehavior::Wrap);
+        let mut position_size = creature(70.0, 70.0);
+
+        keep_inside(&mut position_size, None, Vec2::new(100.0, 100.0), &edges);
+
+        assert_eq!((position_size.x, position_size.y), (50.0, -30.0));
+    }
+
+    #[test]
+    fn wrapping_moves_the_previous_position_along() {
+        let edges = edges(EdgeBehavior::Wrap, EdgeBehavior::Wrap);
+        let mut position_size = creature(52.0, 0.0);
+        let mut previous_position = PreviousPosition { x: 48.0, y: 0.0 };
+
+        keep_inside(
+            &mut position_size,
+            Some(&mut previous_position),
+            Vec2::new(100.0, 100.0),
+            &edges,
+        );
+
+        assert_eq!((position_size.x, position_size.y), (-48.0, 0.0));
+        assert_eq!((previous_position.x, previous_position.y), (-48.0, 0.0));
+    }
+
+    #[test]
+    fn clamping_keeps_the_previous_position() {
+        let edges = edges(EdgeBehavior::Clamp, EdgeBehavior::Clamp);
+        let mut position_size = creature(52.0, 0.0);
+        let mut previous_position = PreviousPosition { x: 48.0, y: 0.0 };
+
+        keep_inside(
+            &mut position_size,
+            Some(&mut previous_position),
+            Vec2::new(100.0, 100.0),
+            &edges,
+        );
+
+        assert_eq!(position_size.x, 50.0);
+        assert_eq!(previous_position.x, 48.0);
+    }
+
+    #[test]
+    fn validate_edges_rejects_wrapping_one_side() {
+        let (clamp, wrap, cull) = (EdgeBehavior::Clamp, EdgeBehavior::Wrap, EdgeBehavior::Cull);
+
+        assert!(validate_edges(&edges(clamp, clamp)).is_ok());
+        assert!(validate_edges(&edges(wrap, wrap)).is_ok());
+        assert!(validate_edges(&edges(clamp, wrap)).is_ok());
+        assert!(validate_edges(&Edges {
+            left: clamp,
+            right: cull,
+            bottom: cull,
+            top: clamp,
+        })
+        .is_ok());
+
+        assert!(validate_edges(&Edges {
+            left: wrap,
+            right: clamp,
+            bottom: clamp,
+            top: clamp,
+        })
+        .is_err());
+        assert!(validate_edges(&Edges {
+            left: clamp,
+            right: clamp,
+            bottom: cull,
+            top: wrap,
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,359 @@
+/*
+    This file includes all systems & child functions used for
+    drawing things that help see what's going on in the simulation, without changing how it behaves.
+
+    Examples:
+    - Functions that turn color settings into colors
+    - Functions that draw reference lines like the background grid
+    - Functions that remember & draw the paths creatures have taken
+    - Functions that draw creatures bigger or smaller depending on their energy
+    - Functions that tint creatures by what they're doing & fade them out as they starve
+    - Functions that save screenshots of the simulation
+    - Functions that thin out long plot lines so they stay quick to draw
+*/
+
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{save_to_disk, Screenshot};
+use bevy_egui::egui::Color32;
+use std::collections::VecDeque;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::position_systems::{energy_scale, world_half_size, PositionSize};
+use crate::{Life, Predator, Prey, Settings};
+
+// The most recent positions of a creature, oldest first
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct Trail {
+    points: VecDeque<Vec2>,
+}
+
+// Colors in the config are written as hex strings like "#2b2c2f", with an optional alpha on the end
+pub fn parse_hex_color(value: &str) -> Result<Color, String> {
+    Srgba::hex(value)
+        .map(Color::from)
+        .map_err(|error| format!("Invalid color \"{}\": {}", value, error))
+}
+
+// The plot is drawn by egui, which has its own color type
+pub fn to_color32(color: Color) -> Color32 {
+    let [red, green, blue, alpha] = color.to_srgba().to_u8_array();
+    Color32::from_rgba_unmultiplied(red, green, blue, alpha)
+}
+
+pub fn draw_grid(mut gizmos: Gizmos, settings: Res<Settings>, windows: Query<&Window>) {
+    if !settings.show_grid || settings.grid_spacing <= 0.0 {
+        return;
+    }
+
+    let half_size = world_half_size(&windows, &settings);
+    let (half_width, half_height) = (half_size.x, half_size.y);
+
+    // Lines are spaced out from the middle of the world so there's always one through the center
+    let columns = (half_width / settings.grid_spacing).floor() as i32;
+    for column in -columns..=columns {
+        let x = column as f32 * settings.grid_spacing;
+        gizmos.line_2d(
+            Vec2::new(x, -half_height),
+            Vec2::new(x, half_height),
+            settings.grid_color,
+        );
+    }
+
+    let rows = (half_height / settings.grid_spacing).floor() as i32;
+    for row in -rows..=rows {
+        let y = row as f32 * settings.grid_spacing;
+        gizmos.line_2d(
+            Vec2::new(-half_width, y),
+            Vec2::new(half_width, y),
+            settings.grid_color,
+        );
+    }
+
+    // Outline the edges of the world
+    gizmos.rect_2d(Isometry2d::IDENTITY, half_size * 2.0, settings.grid_color);
+}
+
+pub fn record_trails(mut query: Query<(&PositionSize, &mut Trail)>, settings: Res<Settings>) {
+    for (position_size, mut trail) in query.iter_mut() {
+        // Throw away old trails when they're turned off so they don't jump across the screen
+        // from where they were left when turned back on
+        if !settings.show_trails {
+            if !trail.points.is_empty() {
+                trail.points.clear();
+            }
+            continue;
+        }
+
+        trail
+            .points
+            .push_back(Vec2::new(position_size.x, position_size.y));
+        while trail.points.len() > settings.trail_length {
+            trail.points.pop_front();
+        }
+    }
+}
+
+pub fn draw_trails(mut gizmos: Gizmos, query: Query<(&Trail, &Sprite)>, settings: Res<Settings>) {
+    if !settings.show_trails {
+        return;
+    }
+
+    for (trail, sprite) in query.iter() {
+        let point_count = trail.points.len();
+        if point_count < 2 {
+            continue;
+        }
+
+        // Fade the trail out the older the point is
+        let points = trail.points.iter().enumerate().map(|(index, point)| {
+            let alpha = (index + 1) as f32 / point_count as f32 * 0.6;
+            (*point, sprite.color.with_alpha(alpha))
+        });
+        gizmos.linestrip_gradient_2d(points);
+    }
+}
+
+const SCREENSHOT_FOLDER: &str = "screenshots";
+
+// How many more frames the ui windows stay hidden for, so they're left out of screenshots
+#[derive(Resource, Default)]
+pub struct ScreenshotState {
+    hide_ui_frames: u32,
+}
+
+// Only changes how creatures are drawn, leaving their collision size alone. When the scale applies
+// to collisions too, scale_collision_by_energy resizes the creatures themselves instead
+#[allow(clippy::type_complexity)]
+pub fn scale_sprites_by_energy(
+    mut query: Query<(&mut Transform, &Life, Has<Predator>), Or<(With<Prey>, With<Predator>)>>,
+    settings: Res<Settings>,
+) {
+    let visual_only = settings.scale_by_energy && !settings.energy_scale_collision;
+
+    for (mut transform, life, is_predator) in query.iter_mut() {
+        let scale = if visual_only {
+            let full_life = if is_predator {
+                settings.predator_life
+            } else {
+                settings.prey_life
+            };
+            energy_scale(life.value, full_life, &settings)
+        } else {
+            1.0
+        };
+
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
+// Written as "status:color" pairs with a semicolon between each, like "1:#ff8fc8;2:#ffe14d"
+pub fn parse_status_colors(value: &str) -> Result<Vec<(u16, Color)>, String> {
+    value
+        .split(';')
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (status, color) = pair
+                .split_once(':')
+                .ok_or(format!("\"{}\" isn't written as status:color", pair))?;
+            let status = status
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| format!("\"{}\" isn't a status", status))?;
+            Ok((status, parse_hex_color(color.trim())?))
+        })
+        .collect()
+}
+
+// Shifts every creature's color towards the color set for its status, so what each one is doing
+// can be seen at a glance. Statuses without a color are drawn in the species' normal color.
+// The colors are set every frame, so turning it off or changing the species colors shows straight away
+#[allow(clippy::type_complexity)]
+pub fn tint_by_status(
+    mut query: Query<
+        (&mut Sprite, Option<&Prey>, Option<&Predator>),
+        Or<(With<Prey>, With<Predator>)>,
+    >,
+    settings: Res<Settings>,
+) {
+    // The settings are checked when they're read, so these never fail
+    let prey_colors = parse_status_colors(&settings.prey_status_colors).unwrap_or_default();
+    let predator_colors = parse_status_colors(&settings.predator_status_colors).unwrap_or_default();
+
+    for (mut sprite, prey, predator) in query.iter_mut() {
+        let (base, status, colors) = match (prey, predator) {
+            (Some(prey), _) => (settings.prey_color, prey.status, &prey_colors),
+            (_, Some(predator)) => (settings.predator_color, predator.status, &predator_colors),
+            _ => continue,
+        };
+
+        let tint = colors
+            .iter()
+            .find(|(colored_status, _)| *colored_status == status)
+            .map(|(_, color)| *color)
+            .filter(|_| settings.color_by_status);
+
+        sprite.color = match tint {
+            Some(tint) => base.mix(&tint, settings.status_tint_strength),
+            None => base,
+        };
+    }
+}
+
+// Fades creatures towards transparent as their energy runs out, so deaths can be seen coming.
+// Only the alpha is changed, on top of whatever color tint_by_status gave them this frame, which
+// also puts it back to normal once they've eaten
+#[allow(clippy::type_complexity)]
+pub fn fade_low_energy(
+    mut query: Query<(&mut Sprite, &Life, Has<Predator>), Or<(With<Prey>, With<Predator>)>>,
+    settings: Res<Settings>,
+) {
+    if !settings.fade_low_energy {
+        return;
+    }
+
+    for (mut sprite, life, is_predator) in query.iter_mut() {
+        let full_life = if is_predator {
+            settings.predator_life
+        } else {
+            settings.prey_life
+        };
+        let fade_below = full_life as f32 * settings.fade_energy_fraction;
+        if fade_below <= 0.0 {
+            continue;
+        }
+
+        let opacity = (life.value as f32 / fade_below).clamp(settings.fade_min_alpha, 1.0);
+        let alpha = sprite.color.alpha();
+        sprite.color.set_alpha(alpha * opacity);
+    }
+}
+
+pub fn ui_visible(state: Res<ScreenshotState>) -> bool {
+    state.hide_ui_frames == 0
+}
+
+// Press F12 to save what's on screen to a png in the screenshots folder. This reads the
+// keyboard directly instead of going through the simulation's time, so it works while paused
+pub fn take_screenshot(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    settings: Res<Settings>,
+    mut state: ResMut<ScreenshotState>,
+) {
+    if state.hide_ui_frames > 0 {
+        state.hide_ui_frames -= 1;
+    }
+
+    if !keys.just_pressed(KeyCode::F12) {
+        return;
+    }
+
+    if let Err(error) = fs::create_dir_all(SCREENSHOT_FOLDER) {
+        error!(
+            "Couldn't create the screenshot folder {}: {}",
+            SCREENSHOT_FOLDER, error
+        );
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    let path = format!("{}/screenshot_{}.png", SCREENSHOT_FOLDER, timestamp);
+
+    // Saving happens once the frame has been rendered, and any error is logged there
+    commands
+        .spawn(Screenshot::primary_window())
+        .observe(save_to_disk(path));
+
+    // The screenshot is of this frame, so hide the ui for just this one
+    if settings.screenshot_hide_ui {
+        state.hide_ui_frames = 1;
+    }
+}
+
+// Keeps a thinned out copy of a plot line, only redoing it when the line or the visible
+// part of the plot changes instead of every frame
+#[derive(Default)]
+pub struct PlotDecimation {
+    // The sample count, last sample, visible x range & point limit the copy was made for
+    key: Option<(usize, [f64; 2], [f64; 2], usize)>,
+    points: Vec<[f64; 2]>,
+}
+
+impl PlotDecimation {
+    pub fn points(
+        &mut self,
+        samples: &[[f64; 2]],
+        visible_x: [f64; 2],
+        max_points: usize,
+    ) -> &[[f64; 2]] {
+        let key = (
+            samples.len(),
+            samples.last().copied().unwrap_or_default(),
+            visible_x,
+            max_points,
+        );
+
+        if self.key != Some(key) {
+            self.points = decimate(samples, visible_x, max_points);
+            self.key = Some(key);
+        }
+
+        &self.points
+    }
+}
+
+// Splits the visible part of the line into buckets along the x axis and keeps only the lowest and
+// highest point in each, in the order they happened, so spikes still show up however many samples
+// are squeezed into one pixel. Samples have to be sorted by x, which the population history is.
+// A max_points of 0 keeps every point.
+pub fn decimate(samples: &[[f64; 2]], visible_x: [f64; 2], max_points: usize) -> Vec<[f64; 2]> {
+    if max_points == 0 || samples.len() <= max_points {
+        return samples.to_vec();
+    }
+
+    // Keep one point either side of the visible range so the line runs off the edges
+    let start = samples
+        .partition_point(|[x, _]| *x < visible_x[0])
+        .saturating_sub(1);
+    let end = (samples.partition_point(|[x, _]| *x <= visible_x[1]) + 1).min(samples.len());
+    let visible = &samples[start..end.max(start)];
+
+    if visible.len() <= max_points {
+        return visible.to_vec();
+    }
+
+    let bucket_count = (max_points / 2).max(1);
+    let bucket_size = visible.len().div_ceil(bucket_count);
+    let mut points = Vec::with_capacity(bucket_count * 2);
+
+    for bucket in visible.chunks(bucket_size) {
+        let mut lowest = bucket[0];
+        let mut highest = bucket[0];
+        for point in bucket.iter() {
+            if point[1] < lowest[1] {
+                lowest = *point;
+            }
+            if point[1] > highest[1] {
+                highest = *point;
+            }
+        }
+
+        if lowest[0] <= highest[0] {
+            points.push(lowest);
+            if highest != lowest {
+                points.push(highest);
+            }
+        } else {
+            points.push(highest);
+            points.push(lowest);
+        }
+    }
+
+    points
+}
@@ -0,0 +1,213 @@
+/*
+    This file includes all child functions used for
+    summing up a finished headless run in one file, so it doesn't have to be worked out from the metrics.
+
+    Examples:
+    - Functions that find the peak populations & any extinctions in the population history
+    - Functions that write the summary, along with the settings the run used, as Markdown or JSON
+*/
+
+use bevy::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::settings_file::format_settings;
+use crate::{PopulationHistory, Predator, Prey, Settings, SimClock, SimStats};
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum RunReportFormat {
+    Off,
+    Markdown,
+    Json,
+}
+
+impl FromStr for RunReportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(RunReportFormat::Off),
+            "markdown" => Ok(RunReportFormat::Markdown),
+            "json" => Ok(RunReportFormat::Json),
+            _ => Err(format!(
+                "Unknown run report format \"{}\", expected \"off\", \"markdown\" or \"json\"",
+                value
+            )),
+        }
+    }
+}
+
+impl RunReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RunReportFormat::Off => "",
+            RunReportFormat::Markdown => "md",
+            RunReportFormat::Json => "json",
+        }
+    }
+}
+
+// What happened to one species over the run
+struct SpeciesSummary {
+    final_count: usize,
+    // The most there ever were, and the first time there were that many
+    peak: (f64, f64),
+    // When they first died out, if they ever did
+    extinct_at: Option<f64>,
+    // In ticks, only counting the ones that have died
+    mean_lifespan: Option<f64>,
+}
+
+impl SpeciesSummary {
+    fn new(final_count: usize, history: &[[f64; 2]], lifespans: (u64, u64)) -> Self {
+        let peak =
+            history.iter().fold(
+                [0.0, 0.0],
+                |peak, sample| {
+                    if sample[1] > peak[1] {
+                        *sample
+                    } else {
+                        peak
+                    }
+                },
+            );
+        // Nothing has spawned yet in the very first sample, so that isn't an extinction
+        let extinct_at = history
+            .iter()
+            .skip_while(|sample| sample[1] == 0.0)
+            .find(|sample| sample[1] == 0.0)
+            .map(|sample| sample[0]);
+        let (total_ticks, count) = lifespans;
+
+        SpeciesSummary {
+            final_count,
+            peak: (peak[0], peak[1]),
+            extinct_at,
+            mean_lifespan: (count > 0).then(|| total_ticks as f64 / count as f64),
+        }
+    }
+
+    fn json(&self) -> String {
+        format!(
+            "{{\"final_count\": {}, \"peak\": {}, \"peak_seconds\": {:.3}, \"extinct_seconds\": {}, \"mean_lifespan_ticks\": {}}}",
+            self.final_count,
+            self.peak.1,
+            self.peak.0,
+            self.extinct_at
+                .map(|seconds| format!("{:.3}", seconds))
+                .unwrap_or("null".to_string()),
+            self.mean_lifespan
+                .map(|ticks| format!("{:.1}", ticks))
+                .unwrap_or("null".to_string())
+        )
+    }
+}
+
+fn seconds_text(seconds: Option<f64>) -> String {
+    seconds
+        .map(|seconds| format!("{:.1}s", seconds))
+        .unwrap_or("never".to_string())
+}
+
+// Writes a summary of the run so far. The seed is in the summary along with every other setting,
+// so the run can be repeated from the report alone
+pub fn write_run_report(
+    world: &mut World,
+    path: &Path,
+    format: RunReportFormat,
+) -> Result<(), String> {
+    if format == RunReportFormat::Off {
+        return Ok(());
+    }
+
+    let prey_count = world.query_filtered::<(), With<Prey>>().iter(world).count();
+    let predator_count = world
+        .query_filtered::<(), With<Predator>>()
+        .iter(world)
+        .count();
+
+    let stats = world.resource::<SimStats>();
+    let history = world.resource::<PopulationHistory>();
+    let clock = world.resource::<SimClock>();
+    let settings = world.resource::<Settings>();
+
+    let prey = SpeciesSummary::new(prey_count, &history.prey_population, stats.prey_lifespans);
+    let predators = SpeciesSummary::new(
+        predator_count,
+        &history.predator_population,
+        stats.predator_lifespans,
+    );
+
+    let mut config: Vec<(String, String)> = format_settings(settings)?.into_iter().collect();
+    config.sort();
+
+    let contents = match format {
+        RunReportFormat::Json => {
+            let config = config
+                .iter()
+                .map(|(name, value)| format!("    \"{}\": {}", name, value))
+                .collect::<Vec<String>>()
+                .join(",\n");
+            format!(
+                "{{\n  \"seed\": {},\n  \"ticks\": {},\n  \"seconds\": {:.3},\n  \"births\": {},\n  \"deaths\": {},\n  \"kills\": {},\n  \"emigrations\": {},\n  \"immigrations\": {},\n  \"prey\": {},\n  \"predators\": {},\n  \"settings\": {{\n{}\n  }}\n}}\n",
+                settings.seed,
+                clock.ticks,
+                clock.seconds,
+                stats.births,
+                stats.deaths,
+                stats.kills,
+                stats.emigrations,
+                stats.immigrations,
+                prey.json(),
+                predators.json(),
+                config
+            )
+        }
+        _ => {
+            let lifespan = |summary: &SpeciesSummary| {
+                summary
+                    .mean_lifespan
+                    .map(|ticks| format!("{:.1} ticks", ticks))
+                    .unwrap_or("none died".to_string())
+            };
+            let config = config
+                .iter()
+                .map(|(name, value)| format!("{} = {}", name, value))
+                .collect::<Vec<String>>()
+                .join("\n");
+            format!(
+                "# Run report\n\nSeed {}, {} ticks ({:.1}s simulated)\n\n\
+                 | | Prey | Predators |\n|---|---|---|\n\
+                 | Final count | {} | {} |\n\
+                 | Peak | {} at {:.1}s | {} at {:.1}s |\n\
+                 | Died out | {} | {} |\n\
+                 | Mean lifespan | {} | {} |\n\n\
+                 {} births, {} deaths ({} of them kills), {} emigrations & {} immigrations\n\n\
+                 ## Settings\n\n```toml\nseed = {}\n{}\n```\n",
+                settings.seed,
+                clock.ticks,
+                clock.seconds,
+                prey.final_count,
+                predators.final_count,
+                prey.peak.1,
+                prey.peak.0,
+                predators.peak.1,
+                predators.peak.0,
+                seconds_text(prey.extinct_at),
+                seconds_text(predators.extinct_at),
+                lifespan(&prey),
+                lifespan(&predators),
+                stats.births,
+                stats.deaths,
+                stats.kills,
+                stats.emigrations,
+                stats.immigrations,
+                settings.seed,
+                config
+            )
+        }
+    };
+
+    fs::write(path, contents).map_err(|error| error.to_string())
+}
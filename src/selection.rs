@@ -0,0 +1,341 @@
+/*
+    This file includes all systems & child functions used for
+    clicking on a creature to keep an eye on it, without digging through the world inspector.
+
+    Examples:
+    - Functions that find the creature nearest to where the mouse was clicked
+    - Functions that outline the selected creature & show its details in a panel
+    - Functions that move the camera along with the selected creature
+    - Functions that drag the selected creature somewhere else with the mouse
+*/
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::position_systems::{world_half_size, PositionSize, PreviousPosition, Velocity};
+use crate::{Age, Genes, Life, MatingTarget, Parents, Predator, Prey, Settings, StableId};
+
+// How far from a creature a click can be and still pick it, in pixels
+const SELECT_RADIUS: f32 = 20.0;
+
+// How quickly the camera catches up with the creature it's following, higher is snappier
+const FOLLOW_SMOOTHING: f32 = 8.0;
+
+// The StableId is kept alongside the entity, as Bevy can give a dead creature's entity to a newborn
+// and the selection shouldn't jump over to it
+#[derive(Resource, Default)]
+pub struct Selection {
+    creature: Option<(Entity, StableId)>,
+    // Whether the camera is following the selected creature
+    following: bool,
+}
+
+// On a creature while it's being dragged around. The systems that move creatures leave it alone
+// until it's let go, so it doesn't fight the mouse
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct BeingDragged;
+
+#[allow(clippy::type_complexity)]
+pub fn select_on_click(
+    mut selection: ResMut<Selection>,
+    mut contexts: EguiContexts,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    creatures: Query<(Entity, &PositionSize, &StableId), Or<(With<Prey>, With<Predator>)>>,
+) {
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // Clicks on the ui windows are for the ui, not for picking creatures
+    if contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(cursor_position) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+
+    // Clicking on empty space clears the selection
+    selection.creature = None;
+    selection.following = false;
+    let mut closest_distance = SELECT_RADIUS;
+
+    for (entity, position_size, stable_id) in creatures.iter() {
+        let distance = cursor_position.distance(Vec2::new(position_size.x, position_size.y));
+        if distance <= closest_distance {
+            selection.creature = Some((entity, *stable_id));
+            closest_distance = distance;
+        }
+    }
+}
+
+pub fn draw_selection(
+    mut gizmos: Gizmos,
+    selection: Res<Selection>,
+    creatures: Query<(&PositionSize, &StableId)>,
+) {
+    let Some((entity, selected_id)) = selection.creature else {
+        return;
+    };
+
+    if let Some((position_size, _)) = creatures
+        .get(entity)
+        .ok()
+        .filter(|(_, stable_id)| **stable_id == selected_id)
+    {
+        gizmos.circle_2d(
+            Vec2::new(position_size.x, position_size.y),
+            position_size.width.max(position_size.height),
+            Color::WHITE,
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn selection_ui(
+    mut contexts: EguiContexts,
+    mut selection: ResMut<Selection>,
+    creatures: Query<(
+        &Life,
+        &Age,
+        &Genes,
+        &MatingTarget,
+        &PositionSize,
+        Option<&Prey>,
+        Option<&Predator>,
+        (&StableId, &Parents),
+    )>,
+) {
+    let Some((entity, selected_id)) = selection.creature else {
+        return;
+    };
+
+    // The selected creature died or left since it was clicked
+    let Some((
+        life,
+        age,
+        genes,
+        mating_target,
+        position_size,
+        prey,
+        predator,
+        (stable_id, parents),
+    )) = creatures
+        .get(entity)
+        .ok()
+        .filter(|(_, _, _, _, _, _, _, (stable_id, _))| **stable_id == selected_id)
+    else {
+        selection.creature = None;
+        selection.following = false;
+        return;
+    };
+
+    let (species, status) = match (prey, predator) {
+        (Some(prey), _) => (
+            "Prey",
+            match prey.status {
+                0 => "Idle",
+                1 => "Mating",
+                2 => "Fleeing",
+                4 => "In a burrow",
+                5 => "Wary",
+                6 => "Frozen",
+                _ => "Unknown",
+            },
+        ),
+        (_, Some(predator)) => (
+            "Predator",
+            match predator.status {
+                0 => "Idle",
+                1 => "Mating",
+                2 => "Hunting",
+                _ => "Unknown",
+            },
+        ),
+        _ => ("Unknown", "Unknown"),
+    };
+
+    egui::Window::new("Selected Creature").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("{} {}", species, stable_id.0));
+        ui.label(format!("Status: {}", status));
+        ui.label(format!("Life: {}", life.value));
+        ui.label(format!("Age: {} ticks", age.ticks));
+        match (parents.founder, parents.a) {
+            (Some(founder), Some(parent)) => {
+                ui.label(format!(
+                    "Generation {}, descended from founder {}",
+                    parents.generation, founder.0
+                ));
+                match parents.b {
+                    Some(partner) => ui.label(format!("Parents: {} & {}", parent.0, partner.0)),
+                    None => ui.label(format!("Parent: {}", parent.0)),
+                };
+            }
+            _ => {
+                ui.label("Founder of its own lineage");
+            }
+        }
+        ui.label(format!("Speed: {:.3}", genes.speed));
+        ui.label(format!("Detection range: {:.1}", genes.detection_range));
+        if prey.is_some() {
+            ui.label(if genes.freezes {
+                "Defense: freezes"
+            } else {
+                "Defense: flees"
+            });
+        }
+        ui.label(format!(
+            "Position: {:.1}, {:.1}",
+            position_size.x, position_size.y
+        ));
+
+        match &mating_target.entity {
+            Some(target) => ui.label(format!("Mating target: {:.1}, {:.1}", target.x, target.y)),
+            None => ui.label("Mating target: none"),
+        };
+
+        ui.label(if selection.following {
+            "Following, press F or Escape to stop"
+        } else {
+            "Press F to follow"
+        });
+    });
+}
+
+// F toggles following the selected creature, Escape stops. The camera is moved a bit closer every
+// frame instead of jumping straight there, and is kept from showing past the edges of the world
+#[allow(clippy::too_many_arguments)]
+pub fn follow_selected(
+    mut selection: ResMut<Selection>,
+    mut contexts: EguiContexts,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    creatures: Query<(&PositionSize, &StableId)>,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<Camera>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<Settings>,
+) {
+    // Typing into a ui text box shouldn't toggle anything
+    if !contexts.ctx_mut().wants_keyboard_input() {
+        if keys.just_pressed(KeyCode::KeyF) && selection.creature.is_some() {
+            selection.following = !selection.following;
+        }
+        if keys.just_pressed(KeyCode::Escape) {
+            selection.following = false;
+        }
+    }
+    if !selection.following {
+        return;
+    }
+
+    let Some((entity, selected_id)) = selection.creature else {
+        selection.following = false;
+        return;
+    };
+    let Some((position_size, _)) = creatures
+        .get(entity)
+        .ok()
+        .filter(|(_, stable_id)| **stable_id == selected_id)
+    else {
+        // The creature died or left, so there's nothing to follow
+        selection.following = false;
+        return;
+    };
+    let Ok((mut transform, projection)) = cameras.get_single_mut() else {
+        return;
+    };
+
+    let mut target = Vec2::new(position_size.x, position_size.y);
+
+    // An open world has no edges to stay inside of
+    if !settings.open_world {
+        let world_half_size = world_half_size(&windows, &settings);
+        let room = (world_half_size - projection.area.half_size()).max(Vec2::ZERO);
+        target = target.clamp(-room, room);
+    }
+
+    let blend = 1.0 - (-FOLLOW_SMOOTHING * time.delta_secs()).exp();
+    let position = transform.translation.truncate().lerp(target, blend);
+    transform.translation.x = position.x;
+    transform.translation.y = position.y;
+}
+
+// Holding the mouse down on a creature picks it up, and it follows the cursor until it's let go.
+// It carries on from wherever it was dropped, standing still to begin with
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn drag_selected(
+    mut commands: Commands,
+    selection: Res<Selection>,
+    mut contexts: EguiContexts,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut dragged: Query<
+        (
+            Entity,
+            &mut PositionSize,
+            Option<&mut PreviousPosition>,
+            Option<&mut Velocity>,
+        ),
+        With<BeingDragged>,
+    >,
+) {
+    if mouse_buttons.just_released(MouseButton::Left) {
+        for (entity, _, _, _) in dragged.iter() {
+            commands.entity(entity).remove::<BeingDragged>();
+        }
+        return;
+    }
+
+    // select_on_click has already picked whatever was under the click. Clicks on the ui leave the
+    // old selection alone, so they mustn't pick it up
+    if mouse_buttons.just_pressed(MouseButton::Left) && !contexts.ctx_mut().is_pointer_over_area() {
+        if let Some((entity, _)) = selection.creature {
+            commands.entity(entity).insert(BeingDragged);
+        }
+        return;
+    }
+
+    if !mouse_buttons.pressed(MouseButton::Left) {
+        return;
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(cursor_position) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+
+    for (_, mut position_size, previous_position, velocity) in dragged.iter_mut() {
+        position_size.x = cursor_position.x;
+        position_size.y = cursor_position.y;
+
+        // Otherwise it'd be drawn sliding over from where it was picked up
+        if let Some(mut previous_position) = previous_position {
+            previous_position.x = cursor_position.x;
+            previous_position.y = cursor_position.y;
+        }
+        if let Some(mut velocity) = velocity {
+            velocity.0 = Vec2::ZERO;
+        }
+    }
+}
@@ -0,0 +1,190 @@
+/*
+    This file includes all child functions used for
+    writing the settings back to Settings.toml and reading them in again while the simulation is running.
+
+    Examples:
+    - Functions that turn each setting back into the text it would be written as in the config
+    - Functions that save & reload the settings file without crashing when something's wrong
+*/
+
+use bevy::prelude::*;
+use bevy::reflect::ReflectRef;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::{
+    difficulty_multiplier, try_read_settings, try_read_settings_from, PopulationMode,
+    ReproductionEnergyMode, Settings, DIFFICULTY_FIELDS,
+};
+
+const SETTINGS_PATH: &str = "Settings.toml";
+// Saved settings are written here first, and only moved over the settings file once they've been
+// read back in, so a failed save can't leave a broken or half written settings file behind
+const SAVING_PATH: &str = "Settings.saving.toml";
+
+// Turns a setting into how it's written in the config, so it reads back in as the same value
+fn format_setting(field: &dyn PartialReflect) -> Option<String> {
+    // Debug keeps the decimal point on whole numbers, so 50.0 doesn't become 50
+    if let Some(value) = field.try_downcast_ref::<f32>() {
+        return Some(format!("{:?}", value));
+    }
+    if let Some(value) = field.try_downcast_ref::<i32>() {
+        return Some(value.to_string());
+    }
+    if let Some(value) = field.try_downcast_ref::<u32>() {
+        return Some(value.to_string());
+    }
+    if let Some(value) = field.try_downcast_ref::<u64>() {
+        return Some(value.to_string());
+    }
+    if let Some(value) = field.try_downcast_ref::<usize>() {
+        return Some(value.to_string());
+    }
+    if let Some(value) = field.try_downcast_ref::<bool>() {
+        return Some(value.to_string());
+    }
+    if let Some(value) = field.try_downcast_ref::<String>() {
+        return Some(format!(
+            "\"{}\"",
+            value.replace('\\', "\\\\").replace('"', "\\\"")
+        ));
+    }
+    if let Some(value) = field.try_downcast_ref::<Color>() {
+        return Some(format!("\"{}\"", value.to_srgba().to_hex().to_lowercase()));
+    }
+
+    // Every other setting is one of our enums, which are read in by their lowercase name
+    if let ReflectRef::Enum(value) = field.reflect_ref() {
+        return Some(format!("\"{}\"", value.variant_name().to_lowercase()));
+    }
+
+    None
+}
+
+// Settings that are worked out from others when they're read in instead of being read straight
+// from the file. Writing them back would either do nothing or override what they're worked out from
+fn derived_fields(settings: &Settings) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+
+    if settings.population_mode == PopulationMode::Fraction {
+        fields.extend(["predator_population", "prey_population"]);
+    }
+    if settings.reproduction_energy_mode == ReproductionEnergyMode::Fraction {
+        fields.extend(["prey_reproduction_energy", "predator_reproduction_energy"]);
+    }
+    if settings.realistic_reproduction {
+        fields.extend(["conserve_reproduction_energy", "post_mate_reserve"]);
+    }
+
+    fields
+}
+
+pub(crate) fn format_settings(settings: &Settings) -> Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+    let derived = derived_fields(settings);
+
+    for index in 0..settings.field_len() {
+        let (Some(name), Some(field)) = (settings.name_at(index), settings.field_at(index)) else {
+            continue;
+        };
+
+        // The seed in the file is left alone, so a seed of 0 still picks a new one every run
+        if name == "seed" {
+            continue;
+        }
+        // The fields scaled by the difficulty would be scaled again when they're read back in, so
+        // they're left as they are in the file unless the difficulty is normal
+        if DIFFICULTY_FIELDS.contains(&name)
+            && difficulty_multiplier(&settings.difficulty) != Ok(1.0)
+        {
+            continue;
+        }
+        if derived.contains(&name) {
+            continue;
+        }
+
+        let value = format_setting(field).ok_or(format!("Don't know how to save {}", name))?;
+        values.insert(name.to_string(), value);
+    }
+
+    Ok(values)
+}
+
+// Reads the settings file again, without crashing if it has a mistake in it
+pub fn reload_settings() -> Result<Settings, String> {
+    try_read_settings().map_err(|error| format!("Couldn't read {}: {}", SETTINGS_PATH, error))
+}
+
+// Writes the current value of every setting over the one in the file. Only the values are
+// changed, so the comments and the order of the file stay the same
+pub fn save_settings(settings: &Settings) -> Result<(), String> {
+    let values = format_settings(settings)?;
+    let contents = fs::read_to_string(SETTINGS_PATH)
+        .map_err(|error| format!("Couldn't read {}: {}", SETTINGS_PATH, error))?;
+
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let is_comment = line.trim_start().starts_with('#');
+
+        match line.split_once('=') {
+            Some((key, _)) if !is_comment && values.contains_key(key.trim()) => {
+                lines.push(format!("{} = {}", key.trim(), values[key.trim()]));
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    fs::write(SAVING_PATH, lines.join("\n") + "\n")
+        .map_err(|error| format!("Couldn't write {}: {}", SAVING_PATH, error))?;
+
+    if let Err(error) = check_saved(&values) {
+        let _ = fs::remove_file(SAVING_PATH);
+        return Err(error);
+    }
+
+    fs::rename(SAVING_PATH, SETTINGS_PATH)
+        .map_err(|error| format!("Couldn't replace {}: {}", SETTINGS_PATH, error))
+}
+
+// Makes sure what was written reads back in as the same settings
+fn check_saved(values: &HashMap<String, String>) -> Result<(), String> {
+    let saved = try_read_settings_from(SAVING_PATH)
+        .map_err(|error| format!("The saved settings couldn't be read back in: {}", error))?;
+    let saved = format_settings(&saved)?;
+
+    if let Some(name) = values
+        .keys()
+        .find(|name| saved.get(*name) != Some(&values[*name]))
+    {
+        return Err(format!(
+            "{} was saved as {} but read back as {}",
+            name,
+            values[name],
+            saved.get(name).map_or("nothing", |value| value.as_str())
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_settings;
+
+    #[test]
+    fn worked_out_populations_are_not_saved() {
+        let mut settings = read_settings();
+        settings.population_mode = PopulationMode::Absolute;
+        let values = format_settings(&settings).unwrap();
+        assert!(values.contains_key("prey_population"));
+        assert!(values.contains_key("predator_population"));
+
+        settings.population_mode = PopulationMode::Fraction;
+        let values = format_settings(&settings).unwrap();
+        assert!(!values.contains_key("prey_population"));
+        assert!(!values.contains_key("predator_population"));
+        assert!(values.contains_key("total_population"));
+        assert!(values.contains_key("predator_fraction"));
+    }
+}
@@ -0,0 +1,143 @@
+/*
+    This file includes everything used for
+    driving the simulation from other Rust code, like a training environment or a notebook, instead of a window.
+
+    Examples:
+    - SimApi, which builds a headless copy of the simulation and steps it one tick at a time
+    - SimSnapshot, a plain copy of where every creature is and how much energy it has
+*/
+
+use bevy::prelude::*;
+
+use crate::perturbations::{Perturbation, PerturbationSchedule};
+use crate::position_systems::PositionSize;
+use crate::{
+    build_headless_app, flush_exports, Environment, Life, Predator, Prey, Settings, SimClock,
+    StableId,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Species {
+    Prey,
+    Predator,
+}
+
+#[derive(Clone, Debug)]
+pub struct CreatureSnapshot {
+    pub entity: Entity,
+    // Unlike the entity, this is never reused, so it follows one creature across snapshots
+    pub id: u64,
+    pub species: Species,
+    pub x: f32,
+    pub y: f32,
+    pub energy: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimSnapshot {
+    pub tick: u64,
+    pub seconds: f64,
+    pub prey_count: usize,
+    pub predator_count: usize,
+    pub environment_energy: i32,
+    // Sorted by id, so the same seed always gives the same order
+    pub creatures: Vec<CreatureSnapshot>,
+}
+
+pub struct SimApi {
+    app: App,
+}
+
+impl SimApi {
+    // Nothing runs until the first step, which also spawns the starting creatures
+    pub fn new(settings: Settings) -> Self {
+        SimApi {
+            app: build_headless_app(settings),
+        }
+    }
+
+    // Each step is one fixed tick of the simulation (1/60th of a simulated second),
+    // the same as one frame of a headless ensemble run. Bevy doesn't move time forward on the
+    // very first update, so SimClock.seconds starts counting from the second step
+    pub fn step(&mut self, ticks: u32) {
+        for _tick in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    pub fn snapshot(&mut self) -> SimSnapshot {
+        take_snapshot(self.app.world_mut())
+    }
+
+    // Writes out the metrics, recording & summary files as they'd be left when closing the window
+    pub fn flush(&mut self) {
+        flush_exports(self.app.world_mut());
+    }
+
+    // Queued along with the perturbations from the settings, and applied once its time comes
+    pub fn schedule_perturbation(&mut self, perturbation: Perturbation) {
+        self.app
+            .world_mut()
+            .resource_mut::<PerturbationSchedule>()
+            .add(perturbation);
+    }
+
+    // For anything the snapshot doesn't cover, the whole Bevy app can be reached directly
+    pub fn app_mut(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+// Also used to record snapshots to disk while the simulation runs
+pub(crate) fn take_snapshot(world: &mut World) -> SimSnapshot {
+    let mut creatures: Vec<CreatureSnapshot> = world
+        .query::<(
+            Entity,
+            &StableId,
+            &PositionSize,
+            &Life,
+            Has<Prey>,
+            Has<Predator>,
+        )>()
+        .iter(world)
+        .filter(|(_, _, _, _, is_prey, is_predator)| *is_prey || *is_predator)
+        .map(
+            |(entity, id, position_size, life, is_prey, _)| CreatureSnapshot {
+                entity,
+                id: id.0,
+                species: if is_prey {
+                    Species::Prey
+                } else {
+                    Species::Predator
+                },
+                x: position_size.x,
+                y: position_size.y,
+                energy: life.value,
+            },
+        )
+        .collect();
+    creatures.sort_by_key(|creature| creature.id);
+
+    let environment_energy = world
+        .query::<&Environment>()
+        .iter(world)
+        .map(|environment| environment.energy_pool)
+        .sum();
+
+    let clock = world.resource::<SimClock>();
+
+    SimSnapshot {
+        tick: clock.ticks,
+        seconds: clock.seconds,
+        prey_count: creatures
+            .iter()
+            .filter(|creature| creature.species == Species::Prey)
+            .count(),
+        predator_count: creatures
+            .iter()
+            .filter(|creature| creature.species == Species::Predator)
+            .count(),
+        environment_energy,
+        creatures,
+    }
+}
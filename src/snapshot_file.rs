@@ -0,0 +1,297 @@
+/*
+    This file includes all child functions used for
+    saving snapshots of the simulation to files and reading them back in.
+
+    Examples:
+    - Functions that write & read the readable JSON format
+    - Functions that write & read the compact binary format, for checkpointing big runs often
+    - Functions that pick between the two from a file's extension, so callers don't have to care
+*/
+
+use bevy::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::sim_api::{CreatureSnapshot, SimSnapshot, Species};
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum SnapshotFormat {
+    Json,
+    Binary,
+}
+
+impl FromStr for SnapshotFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "json" => Ok(SnapshotFormat::Json),
+            "binary" => Ok(SnapshotFormat::Binary),
+            other => Err(format!(
+                "Unknown snapshot format \"{}\", expected \"json\" or \"binary\"",
+                other
+            )),
+        }
+    }
+}
+
+impl SnapshotFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SnapshotFormat::Json => "json",
+            SnapshotFormat::Binary => "bin",
+        }
+    }
+
+    // Anything that isn't a .bin file is treated as JSON
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("bin") => SnapshotFormat::Binary,
+            _ => SnapshotFormat::Json,
+        }
+    }
+}
+
+// The format is picked from the file's extension, .bin for binary & anything else for JSON
+pub fn save_snapshot(path: &Path, snapshot: &SimSnapshot) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    match SnapshotFormat::from_path(path) {
+        SnapshotFormat::Json => write_json(&mut writer, snapshot)?,
+        SnapshotFormat::Binary => write_binary(&mut writer, snapshot)?,
+    }
+
+    writer.flush()
+}
+
+// Entities aren't saved, as they mean nothing outside the world they came from, so every loaded
+// creature's entity is Entity::PLACEHOLDER. Its id is what to follow it by
+pub fn load_snapshot(path: &Path) -> io::Result<SimSnapshot> {
+    match SnapshotFormat::from_path(path) {
+        SnapshotFormat::Json => read_json(&fs::read_to_string(path)?),
+        SnapshotFormat::Binary => {
+            let mut bytes = Vec::new();
+            File::open(path)?.read_to_end(&mut bytes)?;
+            read_binary(&bytes)
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn species_name(species: Species) -> &'static str {
+    match species {
+        Species::Prey => "prey",
+        Species::Predator => "predator",
+    }
+}
+
+// Laid out like:
+// {"tick":120,"seconds":2.000,"prey_count":2,"predator_count":1,"environment_energy":1000,
+//  "creatures":[{"id":4,"species":"prey","x":-10.5,"y":3.25,"energy":4980}, ...]}
+// Creatures are sorted by id. Ids are their StableId, which is never reused, so the same id in
+// two files is always the same creature.
+fn write_json(writer: &mut impl Write, snapshot: &SimSnapshot) -> io::Result<()> {
+    write!(
+        writer,
+        "{{\"tick\":{},\"seconds\":{:.3},\"prey_count\":{},\"predator_count\":{},\"environment_energy\":{},\"creatures\":[",
+        snapshot.tick,
+        snapshot.seconds,
+        snapshot.prey_count,
+        snapshot.predator_count,
+        snapshot.environment_energy
+    )?;
+
+    for (index, creature) in snapshot.creatures.iter().enumerate() {
+        if index > 0 {
+            write!(writer, ",")?;
+        }
+
+        write!(
+            writer,
+            "{{\"id\":{},\"species\":\"{}\",\"x\":{:.2},\"y\":{:.2},\"energy\":{}}}",
+            creature.id,
+            species_name(creature.species),
+            creature.x,
+            creature.y,
+            creature.energy
+        )?;
+    }
+
+    writeln!(writer, "]}}")
+}
+
+// Only reads the flat layout write_json writes, not JSON in general
+fn json_field<T: FromStr>(object: &str, key: &str) -> io::Result<T> {
+    let pattern = format!("\"{}\":", key);
+    let start = object
+        .find(&pattern)
+        .ok_or(invalid_data(format!("The snapshot is missing {}", key)))?
+        + pattern.len();
+    let value = object[start..]
+        .split([',', '}', ']'])
+        .next()
+        .unwrap_or("")
+        .trim()
+        .trim_matches('"');
+
+    value
+        .parse::<T>()
+        .map_err(|_| invalid_data(format!("{} = \"{}\" is invalid", key, value)))
+}
+
+fn read_json(text: &str) -> io::Result<SimSnapshot> {
+    let (header, creatures_text) = text.split_once("\"creatures\":[").ok_or(invalid_data(
+        "The snapshot has no creatures list".to_string(),
+    ))?;
+
+    let mut creatures = Vec::new();
+    for object in creatures_text.split('{').skip(1) {
+        let species = match json_field::<String>(object, "species")?.as_str() {
+            "prey" => Species::Prey,
+            "predator" => Species::Predator,
+            other => return Err(invalid_data(format!("Unknown species {}", other))),
+        };
+
+        creatures.push(CreatureSnapshot {
+            entity: Entity::PLACEHOLDER,
+            id: json_field(object, "id")?,
+            species,
+            x: json_field(object, "x")?,
+            y: json_field(object, "y")?,
+            energy: json_field(object, "energy")?,
+        });
+    }
+
+    Ok(SimSnapshot {
+        tick: json_field(header, "tick")?,
+        seconds: json_field(header, "seconds")?,
+        prey_count: json_field(header, "prey_count")?,
+        predator_count: json_field(header, "predator_count")?,
+        environment_energy: json_field(header, "environment_energy")?,
+        creatures,
+    })
+}
+
+// Marks the start of a binary snapshot, followed by the version of the layout
+const BINARY_MAGIC: &[u8; 6] = b"PPSNAP";
+const BINARY_VERSION: u8 = 1;
+
+// Everything is little endian. After the magic & version comes the header:
+// tick (u64), seconds (f64), prey_count (u32), predator_count (u32), environment_energy (i32) and
+// the number of creatures n (u32). Then each field of every creature is packed into its own array
+// one after another: ids (n u64s), species (n u8s, 0 for prey & 1 for predators), x (n f32s),
+// y (n f32s) and energy (n i32s). Seconds are kept exactly, unlike the JSON format.
+fn write_binary(writer: &mut impl Write, snapshot: &SimSnapshot) -> io::Result<()> {
+    writer.write_all(BINARY_MAGIC)?;
+    writer.write_all(&[BINARY_VERSION])?;
+
+    writer.write_all(&snapshot.tick.to_le_bytes())?;
+    writer.write_all(&snapshot.seconds.to_le_bytes())?;
+    writer.write_all(&(snapshot.prey_count as u32).to_le_bytes())?;
+    writer.write_all(&(snapshot.predator_count as u32).to_le_bytes())?;
+    writer.write_all(&snapshot.environment_energy.to_le_bytes())?;
+    writer.write_all(&(snapshot.creatures.len() as u32).to_le_bytes())?;
+
+    for creature in snapshot.creatures.iter() {
+        writer.write_all(&creature.id.to_le_bytes())?;
+    }
+    for creature in snapshot.creatures.iter() {
+        let species: u8 = match creature.species {
+            Species::Prey => 0,
+            Species::Predator => 1,
+        };
+        writer.write_all(&[species])?;
+    }
+    for creature in snapshot.creatures.iter() {
+        writer.write_all(&creature.x.to_le_bytes())?;
+    }
+    for creature in snapshot.creatures.iter() {
+        writer.write_all(&creature.y.to_le_bytes())?;
+    }
+    for creature in snapshot.creatures.iter() {
+        writer.write_all(&creature.energy.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+// Hands out the bytes of a binary snapshot in order, erroring if it ends too soon
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.bytes.len() < N {
+            return Err(invalid_data("The snapshot ends too soon".to_string()));
+        }
+
+        let (taken, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(taken.try_into().unwrap())
+    }
+}
+
+fn read_binary(bytes: &[u8]) -> io::Result<SimSnapshot> {
+    let mut reader = ByteReader { bytes };
+
+    if &reader.take::<6>()? != BINARY_MAGIC {
+        return Err(invalid_data("This isn't a binary snapshot".to_string()));
+    }
+    let [version] = reader.take::<1>()?;
+    if version != BINARY_VERSION {
+        return Err(invalid_data(format!(
+            "Binary snapshot version {} isn't supported, only {} is",
+            version, BINARY_VERSION
+        )));
+    }
+
+    let tick = u64::from_le_bytes(reader.take()?);
+    let seconds = f64::from_le_bytes(reader.take()?);
+    let prey_count = u32::from_le_bytes(reader.take()?) as usize;
+    let predator_count = u32::from_le_bytes(reader.take()?) as usize;
+    let environment_energy = i32::from_le_bytes(reader.take()?);
+    let count = u32::from_le_bytes(reader.take()?) as usize;
+
+    let mut creatures: Vec<CreatureSnapshot> = Vec::with_capacity(count);
+    for _index in 0..count {
+        creatures.push(CreatureSnapshot {
+            entity: Entity::PLACEHOLDER,
+            id: u64::from_le_bytes(reader.take()?),
+            species: Species::Prey,
+            x: 0.0,
+            y: 0.0,
+            energy: 0,
+        });
+    }
+    for creature in creatures.iter_mut() {
+        creature.species = match reader.take::<1>()? {
+            [0] => Species::Prey,
+            [1] => Species::Predator,
+            [other] => return Err(invalid_data(format!("Unknown species {}", other))),
+        };
+    }
+    for creature in creatures.iter_mut() {
+        creature.x = f32::from_le_bytes(reader.take()?);
+    }
+    for creature in creatures.iter_mut() {
+        creature.y = f32::from_le_bytes(reader.take()?);
+    }
+    for creature in creatures.iter_mut() {
+        creature.energy = i32::from_le_bytes(reader.take()?);
+    }
+
+    Ok(SimSnapshot {
+        tick,
+        seconds,
+        prey_count,
+        predator_count,
+        environment_energy,
+        creatures,
+    })
+}
@@ -0,0 +1,536 @@
+/*
+    This file includes all systems & child functions used for
+    quickly finding which predators or prey are near a spot, instead of checking every single one.
+
+    Examples:
+    - The SpatialIndex trait that the detection & mating systems look up neighbors through
+    - A uniform grid and a quadtree that can be swapped between in the settings
+    - Functions that rebuild the indexes every frame (or only when something moved) and draw them
+      for debugging
+    - Functions that count the creatures around the cursor, checked against going through every one
+*/
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::position_systems::PositionSize;
+use crate::{Predator, Prey, Settings};
+
+pub trait SpatialIndex: Send + Sync {
+    // Throws away whatever was in the index and fills it with these points
+    fn build(&mut self, points: &[(Entity, Vec2)]);
+
+    // Adds every entity within radius of center to results
+    fn query_radius(&self, center: Vec2, radius: f32, results: &mut Vec<Entity>);
+
+    // Draws the shape of the index, used to check it's splitting up the world sensibly
+    fn draw(&self, gizmos: &mut Gizmos, color: Color);
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Debug)]
+pub enum SpatialIndexKind {
+    Grid,
+    QuadTree,
+}
+
+impl FromStr for SpatialIndexKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "grid" => Ok(SpatialIndexKind::Grid),
+            "quadtree" => Ok(SpatialIndexKind::QuadTree),
+            _ => Err(format!("Unknown spatial index \"{}\"", value)),
+        }
+    }
+}
+
+// Splits the world into equal square cells, which is fast when creatures are spread out evenly
+pub struct UniformGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Entity, Vec2)>>,
+}
+
+impl UniformGrid {
+    pub fn new(cell_size: f32) -> Self {
+        UniformGrid {
+            cell_size: cell_size.max(1.0),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+impl SpatialIndex for UniformGrid {
+    fn build(&mut self, points: &[(Entity, Vec2)]) {
+        self.cells.clear();
+
+        for (entity, position) in points.iter() {
+            let cell = self.cell(*position);
+            self.cells
+                .entry(cell)
+                .or_default()
+                .push((*entity, *position));
+        }
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, results: &mut Vec<Entity>) {
+        let (min_x, min_y) = self.cell(center - Vec2::splat(radius));
+        let (max_x, max_y) = self.cell(center + Vec2::splat(radius));
+
+        for cell_x in min_x..=max_x {
+            for cell_y in min_y..=max_y {
+                if let Some(cell) = self.cells.get(&(cell_x, cell_y)) {
+                    for (entity, position) in cell.iter() {
+                        if position.distance(center) <= radius {
+                            results.push(*entity);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(&self, gizmos: &mut Gizmos, color: Color) {
+        for (cell_x, cell_y) in self.cells.keys() {
+            let center = Vec2::new(
+                (*cell_x as f32 + 0.5) * self.cell_size,
+                (*cell_y as f32 + 0.5) * self.cell_size,
+            );
+            gizmos.rect_2d(center, Vec2::splat(self.cell_size), color);
+        }
+    }
+}
+
+// A node only splits this many times so stacks of creatures on the same spot can't split forever
+const QUADTREE_MAX_DEPTH: u32 = 12;
+
+struct QuadNode {
+    min: Vec2,
+    max: Vec2,
+    points: Vec<(Entity, Vec2)>,
+    children: Option<Box<[QuadNode; 4]>>,
+}
+
+impl QuadNode {
+    fn new(min: Vec2, max: Vec2) -> Self {
+        QuadNode {
+            min,
+            max,
+            points: Vec::new(),
+            children: None,
+        }
+    }
+
+    // Children are ordered bottom left, bottom right, top left, top right
+    fn child_index(&self, position: Vec2) -> usize {
+        let center = (self.min + self.max) / 2.0;
+        (position.x >= center.x) as usize + 2 * (position.y >= center.y) as usize
+    }
+
+    fn insert(&mut self, entity: Entity, position: Vec2, capacity: usize, depth: u32) {
+        let index = self.child_index(position);
+        if let Some(children) = self.children.as_mut() {
+            children[index].insert(entity, position, capacity, depth + 1);
+            return;
+        }
+
+        self.points.push((entity, position));
+
+        if self.points.len() > capacity && depth < QUADTREE_MAX_DEPTH {
+            let center = (self.min + self.max) / 2.0;
+            let mut children = Box::new([
+                QuadNode::new(self.min, center),
+                QuadNode::new(
+                    Vec2::new(center.x, self.min.y),
+                    Vec2::new(self.max.x, center.y),
+                ),
+                QuadNode::new(
+                    Vec2::new(self.min.x, center.y),
+                    Vec2::new(center.x, self.max.y),
+                ),
+                QuadNode::new(center, self.max),
+            ]);
+
+            for (point_entity, point_position) in std::mem::take(&mut self.points) {
+                let index = self.child_index(point_position);
+                children[index].insert(point_entity, point_position, capacity, depth + 1);
+            }
+            self.children = Some(children);
+        }
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, results: &mut Vec<Entity>) {
+        // Skip this whole node if the circle doesn't touch it
+        let closest = center.clamp(self.min, self.max);
+        if closest.distance(center) > radius {
+            return;
+        }
+
+        for (entity, position) in self.points.iter() {
+            if position.distance(center) <= radius {
+                results.push(*entity);
+            }
+        }
+
+        if let Some(children) = self.children.as_ref() {
+            for child in children.iter() {
+                child.query_radius(center, radius, results);
+            }
+        }
+    }
+
+    fn draw(&self, gizmos: &mut Gizmos, color: Color) {
+        gizmos.rect_2d((self.min + self.max) / 2.0, self.max - self.min, color);
+
+        if let Some(children) = self.children.as_ref() {
+            for child in children.iter() {
+                child.draw(gizmos, color);
+            }
+        }
+    }
+}
+
+// Splits crowded areas into smaller and smaller squares, which is fast when
+// creatures are bunched up with lots of empty space between them
+pub struct QuadTree {
+    capacity: usize,
+    root: Option<QuadNode>,
+}
+
+impl QuadTree {
+    pub fn new(capacity: usize) -> Self {
+        QuadTree {
+            capacity: capacity.max(1),
+            root: None,
+        }
+    }
+}
+
+impl SpatialIndex for QuadTree {
+    fn build(&mut self, points: &[(Entity, Vec2)]) {
+        if points.is_empty() {
+            self.root = None;
+            return;
+        }
+
+        // Fit the tree around the points, as creatures can be slightly outside the window
+        let mut min = points[0].1;
+        let mut max = points[0].1;
+        for (_, position) in points.iter() {
+            min = min.min(*position);
+            max = max.max(*position);
+        }
+
+        let mut root = QuadNode::new(min, max);
+        for (entity, position) in points.iter() {
+            root.insert(*entity, *position, self.capacity, 0);
+        }
+        self.root = Some(root);
+    }
+
+    fn query_radius(&self, center: Vec2, radius: f32, results: &mut Vec<Entity>) {
+        if let Some(root) = self.root.as_ref() {
+            root.query_radius(center, radius, results);
+        }
+    }
+
+    fn draw(&self, gizmos: &mut Gizmos, color: Color) {
+        if let Some(root) = self.root.as_ref() {
+            root.draw(gizmos, color);
+        }
+    }
+}
+
+fn new_spatial_index(settings: &Settings) -> Box<dyn SpatialIndex> {
+    match settings.spatial_index {
+        SpatialIndexKind::Grid => Box::new(UniformGrid::new(settings.spatial_cell_size)),
+        SpatialIndexKind::QuadTree => Box::new(QuadTree::new(settings.quadtree_capacity)),
+    }
+}
+
+// Separate indexes for each species, as systems only ever look for one or the other
+#[derive(Resource)]
+pub struct SpatialIndexes {
+    pub prey: Box<dyn SpatialIndex>,
+    pub predators: Box<dyn SpatialIndex>,
+    // What the indexes were made with, so they can be remade if the settings change
+    kind: SpatialIndexKind,
+    cell_size: f32,
+    capacity: usize,
+    // The points the indexes were last built from, so with cache_spatial_index a rebuild can be
+    // skipped when nothing has moved
+    prey_points: Vec<(Entity, Vec2)>,
+    predator_points: Vec<(Entity, Vec2)>,
+}
+
+impl SpatialIndexes {
+    pub fn new(settings: &Settings) -> Self {
+        SpatialIndexes {
+            prey: new_spatial_index(settings),
+            predators: new_spatial_index(settings),
+            kind: settings.spatial_index,
+            cell_size: settings.spatial_cell_size,
+            capacity: settings.quadtree_capacity,
+            prey_points: Vec::new(),
+            predator_points: Vec::new(),
+        }
+    }
+}
+
+// With cache_spatial_index the indexes are only rebuilt when a creature has moved, arrived or
+// left since the last time, so nothing is redone while the simulation sits still. Anything that
+// happened during a tick is seen by the next one, so stepping once always rebuilds. Systems often
+// write a position back without changing it, so it's also checked against the last build
+#[allow(clippy::type_complexity)]
+pub fn rebuild_spatial_indexes(
+    mut indexes: ResMut<SpatialIndexes>,
+    settings: Res<Settings>,
+    prey_query: Query<(Entity, &PositionSize), With<Prey>>,
+    predator_query: Query<(Entity, &PositionSize), With<Predator>>,
+    moved: Query<(), (Changed<PositionSize>, Or<(With<Prey>, With<Predator>)>)>,
+    mut removed_preys: RemovedComponents<Prey>,
+    mut removed_predators: RemovedComponents<Predator>,
+) {
+    let _span = debug_span!(target: "profiling", "rebuild_spatial_indexes").entered();
+
+    let remade = indexes.kind != settings.spatial_index
+        || indexes.cell_size != settings.spatial_cell_size
+        || indexes.capacity != settings.quadtree_capacity;
+    if remade {
+        *indexes = SpatialIndexes::new(&settings);
+    }
+
+    // The removals are always read, so the same ones aren't seen again next time
+    let removed = removed_preys.read().count() + removed_predators.read().count() > 0;
+    if settings.cache_spatial_index && !remade && !removed && moved.is_empty() {
+        return;
+    }
+
+    let prey_points: Vec<(Entity, Vec2)> = prey_query
+        .iter()
+        .map(|(entity, position_size)| (entity, Vec2::new(position_size.x, position_size.y)))
+        .collect();
+    let predator_points: Vec<(Entity, Vec2)> = predator_query
+        .iter()
+        .map(|(entity, position_size)| (entity, Vec2::new(position_size.x, position_size.y)))
+        .collect();
+
+    if settings.cache_spatial_index
+        && !remade
+        && prey_points == indexes.prey_points
+        && predator_points == indexes.predator_points
+    {
+        return;
+    }
+
+    indexes.prey.build(&prey_points);
+    indexes.predators.build(&predator_points);
+    indexes.prey_points = prey_points;
+    indexes.predator_points = predator_points;
+}
+
+pub fn draw_spatial_indexes(
+    mut gizmos: Gizmos,
+    indexes: Res<SpatialIndexes>,
+    settings: Res<Settings>,
+) {
+    if !settings.show_spatial_index {
+        return;
+    }
+
+    indexes
+        .prey
+        .draw(&mut gizmos, settings.prey_color.with_alpha(0.15));
+    indexes
+        .predators
+        .draw(&mut gizmos, settings.predator_color.with_alpha(0.15));
+}
+
+// How many of the points are within radius of center, found the slow way to check the index against
+fn brute_force_count(points: &[(Entity, Vec2)], center: Vec2, radius: f32) -> usize {
+    points
+        .iter()
+        .filter(|(_, position)| position.distance(center) <= radius)
+        .count()
+}
+
+// Shows how many prey a predator at the cursor would see, and how many predators a prey there would
+// see. A fresh index of the kind in the settings is built from where everyone is right now, as the
+// simulation's own indexes were built before this tick's movement, and each count is checked
+// against going through every creature
+#[allow(clippy::too_many_arguments)]
+pub fn probe_spatial_indexes(
+    mut contexts: EguiContexts,
+    mut gizmos: Gizmos,
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    prey_query: Query<(Entity, &PositionSize), With<Prey>>,
+    predator_query: Query<(Entity, &PositionSize), With<Predator>>,
+) {
+    if !settings.spatial_query_debug || contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(cursor_position) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+
+    let prey_points: Vec<(Entity, Vec2)> = prey_query
+        .iter()
+        .map(|(entity, position_size)| (entity, Vec2::new(position_size.x, position_size.y)))
+        .collect();
+    let predator_points: Vec<(Entity, Vec2)> = predator_query
+        .iter()
+        .map(|(entity, position_size)| (entity, Vec2::new(position_size.x, position_size.y)))
+        .collect();
+
+    let mut indexes = SpatialIndexes::new(&settings);
+    indexes.prey.build(&prey_points);
+    indexes.predators.build(&predator_points);
+
+    let mut prey_found = Vec::new();
+    indexes.prey.query_radius(
+        cursor_position,
+        settings.predator_detection_range,
+        &mut prey_found,
+    );
+    let mut predators_found = Vec::new();
+    indexes.predators.query_radius(
+        cursor_position,
+        settings.prey_detection_range,
+        &mut predators_found,
+    );
+
+    let prey_expected = brute_force_count(
+        &prey_points,
+        cursor_position,
+        settings.predator_detection_range,
+    );
+    let predators_expected = brute_force_count(
+        &predator_points,
+        cursor_position,
+        settings.prey_detection_range,
+    );
+
+    if settings.spatial_query_circle {
+        gizmos.circle_2d(
+            cursor_position,
+            settings.predator_detection_range,
+            settings.prey_color.with_alpha(0.5),
+        );
+        gizmos.circle_2d(
+            cursor_position,
+            settings.prey_detection_range,
+            settings.predator_color.with_alpha(0.5),
+        );
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::show_tooltip_at_pointer(
+        ctx,
+        egui::LayerId::background(),
+        egui::Id::new("spatial_query"),
+        |ui| {
+            for (name, found, expected) in [
+                ("Prey", prey_found.len(), prey_expected),
+                ("Predators", predators_found.len(), predators_expected),
+            ] {
+                if found == expected {
+                    ui.label(format!("{}: {}", name, found));
+                } else {
+                    ui.colored_label(
+                        egui::Color32::LIGHT_RED,
+                        format!(
+                            "{}: {} (checking every one found {})",
+                            name, found, expected
+                        ),
+                    );
+                }
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // Some spread out and some bunched up, with a few on exactly the same spot
+    fn points(rng: &mut StdRng) -> Vec<(Entity, Vec2)> {
+        let mut points: Vec<(Entity, Vec2)> = (0..300)
+            .map(|index| {
+                let position = if index % 3 == 0 {
+                    Vec2::new(rng.gen_range(-20.0..20.0), rng.gen_range(-20.0..20.0))
+                } else {
+                    Vec2::new(rng.gen_range(-400.0..400.0), rng.gen_range(-300.0..300.0))
+                };
+                (Entity::from_raw(index), position)
+            })
+            .collect();
+        for index in 300..310 {
+            points.push((Entity::from_raw(index), Vec2::new(100.0, 100.0)));
+        }
+        points
+    }
+
+    #[test]
+    fn queries_find_the_same_creatures_as_checking_every_one() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let points = points(&mut rng);
+        let indexes: [Box<dyn SpatialIndex>; 3] = [
+            Box::new(UniformGrid::new(50.0)),
+            Box::new(UniformGrid::new(7.0)),
+            Box::new(QuadTree::new(4)),
+        ];
+
+        for mut index in indexes {
+            index.build(&points);
+
+            for _ in 0..200 {
+                let center = Vec2::new(rng.gen_range(-450.0..450.0), rng.gen_range(-350.0..350.0));
+                let radius = rng.gen_range(0.0..150.0);
+
+                let mut found = Vec::new();
+                index.query_radius(center, radius, &mut found);
+                found.sort();
+                let mut expected: Vec<Entity> = points
+                    .iter()
+                    .filter(|(_, position)| position.distance(center) <= radius)
+                    .map(|(entity, _)| *entity)
+                    .collect();
+                expected.sort();
+
+                assert_eq!(found, expected);
+                assert_eq!(found.len(), brute_force_count(&points, center, radius));
+            }
+
+            // Right on the edge of the radius counts, and so does being right on the center
+            let mut found = Vec::new();
+            index.query_radius(Vec2::new(100.0, 50.0), 50.0, &mut found);
+            assert!(found.contains(&Entity::from_raw(305)));
+            found.clear();
+            index.query_radius(Vec2::new(100.0, 100.0), 0.0, &mut found);
+            assert_eq!(found.len(), 10);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+/*
+    This file includes everything used for
+    giving every creature an id that's never reused, unlike Bevy's entity ids.
+
+    Examples:
+    - The StableId component, numbered from a counter as creatures are spawned
+    - The counter itself, which is kept ahead of any ids that are put back onto creatures
+*/
+
+use bevy::ecs::{component::ComponentId, world::DeferredWorld};
+use bevy::prelude::*;
+
+// Bevy reuses an entity's index once it's despawned, so anything that has to keep pointing at the
+// same creature over time (mating partners, logs, recorded snapshots, the selection) uses this.
+// Ids start at 1, a StableId of 0 is replaced with the next free id as soon as it's added.
+#[derive(Reflect, Component, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[reflect(Component)]
+#[component(on_add = assign_stable_id)]
+pub struct StableId(pub u64);
+
+// The id the next creature will get
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct NextStableId(pub u64);
+
+impl Default for NextStableId {
+    fn default() -> Self {
+        NextStableId(1)
+    }
+}
+
+// Creatures put back with the id they already had (like when they're restored from a saved
+// world) keep it, and the counter is moved past it so no new creature can be given it too
+fn assign_stable_id(mut world: DeferredWorld, entity: Entity, _component_id: ComponentId) {
+    let Some(existing) = world.get::<StableId>(entity).copied() else {
+        return;
+    };
+
+    let mut next = world.resource_mut::<NextStableId>();
+    if existing.0 == 0 {
+        let id = next.0;
+        next.0 += 1;
+
+        if let Some(mut stable_id) = world.get_mut::<StableId>(entity) {
+            stable_id.0 = id;
+        }
+    } else if existing.0 >= next.0 {
+        next.0 = existing.0 + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_snapshot, read_settings, save_snapshot, SimApi};
+
+    #[test]
+    fn ids_survive_saving_and_loading() {
+        let mut settings = read_settings();
+        settings.seed = 1;
+        settings.prey_population = 20;
+        settings.predator_population = 5;
+        settings.metrics_output = String::new();
+        let mut api = SimApi::new(settings);
+        api.step(10);
+        let snapshot = api.snapshot();
+        let ids: Vec<u64> = snapshot
+            .creatures
+            .iter()
+            .map(|creature| creature.id)
+            .collect();
+        assert_eq!(ids.len(), 25);
+
+        let folder = std::env::temp_dir().join("predator_prey_stable_id_test");
+        std::fs::create_dir_all(&folder).unwrap();
+        for file in ["snapshot.json", "snapshot.bin"] {
+            let path = folder.join(file);
+            save_snapshot(&path, &snapshot).unwrap();
+            let loaded = load_snapshot(&path).unwrap();
+            let loaded_ids: Vec<u64> = loaded
+                .creatures
+                .iter()
+                .map(|creature| creature.id)
+                .collect();
+            assert_eq!(loaded_ids, ids, "{}", file);
+        }
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        // Put back onto creatures, they keep their ids and new ones carry on after them
+        let mut world = World::new();
+        world.init_resource::<NextStableId>();
+        for id in ids.iter().rev() {
+            world.spawn(StableId(*id));
+        }
+        let mut restored: Vec<u64> = world
+            .query::<&StableId>()
+            .iter(&world)
+            .map(|id| id.0)
+            .collect();
+        restored.sort();
+        assert_eq!(restored, ids);
+
+        let newborn = world.spawn(StableId::default()).id();
+        assert_eq!(
+            world.get::<StableId>(newborn).unwrap().0,
+            ids.iter().max().unwrap() + 1
+        );
+    }
+}
@@ -0,0 +1,402 @@
+/*
+    This file includes all systems & child functions used for
+    sweeping one setting across a range of values, running the simulation headless for each one.
+
+    Examples:
+    - Functions that run the headless copies on a background thread so the window keeps running
+    - Functions that boil each run down to outcomes like the mean populations & time to extinction
+    - The sweep panel, with a table & plot of the results and exporting them to a CSV file
+*/
+
+use bevy::prelude::*;
+use bevy::reflect::Struct;
+use bevy_egui::{egui, EguiContexts};
+use egui_plot::{Legend, Line, Plot, PlotPoints, Points};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::render_systems::to_color32;
+use crate::{PopulationHistory, Settings, SimApi};
+
+#[derive(Clone, Debug)]
+pub struct SweepResult {
+    pub value: f64,
+    pub mean_prey: f64,
+    pub mean_predators: f64,
+    // Simulated seconds until the first species died out, None if both lasted the whole run
+    pub extinction_seconds: Option<f64>,
+}
+
+// What the background thread sends back
+enum SweepMessage {
+    Result(SweepResult),
+    Finished,
+}
+
+struct RunningSweep {
+    // A Receiver can't be shared between threads by itself, which resources have to be
+    receiver: Mutex<Receiver<SweepMessage>>,
+    cancelled: Arc<AtomicBool>,
+    total_runs: u32,
+}
+
+#[derive(Resource)]
+pub struct SweepPanel {
+    parameter: String,
+    from: f64,
+    to: f64,
+    steps: u32,
+    ticks: u32,
+    output: String,
+    results: Vec<SweepResult>,
+    running: Option<RunningSweep>,
+    status: String,
+}
+
+impl Default for SweepPanel {
+    fn default() -> Self {
+        SweepPanel {
+            parameter: "predator_detection_range".to_string(),
+            from: 25.0,
+            to: 100.0,
+            steps: 4,
+            ticks: 3600,
+            output: "sweep.csv".to_string(),
+            results: Vec::new(),
+            running: None,
+            status: String::new(),
+        }
+    }
+}
+
+// Every setting that's a plain number, which are the ones that can be swept
+fn numeric_settings(settings: &Settings) -> Vec<String> {
+    (0..settings.field_len())
+        .filter(|index| {
+            settings.field_at(*index).is_some_and(|field| {
+                field.try_downcast_ref::<f32>().is_some()
+                    || field.try_downcast_ref::<i32>().is_some()
+                    || field.try_downcast_ref::<u32>().is_some()
+            })
+        })
+        .filter_map(|index| settings.name_at(index).map(|name| name.to_string()))
+        .collect()
+}
+
+// Whole number settings are rounded to the closest value they can hold
+fn set_numeric_setting(settings: &mut Settings, name: &str, value: f64) -> Result<(), String> {
+    let field = settings
+        .field_mut(name)
+        .ok_or(format!("There's no setting called {}", name))?;
+
+    if let Some(field) = field.try_downcast_mut::<f32>() {
+        *field = value as f32;
+    } else if let Some(field) = field.try_downcast_mut::<i32>() {
+        *field = value.round() as i32;
+    } else if let Some(field) = field.try_downcast_mut::<u32>() {
+        *field = value.round().max(0.0) as u32;
+    } else {
+        return Err(format!("{} isn't a number", name));
+    }
+
+    Ok(())
+}
+
+// The values to try, evenly spaced from from to to, including both ends
+fn sweep_values(from: f64, to: f64, steps: u32) -> Vec<f64> {
+    if steps <= 1 {
+        return vec![from];
+    }
+
+    (0..steps)
+        .map(|step| from + (to - from) * step as f64 / (steps - 1) as f64)
+        .collect()
+}
+
+fn summarize_run(value: f64, history: &PopulationHistory) -> SweepResult {
+    let mean = |samples: &[[f64; 2]]| {
+        if samples.is_empty() {
+            0.0
+        } else {
+            samples.iter().map(|[_, count]| count).sum::<f64>() / samples.len() as f64
+        }
+    };
+
+    let extinction_seconds = history
+        .prey_population
+        .iter()
+        .zip(history.predator_population.iter())
+        .find(|([_, prey], [_, predators])| *prey == 0.0 || *predators == 0.0)
+        .map(|([seconds, _], _)| *seconds);
+
+    SweepResult {
+        value,
+        mean_prey: mean(&history.prey_population),
+        mean_predators: mean(&history.predator_population),
+        extinction_seconds,
+    }
+}
+
+// Each value gets a fresh headless copy of the simulation with the same seed, so the only
+// difference between the runs is the setting being swept
+fn start_sweep(panel: &mut SweepPanel, settings: &Settings) -> Result<(), String> {
+    let values = sweep_values(panel.from, panel.to, panel.steps);
+
+    // Check the setting can be changed before starting any runs
+    let mut base_settings = settings.clone();
+    set_numeric_setting(&mut base_settings, &panel.parameter, panel.from)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let thread_cancelled = cancelled.clone();
+    let parameter = panel.parameter.clone();
+    let ticks = panel.ticks;
+
+    thread::spawn(move || {
+        for value in values {
+            if thread_cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let mut run_settings = base_settings.clone();
+            // Already checked above, so this can't fail
+            let _ = set_numeric_setting(&mut run_settings, &parameter, value);
+            // Only the window runs ensembles, the sweep's copies just run for the ticks asked for
+            run_settings.ensemble_runs = 0;
+            run_settings.metrics_output = String::new();
+            run_settings.record_snapshots = false;
+
+            let mut api = SimApi::new(run_settings);
+            for _tick in 0..ticks {
+                if thread_cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+                api.step(1);
+            }
+            // A run cut short would look like it ended early, so it's left out
+            if thread_cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let result =
+                summarize_run(value, api.app_mut().world().resource::<PopulationHistory>());
+            if sender.send(SweepMessage::Result(result)).is_err() {
+                return;
+            }
+        }
+
+        let _ = sender.send(SweepMessage::Finished);
+    });
+
+    panel.results.clear();
+    panel.running = Some(RunningSweep {
+        receiver: Mutex::new(receiver),
+        cancelled,
+        total_runs: panel.steps.max(1),
+    });
+    Ok(())
+}
+
+fn export_results(results: &[SweepResult], parameter: &str, path: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writeln!(
+        writer,
+        "{},mean_prey,mean_predators,extinction_seconds",
+        parameter
+    )?;
+    for result in results.iter() {
+        // Runs where neither species died out leave the extinction time empty
+        let extinction = result
+            .extinction_seconds
+            .map(|seconds| format!("{:.3}", seconds))
+            .unwrap_or_default();
+        writeln!(
+            writer,
+            "{},{:.3},{:.3},{}",
+            result.value, result.mean_prey, result.mean_predators, extinction
+        )?;
+    }
+
+    writer.flush()
+}
+
+// Picks up results from the background thread as they come in
+pub fn collect_sweep_results(mut panel: ResMut<SweepPanel>) {
+    let Some(running) = panel.running.as_ref() else {
+        return;
+    };
+
+    let mut results = Vec::new();
+    let mut finished = false;
+    let mut failed = false;
+    if let Ok(receiver) = running.receiver.lock() {
+        loop {
+            match receiver.try_recv() {
+                Ok(SweepMessage::Result(result)) => results.push(result),
+                Ok(SweepMessage::Finished) => finished = true,
+                Err(TryRecvError::Empty) => break,
+                // The thread only hangs up without saying it's finished if a run panicked
+                Err(TryRecvError::Disconnected) => {
+                    failed = !finished;
+                    break;
+                }
+            }
+        }
+    }
+    let cancelled = running.cancelled.load(Ordering::Relaxed);
+
+    panel.results.extend(results);
+    if failed {
+        panel.running = None;
+        panel.status = format!("The sweep failed after {} runs", panel.results.len());
+    } else if finished {
+        panel.running = None;
+        panel.status = if cancelled {
+            format!("Cancelled after {} runs", panel.results.len())
+        } else {
+            format!("Finished {} runs", panel.results.len())
+        };
+    }
+}
+
+pub fn sweep_ui(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<SweepPanel>,
+    settings: Res<Settings>,
+) {
+    let panel = panel.as_mut();
+
+    egui::Window::new("Parameter Sweep")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let running = panel.running.is_some();
+
+            ui.add_enabled_ui(!running, |ui| {
+                egui::ComboBox::from_label("Setting")
+                    .selected_text(panel.parameter.clone())
+                    .show_ui(ui, |ui| {
+                        for name in numeric_settings(&settings) {
+                            ui.selectable_value(&mut panel.parameter, name.clone(), name);
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    ui.label("From");
+                    ui.add(egui::DragValue::new(&mut panel.from).speed(0.1));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut panel.to).speed(0.1));
+                    ui.label("in");
+                    ui.add(egui::DragValue::new(&mut panel.steps).range(1..=100));
+                    ui.label("steps");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Ticks per run");
+                    ui.add(egui::DragValue::new(&mut panel.ticks).range(1..=1_000_000));
+                });
+            });
+
+            ui.horizontal(|ui| {
+                if let Some(running) = panel.running.as_ref() {
+                    ui.label(format!(
+                        "Running {}/{}",
+                        panel.results.len() + 1,
+                        running.total_runs
+                    ));
+                    if ui.button("Cancel").clicked() {
+                        running.cancelled.store(true, Ordering::Relaxed);
+                    }
+                } else if ui.button("Run sweep").clicked() {
+                    panel.status = match start_sweep(panel, &settings) {
+                        Ok(()) => String::new(),
+                        Err(error) => error,
+                    };
+                }
+            });
+
+            if !panel.status.is_empty() {
+                ui.label(panel.status.clone());
+            }
+
+            if panel.results.is_empty() {
+                return;
+            }
+
+            ui.separator();
+
+            egui::Grid::new("sweep_results")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(panel.parameter.clone());
+                    ui.label("Mean prey");
+                    ui.label("Mean predators");
+                    ui.label("Extinct after");
+                    ui.end_row();
+
+                    for result in panel.results.iter() {
+                        ui.label(format!("{:.3}", result.value));
+                        ui.label(format!("{:.1}", result.mean_prey));
+                        ui.label(format!("{:.1}", result.mean_predators));
+                        ui.label(match result.extinction_seconds {
+                            Some(seconds) => format!("{:.1}s", seconds),
+                            None => "Never".to_string(),
+                        });
+                        ui.end_row();
+                    }
+                });
+
+            let prey_points: Vec<[f64; 2]> = panel
+                .results
+                .iter()
+                .map(|result| [result.value, result.mean_prey])
+                .collect();
+            let predator_points: Vec<[f64; 2]> = panel
+                .results
+                .iter()
+                .map(|result| [result.value, result.mean_predators])
+                .collect();
+
+            Plot::new("sweep_plot")
+                .legend(Legend::default())
+                .height(150.0)
+                .x_axis_label(panel.parameter.clone())
+                .y_axis_label("Mean population")
+                .show(ui, |plot_ui| {
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(prey_points.clone()))
+                            .name("Prey")
+                            .color(to_color32(settings.prey_color)),
+                    );
+                    plot_ui.points(
+                        Points::new(PlotPoints::from(prey_points))
+                            .radius(3.0)
+                            .color(to_color32(settings.prey_color)),
+                    );
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(predator_points.clone()))
+                            .name("Predators")
+                            .color(to_color32(settings.predator_color)),
+                    );
+                    plot_ui.points(
+                        Points::new(PlotPoints::from(predator_points))
+                            .radius(3.0)
+                            .color(to_color32(settings.predator_color)),
+                    );
+                });
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut panel.output);
+                if ui.button("Export CSV").clicked() {
+                    panel.status =
+                        match export_results(&panel.results, &panel.parameter, &panel.output) {
+                            Ok(()) => format!("Saved to {}", panel.output),
+                            Err(error) => format!("Couldn't save {}: {}", panel.output, error),
+                        };
+                }
+            });
+        });
+}
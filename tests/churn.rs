@@ -0,0 +1,56 @@
+/*
+    A stress test for a world where creatures are born & die all the time. It runs the scenario for
+    a while and checks it got through without panicking, to catch problems with despawned
+    creatures' entities being given to newborns. benches/churn.rs times the same scenario.
+
+    It takes a while without optimizations, so run it with: cargo test --release -- --ignored
+*/
+
+use predator_prey::{read_settings, NextStableId, Settings, SimApi};
+
+// Long enough for thousands of births & deaths
+const STRESS_TICKS: u32 = 600;
+
+// Creatures with little energy that breed as soon as they can, and a fifth of the prey culled
+// every 10 ticks, so the populations stay about the same size while being replaced constantly
+fn churn_settings() -> Settings {
+    let mut settings = read_settings();
+    settings.seed = 1;
+    settings.prey_population = 300;
+    settings.predator_population = 100;
+    settings.prey_life = 400;
+    settings.predator_life = 400;
+    settings.prey_reproduction_energy = 200;
+    settings.predator_reproduction_energy = 200;
+    settings.predator_energy_loss = 4;
+    settings.predation_efficiency = 1.0;
+    settings.max_entities = 800;
+    settings.prevent_extinction = true;
+    settings.min_prey = 100;
+    settings.min_predator = 30;
+    settings.perturbations = (1..=STRESS_TICKS / 10)
+        .map(|step| format!("{} cull prey 0.2", step * 10))
+        .collect::<Vec<String>>()
+        .join(";");
+    settings.metrics_output = String::new();
+    settings
+}
+
+#[test]
+#[ignore]
+fn creatures_keep_being_replaced() {
+    let mut api = SimApi::new(churn_settings());
+    api.step(STRESS_TICKS);
+
+    // Every creature ever spawned got the next stable id, so this is how many there have been
+    let spawned = api.app_mut().world().resource::<NextStableId>().0 - 1;
+    let snapshot = api.snapshot();
+    let alive = (snapshot.prey_count + snapshot.predator_count) as u64;
+    assert!(
+        spawned > alive * 5,
+        "Only {} creatures were spawned in {} ticks, with {} still alive",
+        spawned,
+        STRESS_TICKS,
+        alive
+    );
+}
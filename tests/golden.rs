@@ -0,0 +1,103 @@
+/*
+    Golden regression tests. Each scenario is a seeded headless run whose prey & predator counts at
+    fixed ticks are checked in under tests/golden, so any change to the simulation that shifts the
+    populations, even by one creature, shows up here instead of going unnoticed.
+
+    When a change is meant to shift them, rewrite the files with:
+    UPDATE_GOLDEN=1 cargo test --test golden
+    and check the new counts still look sensible before committing them.
+*/
+
+use std::fs;
+use std::path::PathBuf;
+
+use predator_prey::{read_settings, KillSharing, Settings, SimApi};
+
+// The counts are taken every SAMPLE_TICKS ticks up to GOLDEN_TICKS
+const GOLDEN_TICKS: u32 = 1200;
+const SAMPLE_TICKS: u32 = 200;
+
+// Everything in Settings.toml, but seeded and without writing any files
+fn scenario_settings(seed: u64) -> Settings {
+    let mut settings = read_settings();
+    settings.seed = seed;
+    settings.prey_population = 120;
+    settings.predator_population = 30;
+    settings.metrics_output = String::new();
+    settings.summary_output = String::new();
+    settings.record_snapshots = false;
+    settings
+}
+
+// The prey & predator counts at every sample, one "tick,prey,predators" line each
+fn run_scenario(settings: Settings) -> String {
+    let mut api = SimApi::new(settings);
+    let mut lines = vec!["tick,prey,predators".to_string()];
+    for tick in (SAMPLE_TICKS..=GOLDEN_TICKS).step_by(SAMPLE_TICKS as usize) {
+        api.step(SAMPLE_TICKS);
+        let snapshot = api.snapshot();
+        lines.push(format!(
+            "{},{},{}",
+            tick, snapshot.prey_count, snapshot.predator_count
+        ));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn check_golden(name: &str, settings: Settings) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.csv", name));
+    let counts = run_scenario(settings);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, counts).unwrap();
+        return;
+    }
+
+    let golden = fs::read_to_string(&path).unwrap_or_else(|error| {
+        panic!(
+            "Couldn't read {}, run with UPDATE_GOLDEN=1 to write it: {}",
+            path.display(),
+            error
+        )
+    });
+    assert_eq!(
+        counts,
+        golden.replace("\r\n", "\n"),
+        "The {} scenario's populations changed",
+        name
+    );
+}
+
+#[test]
+fn default_world() {
+    check_golden("default_world", scenario_settings(1));
+}
+
+#[test]
+fn grass_and_cover() {
+    let mut settings = scenario_settings(2);
+    settings.grass_enabled = true;
+    settings.cover_regions = "-200,100,120,80;250,-150,100,100".to_string();
+    settings.prey_seek_cover = true;
+    check_golden("grass_and_cover", settings);
+}
+
+#[test]
+fn per_entity_rng_with_shared_kills() {
+    let mut settings = scenario_settings(3);
+    settings.per_entity_rng = true;
+    settings.kill_sharing = KillSharing::Split;
+    settings.probabilistic_detection = true;
+    check_golden("per_entity_rng_with_shared_kills", settings);
+}
+
+#[test]
+fn realistic_reproduction_in_an_open_world() {
+    let mut settings = scenario_settings(4);
+    settings.realistic_reproduction = true;
+    settings.open_world = true;
+    check_golden("realistic_reproduction_in_an_open_world", settings);
+}